@@ -0,0 +1,281 @@
+use crate::cutlist::cut_duration_sec;
+use crate::job::{has_text, resolve_trim, text_from_option, JobPayload, INPUT_PLACEHOLDER};
+use crate::probe::{probe_media, resolve_executable_path};
+
+/// atempo is exact within this range; larger changes are chained.
+const ATEMPO_MIN: f64 = 0.5;
+const ATEMPO_MAX: f64 = 2.0;
+const DEFAULT_SILENCE_THRESHOLD_DB: f64 = -50.0;
+/// silenceremove only trims the end by cutting every pause at least this long, so it is set
+/// well above ordinary pauses; each cut keeps `TRAILING_SILENCE_KEEP_SEC` of silence.
+const TRAILING_SILENCE_MIN_SEC: f64 = 2.0;
+const TRAILING_SILENCE_KEEP_SEC: f64 = 0.5;
+/// `areverse` buffers the whole stream (about 1 GB of float PCM per stereo hour), so the
+/// reversed fade-out used with silence trimming is limited to short inputs.
+const MAX_REVERSE_SEC: f64 = 600.0;
+
+fn positive(value: Option<f64>, field: &str) -> Result<Option<f64>, String> {
+    match value {
+        None => Ok(None),
+        Some(number) if number.is_finite() && number > 0.0 => Ok(Some(number)),
+        Some(_) => Err(format!("{field} must be a positive number")),
+    }
+}
+
+/// Splits a tempo factor into `atempo` stages that each stay within 0.5–2.0.
+pub fn atempo_chain(tempo: f64) -> Result<Vec<String>, String> {
+    if !tempo.is_finite() || !(0.01..=100.0).contains(&tempo) {
+        return Err("tempo must be between 0.01 and 100".to_string());
+    }
+
+    let mut stages = Vec::new();
+    let mut remaining = tempo;
+    while remaining > ATEMPO_MAX {
+        stages.push(format!("atempo={ATEMPO_MAX}"));
+        remaining /= ATEMPO_MAX;
+    }
+    while remaining < ATEMPO_MIN {
+        stages.push(format!("atempo={ATEMPO_MIN}"));
+        remaining /= ATEMPO_MIN;
+    }
+
+    if (remaining - 1.0).abs() > 1e-9 || stages.is_empty() {
+        stages.push(format!("atempo={}", trim_number(remaining)));
+    }
+
+    Ok(stages)
+}
+
+fn trim_number(value: f64) -> String {
+    let text = format!("{value:.6}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn channel_filter(remap: &str) -> Result<Option<&'static str>, String> {
+    match remap {
+        "" => Ok(None),
+        // aformat lets the resampler apply its standard downmix matrix.
+        "mono" => Ok(Some("aformat=channel_layouts=mono")),
+        "stereo" => Ok(Some("aformat=channel_layouts=stereo")),
+        "swap" => Ok(Some("channelmap=map=FR-FL|FL-FR")),
+        "left" => Ok(Some("pan=mono|c0=FL")),
+        "right" => Ok(Some("pan=mono|c0=FR")),
+        other => Err(format!("Unsupported channelRemap: {other}")),
+    }
+}
+
+/// Length of the audio that reaches the filter chain: the cut list's total, or the trimmed
+/// length cut short by the end of the probed input.
+fn source_duration_sec(job: &JobPayload) -> Result<f64, String> {
+    if let Some(duration) = cut_duration_sec(job)? {
+        return Ok(duration);
+    }
+
+    let trim = resolve_trim(job)?;
+    let input_path = text_from_option(&job.input_path);
+    let has_input = has_text(&input_path) && input_path != INPUT_PLACEHOLDER;
+    if !has_input {
+        return trim
+            .length_sec()
+            .ok_or_else(|| "audioFadeOutSec 需要先选择输入文件或设置时长".to_string());
+    }
+
+    let configured_ffprobe = text_from_option(&job.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    // A failed probe only loses the clamp when the trim has a length of its own.
+    let total = probe_media(&ffprobe_path, configured_ffprobe.as_str(), &input_path)
+        .ok()
+        .and_then(|info| info.duration_sec);
+
+    trim.effective_length_sec(total)
+        .ok_or_else(|| "无法读取输入时长，不能计算淡出位置".to_string())
+}
+
+/// Builds the structured audio filters in a fixed order: silence trim, high/low-pass,
+/// channel remap, volume, fades, tempo, then the free-text `audio_filter`.
+///
+/// Fades run before tempo so their times are in source time.
+pub fn build_audio_filters(job: &JobPayload) -> Result<Vec<String>, String> {
+    let timed =
+        job.trim_silence.unwrap_or(false) || job.audio_fade_in_sec.is_some() || job.audio_fade_out_sec.is_some();
+    // With output-side seeking the graph still sees the audio before the start.
+    let time_offset = if timed { resolve_trim(job)?.filter_offset_sec() } else { 0.0 };
+    build_audio_filters_at(job, time_offset)
+}
+
+/// Same as `build_audio_filters`, for a graph whose audio starts `time_offset` seconds
+/// before the trimmed range, e.g. 0 after a plain input seek.
+pub fn build_audio_filters_at(job: &JobPayload, time_offset: f64) -> Result<Vec<String>, String> {
+    let mut filters = Vec::new();
+    let trim_silence = job.trim_silence.unwrap_or(false);
+
+    if trim_silence {
+        if time_offset > 0.0 {
+            return Err("trimSilence needs seekMode input when startTime is set".to_string());
+        }
+        let threshold = job.silence_threshold_db.unwrap_or(DEFAULT_SILENCE_THRESHOLD_DB);
+        if !threshold.is_finite() || threshold >= 0.0 {
+            return Err("silenceThresholdDb must be a negative number".to_string());
+        }
+
+        let threshold = trim_number(threshold);
+        filters.push(format!(
+            "silenceremove=start_periods=1:start_threshold={threshold}dB:stop_periods=-1:stop_threshold={threshold}dB:\
+             stop_duration={}:stop_silence={}",
+            trim_number(TRAILING_SILENCE_MIN_SEC),
+            trim_number(TRAILING_SILENCE_KEEP_SEC)
+        ));
+    }
+
+    if let Some(highpass) = positive(job.highpass_hz, "highpassHz")? {
+        filters.push(format!("highpass=f={}", trim_number(highpass)));
+    }
+
+    if let Some(lowpass) = positive(job.lowpass_hz, "lowpassHz")? {
+        filters.push(format!("lowpass=f={}", trim_number(lowpass)));
+    }
+
+    if let Some(filter) = channel_filter(text_from_option(&job.channel_remap).as_str())? {
+        filters.push(filter.to_string());
+    }
+
+    if let Some(gain) = job.volume_gain_db {
+        if !gain.is_finite() {
+            return Err("volumeGainDb must be a number".to_string());
+        }
+        if gain != 0.0 {
+            filters.push(format!("volume={}dB", trim_number(gain)));
+        }
+    }
+
+    if let Some(fade_in) = positive(job.audio_fade_in_sec, "audioFadeInSec")? {
+        filters.push(format!("afade=t=in:st={}:d={}", trim_number(time_offset), trim_number(fade_in)));
+    }
+
+    if let Some(fade_out) = positive(job.audio_fade_out_sec, "audioFadeOutSec")? {
+        if trim_silence {
+            // The trimmed length is unknown up front, so fade the reversed stream in instead.
+            if source_duration_sec(job)? > MAX_REVERSE_SEC {
+                return Err("audioFadeOutSec with trimSilence is limited to inputs up to 10 minutes".to_string());
+            }
+            filters.push("areverse".to_string());
+            filters.push(format!("afade=t=in:st=0:d={}", trim_number(fade_out)));
+            filters.push("areverse".to_string());
+        } else {
+            let start = time_offset + (source_duration_sec(job)? - fade_out).max(0.0);
+            filters.push(format!("afade=t=out:st={}:d={}", trim_number(start), trim_number(fade_out)));
+        }
+    }
+
+    if let Some(tempo) = job.tempo {
+        if (tempo - 1.0).abs() > 1e-9 {
+            filters.extend(atempo_chain(tempo)?);
+        }
+    }
+
+    let audio_filter = text_from_option(&job.audio_filter);
+    if has_text(&audio_filter) {
+        filters.push(audio_filter);
+    }
+
+    Ok(filters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atempo_chain_stays_within_supported_range() {
+        assert_eq!(atempo_chain(1.5).unwrap(), vec!["atempo=1.5"]);
+        assert_eq!(atempo_chain(3.0).unwrap(), vec!["atempo=2", "atempo=1.5"]);
+        assert_eq!(atempo_chain(4.0).unwrap(), vec!["atempo=2", "atempo=2"]);
+        assert_eq!(atempo_chain(0.3).unwrap(), vec!["atempo=0.5", "atempo=0.6"]);
+        assert!(atempo_chain(0.0).is_err());
+    }
+
+    #[test]
+    fn audio_filters_follow_defined_order() {
+        let job = JobPayload {
+            duration: Some("60".to_string()),
+            audio_filter: Some("aecho=0.8:0.9:40:0.3".to_string()),
+            tempo: Some(2.5),
+            audio_fade_out_sec: Some(3.0),
+            audio_fade_in_sec: Some(1.5),
+            volume_gain_db: Some(-3.0),
+            channel_remap: Some("mono".to_string()),
+            lowpass_hz: Some(12000.0),
+            highpass_hz: Some(80.0),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            build_audio_filters(&job).unwrap(),
+            vec![
+                "highpass=f=80",
+                "lowpass=f=12000",
+                "aformat=channel_layouts=mono",
+                "volume=-3dB",
+                "afade=t=in:st=0:d=1.5",
+                "afade=t=out:st=57:d=3",
+                "atempo=2",
+                "atempo=1.25",
+                "aecho=0.8:0.9:40:0.3",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fade_out_ends_with_the_input_when_the_trim_runs_past_it() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ffmpeg-gui-fade-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let fake_ffprobe = dir.join("ffprobe");
+        std::fs::write(&fake_ffprobe, "#!/bin/sh\nprintf '{\"format\":{\"duration\":\"100\"},\"streams\":[]}'\n")
+            .expect("write script");
+        std::fs::set_permissions(&fake_ffprobe, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+
+        let job = JobPayload {
+            ffprobe_path: Some(fake_ffprobe.to_string_lossy().to_string()),
+            input_path: Some("in.wav".to_string()),
+            start_time: Some("90".to_string()),
+            duration: Some("30".to_string()),
+            audio_fade_out_sec: Some(3.0),
+            ..Default::default()
+        };
+        let filters = build_audio_filters(&job);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(filters.unwrap(), vec!["afade=t=out:st=7:d=3"]);
+    }
+
+    #[test]
+    fn silence_trim_uses_reversed_fade_out() {
+        let job = JobPayload {
+            trim_silence: Some(true),
+            silence_threshold_db: Some(-45.0),
+            audio_fade_out_sec: Some(2.0),
+            duration: Some("60".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            build_audio_filters(&job).unwrap().join(","),
+            "silenceremove=start_periods=1:start_threshold=-45dB:stop_periods=-1:stop_threshold=-45dB:\
+             stop_duration=2:stop_silence=0.5,areverse,afade=t=in:st=0:d=2,areverse"
+        );
+
+        let long = JobPayload {
+            duration: Some("1:00:00".to_string()),
+            ..job.clone()
+        };
+        assert!(build_audio_filters(&long).is_err());
+        let no_fade = JobPayload {
+            audio_fade_out_sec: None,
+            ..long
+        };
+        assert!(!build_audio_filters(&no_fade).unwrap().iter().any(|filter| filter == "areverse"));
+    }
+}
@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cutlist::TimeRange;
+use crate::job::{has_text, text_from_option};
+use crate::probe::{probe_media, resolve_executable_path, run_command};
+use crate::template::format_seconds;
+
+const DEFAULT_NOISE_DB: f64 = -50.0;
+const DEFAULT_MIN_SILENCE_SEC: f64 = 0.5;
+const DEFAULT_MIN_BLACK_SEC: f64 = 0.5;
+const DEFAULT_PIXEL_THRESHOLD: f64 = 0.10;
+const DEFAULT_PADDING_SEC: f64 = 0.25;
+/// Detector timestamps are frame- or sample-aligned, so "starts at 0" needs some slack.
+const EDGE_TOLERANCE_SEC: f64 = 0.05;
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoTrimPayload {
+    pub input_path: Option<String>,
+    pub ffmpeg_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    /// Both default to on; either is skipped when the input has no stream of that kind.
+    pub detect_silence: Option<bool>,
+    pub detect_black: Option<bool>,
+    /// Level below which audio counts as silence, in dB.
+    pub noise_db: Option<f64>,
+    pub min_silence_sec: Option<f64>,
+    pub min_black_sec: Option<f64>,
+    /// blackdetect `pix_th`: how dark (0–1) a pixel must be to count as black.
+    pub pixel_threshold: Option<f64>,
+    /// Silence kept on each side of an interior cut so speech isn't clipped.
+    pub padding_sec: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoTrimResult {
+    pub duration_sec: f64,
+    pub silences: Vec<TimeRange>,
+    pub black_frames: Vec<TimeRange>,
+    /// Values for the job's `startTime` / `duration`; `None` when nothing needs trimming at that end.
+    pub start_time: Option<String>,
+    pub duration: Option<String>,
+    /// Parts of the trimmed range left after removing interior silences; usable as the job's `cutRanges`.
+    pub keep_ranges: Vec<TimeRange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AutoTrimSettings {
+    detect_silence: bool,
+    detect_black: bool,
+    noise_db: f64,
+    min_silence_sec: f64,
+    min_black_sec: f64,
+    pixel_threshold: f64,
+    padding_sec: f64,
+}
+
+impl AutoTrimSettings {
+    fn from_payload(payload: &AutoTrimPayload) -> Result<Self, String> {
+        let settings = Self {
+            detect_silence: payload.detect_silence.unwrap_or(true),
+            detect_black: payload.detect_black.unwrap_or(true),
+            noise_db: payload.noise_db.unwrap_or(DEFAULT_NOISE_DB),
+            min_silence_sec: payload.min_silence_sec.unwrap_or(DEFAULT_MIN_SILENCE_SEC),
+            min_black_sec: payload.min_black_sec.unwrap_or(DEFAULT_MIN_BLACK_SEC),
+            pixel_threshold: payload.pixel_threshold.unwrap_or(DEFAULT_PIXEL_THRESHOLD),
+            padding_sec: payload.padding_sec.unwrap_or(DEFAULT_PADDING_SEC),
+        };
+
+        if !settings.detect_silence && !settings.detect_black {
+            return Err("at least one of detectSilence and detectBlack must be enabled".to_string());
+        }
+        if !settings.noise_db.is_finite() || !(-90.0..=0.0).contains(&settings.noise_db) {
+            return Err("noiseDb must be between -90 and 0".to_string());
+        }
+        for (value, field) in [
+            (settings.min_silence_sec, "minSilenceSec"),
+            (settings.min_black_sec, "minBlackSec"),
+        ] {
+            if !value.is_finite() || !(0.05..=60.0).contains(&value) {
+                return Err(format!("{field} must be between 0.05 and 60"));
+            }
+        }
+        if !settings.pixel_threshold.is_finite() || !(0.0..=1.0).contains(&settings.pixel_threshold) {
+            return Err("pixelThreshold must be between 0 and 1".to_string());
+        }
+        if !settings.padding_sec.is_finite() || settings.padding_sec < 0.0 {
+            return Err("paddingSec must be a non-negative number".to_string());
+        }
+
+        Ok(settings)
+    }
+}
+
+/// One decoding pass runs both detectors; each only sees the stream it is mapped to.
+fn detect_args(input_path: &str, settings: &AutoTrimSettings, audio: bool, video: bool) -> Vec<String> {
+    let mut args = vec![
+        "-hide_banner".to_string(),
+        "-nostats".to_string(),
+        "-i".to_string(),
+        input_path.to_string(),
+    ];
+    if audio {
+        args.extend([
+            "-map".to_string(),
+            "0:a:0".to_string(),
+            "-af".to_string(),
+            format!(
+                "silencedetect=noise={}dB:d={}",
+                format_seconds(settings.noise_db),
+                format_seconds(settings.min_silence_sec)
+            ),
+        ]);
+    }
+    if video {
+        args.extend([
+            "-map".to_string(),
+            "0:v:0".to_string(),
+            "-vf".to_string(),
+            format!(
+                "blackdetect=d={}:pix_th={}",
+                format_seconds(settings.min_black_sec),
+                format_seconds(settings.pixel_threshold)
+            ),
+        ]);
+    }
+    args.extend(["-f".to_string(), "null".to_string(), "-".to_string()]);
+    args
+}
+
+fn value_after(line: &str, key: &str) -> Option<f64> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.split(|ch: char| ch.is_whitespace() || ch == '|')
+        .find(|part| !part.is_empty())?
+        .parse::<f64>()
+        .ok()
+}
+
+/// Pairs `silence_start: X` with the following `silence_end: Y | silence_duration: D`.
+/// A start with no end means the input finishes silent.
+pub fn parse_silences(stderr: &str, duration_sec: f64) -> Vec<TimeRange> {
+    let mut silences = Vec::new();
+    let mut open_start = None;
+    for line in stderr.lines().filter(|line| line.contains("silencedetect")) {
+        if let Some(start) = value_after(line, "silence_start:") {
+            open_start = Some(start.max(0.0));
+        } else if let Some(end) = value_after(line, "silence_end:") {
+            if let Some(start_sec) = open_start.take() {
+                silences.push(TimeRange {
+                    start_sec,
+                    end_sec: end,
+                });
+            }
+        }
+    }
+    if let Some(start_sec) = open_start.filter(|start| *start < duration_sec) {
+        silences.push(TimeRange {
+            start_sec,
+            end_sec: duration_sec,
+        });
+    }
+
+    silences
+}
+
+/// Reads `black_start:0 black_end:2.5 black_duration:2.5` lines.
+pub fn parse_black_frames(stderr: &str) -> Vec<TimeRange> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("blackdetect"))
+        .filter_map(|line| {
+            Some(TimeRange {
+                start_sec: value_after(line, "black_start:")?,
+                end_sec: value_after(line, "black_end:")?,
+            })
+        })
+        .collect()
+}
+
+/// Sorted union of overlapping or touching ranges.
+fn merge_ranges(ranges: &[TimeRange]) -> Vec<TimeRange> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by(|left, right| left.start_sec.total_cmp(&right.start_sec));
+
+    let mut merged: Vec<TimeRange> = Vec::new();
+    for range in sorted {
+        match merged.last_mut() {
+            Some(last) if range.start_sec <= last.end_sec + EDGE_TOLERANCE_SEC => {
+                last.end_sec = last.end_sec.max(range.end_sec);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// The content range between dead air (silence or black) at the very start and at the very end.
+pub fn content_range(dead_air: &[TimeRange], duration_sec: f64) -> Option<TimeRange> {
+    let merged = merge_ranges(dead_air);
+    let start_sec = merged
+        .first()
+        .filter(|range| range.start_sec <= EDGE_TOLERANCE_SEC)
+        .map_or(0.0, |range| range.end_sec);
+    let end_sec = merged
+        .last()
+        .filter(|range| range.end_sec >= duration_sec - EDGE_TOLERANCE_SEC)
+        .map_or(duration_sec, |range| range.start_sec);
+
+    (end_sec > start_sec).then_some(TimeRange { start_sec, end_sec })
+}
+
+/// Splits `content` around the silences that lie strictly inside it, leaving `padding` of
+/// silence on each side of every cut.
+pub fn keep_ranges(content: TimeRange, silences: &[TimeRange], padding: f64) -> Vec<TimeRange> {
+    let mut keep = Vec::new();
+    let mut cursor = content.start_sec;
+    for silence in merge_ranges(silences) {
+        let cut_start = silence.start_sec + padding;
+        let cut_end = silence.end_sec - padding;
+        if cut_end <= cut_start || cut_start <= cursor || cut_end >= content.end_sec {
+            continue;
+        }
+        keep.push(TimeRange {
+            start_sec: cursor,
+            end_sec: cut_start,
+        });
+        cursor = cut_end;
+    }
+    keep.push(TimeRange {
+        start_sec: cursor,
+        end_sec: content.end_sec,
+    });
+    keep
+}
+
+pub fn detect_dead_air(payload: &AutoTrimPayload) -> Result<AutoTrimResult, String> {
+    let input_path = text_from_option(&payload.input_path);
+    if !has_text(&input_path) {
+        return Err("inputPath is required".to_string());
+    }
+
+    let settings = AutoTrimSettings::from_payload(payload)?;
+    let configured_ffprobe = text_from_option(&payload.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let info = probe_media(&ffprobe_path, &configured_ffprobe, &input_path)?;
+    let duration_sec = info
+        .duration_sec
+        .filter(|duration| *duration > 0.0)
+        .ok_or_else(|| "无法读取输入时长，不能检测首尾空白".to_string())?;
+    let has_stream = |kind: &str| {
+        info.streams
+            .iter()
+            .any(|stream| stream.codec_type.as_deref() == Some(kind))
+    };
+    let audio = settings.detect_silence && has_stream("audio");
+    let video = settings.detect_black && has_stream("video");
+    if !audio && !video {
+        return Err("输入中没有可检测的音频或视频流".to_string());
+    }
+
+    let configured_ffmpeg = text_from_option(&payload.ffmpeg_path);
+    let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+    let args = detect_args(&input_path, &settings, audio, video);
+    let (_, stderr) = run_command(&ffmpeg_path, &args, "ffmpeg", &configured_ffmpeg)?;
+
+    let silences = parse_silences(&stderr, duration_sec);
+    let black_frames = parse_black_frames(&stderr);
+    let dead_air: Vec<TimeRange> = silences.iter().chain(black_frames.iter()).copied().collect();
+    let content = content_range(&dead_air, duration_sec).ok_or_else(|| "整个输入都是静音或黑场".to_string())?;
+
+    Ok(AutoTrimResult {
+        duration_sec,
+        start_time: (content.start_sec > 0.0).then(|| format_seconds(content.start_sec)),
+        duration: (content.end_sec < duration_sec).then(|| format_seconds(content.end_sec - content.start_sec)),
+        keep_ranges: keep_ranges(content, &silences, settings.padding_sec),
+        silences,
+        black_frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start_sec: f64, end_sec: f64) -> TimeRange {
+        TimeRange { start_sec, end_sec }
+    }
+
+    #[test]
+    fn parses_detector_output() {
+        let stderr = "\
+[silencedetect @ 0x55d1] silence_start: 0
+[silencedetect @ 0x55d1] silence_end: 3.2 | silence_duration: 3.2
+[blackdetect @ 0x55d2] black_start:0 black_end:2.5 black_duration:2.5
+[silencedetect @ 0x55d1] silence_start: 40.125
+[silencedetect @ 0x55d1] silence_end: 43.5 | silence_duration: 3.375
+[silencedetect @ 0x55d1] silence_start: 118
+[out#0/null @ 0x55d3] video:60KiB audio:120KiB";
+
+        assert_eq!(
+            parse_silences(stderr, 120.0),
+            vec![range(0.0, 3.2), range(40.125, 43.5), range(118.0, 120.0)]
+        );
+        assert_eq!(parse_black_frames(stderr), vec![range(0.0, 2.5)]);
+    }
+
+    #[test]
+    fn trims_leading_and_trailing_dead_air() {
+        let dead_air = [range(0.0, 2.5), range(2.4, 3.2), range(40.0, 43.0), range(118.0, 120.0)];
+        assert_eq!(content_range(&dead_air, 120.0), Some(range(3.2, 118.0)));
+        assert_eq!(content_range(&[range(10.0, 12.0)], 120.0), Some(range(0.0, 120.0)));
+        assert_eq!(content_range(&[range(0.0, 120.0)], 120.0), None);
+    }
+
+    #[test]
+    fn keep_ranges_remove_interior_silences_with_padding() {
+        let silences = [
+            range(0.0, 3.2),
+            range(40.0, 43.0),
+            range(60.0, 60.4),
+            range(118.0, 120.0),
+        ];
+        assert_eq!(
+            keep_ranges(range(3.2, 118.0), &silences, 0.25),
+            vec![range(3.2, 40.25), range(42.75, 118.0)]
+        );
+
+        let settings = AutoTrimSettings::from_payload(&AutoTrimPayload::default()).unwrap();
+        assert_eq!(
+            detect_args("in.mp4", &settings, true, false).join(" "),
+            "-hide_banner -nostats -i in.mp4 -map 0:a:0 -af silencedetect=noise=-50dB:d=0.5 -f null -"
+        );
+    }
+}
@@ -0,0 +1,584 @@
+use ffmpeg_gui_core::command::preview_job;
+use ffmpeg_gui_core::cropdetect::{detect_crop, CropDetectPayload};
+use ffmpeg_gui_core::job::{has_text, JobPayload};
+use ffmpeg_gui_core::jobfile;
+use ffmpeg_gui_core::keyframes::{list_keyframes, KeyframePayload};
+use ffmpeg_gui_core::loudnorm::LoudnessMeasurement;
+use ffmpeg_gui_core::probe::{probe_media, resolve_executable_path};
+use ffmpeg_gui_core::resources::ResourceSample;
+use ffmpeg_gui_core::runner::{self, JobEvents, ProgressEvent, StateEvent};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+const EXIT_OK: u8 = 0;
+const EXIT_JOB_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_INVALID_JOB: u8 = 3;
+const EXIT_TIMEOUT: u8 = 4;
+const EXIT_STOPPED: u8 = 5;
+
+const USAGE: &str = "\
+用法：
+  ffmpeg-gui-cli run [--job <file|->] [options] [input] [output]
+  ffmpeg-gui-cli preview [--job <file|->] [options] [input] [output]
+  ffmpeg-gui-cli probe [--ffprobe <path>] <input>
+  ffmpeg-gui-cli cropdetect [--ffmpeg <path>] [--ffprobe <path>] [--samples <n>]
+                            [--sample-duration <sec>] [--limit <0-255>] [--round <n>] <input>
+  ffmpeg-gui-cli keyframes [--ffprobe <path>] [--from <sec>] [--to <sec>] <input>
+  ffmpeg-gui-cli batch <job.ffjob|jobs.json>...
+
+run/preview 选项（覆盖 job 文件里的同名字段）：
+  --mode <preset|visual|raw>   --preset <h264|h265|mp3|gif>   --raw <args>
+  --ffmpeg <path>  --ffprobe <path>  --ss <time>  --duration <time>  --to <time>
+  --seek <input|output|hybrid>  --snap-keyframe
+  --crf <n>  --speed-preset <name>  --video-codec <c>  --audio-codec <c>
+  --video-bitrate <b>  --audio-bitrate <b>  --audio-quality <q>  --pixel-format <f>
+  --fps <n>  --width <n>  --height <n>  --sample-rate <n>  --channels <n>
+  --threads <n>  --format <f>  --map <spec>  --loop <n>  --vf <filters>  --chapters <ffmetadata>
+  --faststart  --no-video  --no-audio  --no-overwrite
+  --af <filters>  --volume-db <dB>  --tempo <x>  --fade-in <sec>  --fade-out <sec>
+  --channel-remap <mono|stereo|swap|left|right>  --highpass <Hz>  --lowpass <Hz>  --trim-silence
+  --loudnorm  --loudness-target <LUFS>  --true-peak <dBTP>  --loudness-range <LU>
+  --metrics（完成后评估画质）  --metrics-engine <auto|vmaf|ssim_psnr>
+  --timeout <sec>  --stall-timeout <sec>  --nice <n>  --priority-class <name>
+  --cpu-cores <0,1,...>  --var <name=value>（raw 模式的自定义占位符，可重复）  --quiet
+
+进度、状态和资源占用以 JSON 行输出到 stdout，ffmpeg 日志输出到 stderr。
+退出码：0 成功，1 ffmpeg 失败，2 参数错误，3 任务无效，4 超时或卡死，5 被停止。";
+
+#[derive(Clone, Copy)]
+enum FlagKind {
+    Text,
+    Number,
+    Switch(bool),
+    CoreList,
+}
+
+/// CLI flag → JobPayload JSON key. Keeping the mapping in JSON space means the CLI
+/// accepts exactly what the GUI sends.
+const JOB_FLAGS: &[(&str, &str, FlagKind)] = &[
+    ("--mode", "mode", FlagKind::Text),
+    ("--preset", "preset", FlagKind::Text),
+    ("--ffmpeg", "ffmpegPath", FlagKind::Text),
+    ("--ffprobe", "ffprobePath", FlagKind::Text),
+    ("--ss", "startTime", FlagKind::Text),
+    ("--duration", "duration", FlagKind::Text),
+    ("--to", "endTime", FlagKind::Text),
+    ("--seek", "seekMode", FlagKind::Text),
+    ("--snap-keyframe", "snapToKeyframe", FlagKind::Switch(true)),
+    ("--crf", "crf", FlagKind::Number),
+    ("--speed-preset", "speedPreset", FlagKind::Text),
+    ("--video-codec", "videoCodec", FlagKind::Text),
+    ("--audio-codec", "audioCodec", FlagKind::Text),
+    ("--pixel-format", "pixelFormat", FlagKind::Text),
+    ("--video-bitrate", "videoBitrate", FlagKind::Text),
+    ("--audio-bitrate", "audioBitrate", FlagKind::Text),
+    ("--audio-quality", "audioQuality", FlagKind::Text),
+    ("--fps", "fps", FlagKind::Number),
+    ("--width", "scaleWidth", FlagKind::Number),
+    ("--height", "scaleHeight", FlagKind::Number),
+    ("--sample-rate", "sampleRate", FlagKind::Number),
+    ("--channels", "channels", FlagKind::Number),
+    ("--threads", "threads", FlagKind::Number),
+    ("--format", "format", FlagKind::Text),
+    ("--map", "map", FlagKind::Text),
+    ("--chapters", "chaptersPath", FlagKind::Text),
+    ("--loop", "loop", FlagKind::Text),
+    ("--vf", "videoFilter", FlagKind::Text),
+    ("--faststart", "movflagsFaststart", FlagKind::Switch(true)),
+    ("--no-video", "disableVideo", FlagKind::Switch(true)),
+    ("--no-audio", "disableAudio", FlagKind::Switch(true)),
+    ("--af", "audioFilter", FlagKind::Text),
+    ("--volume-db", "volumeGainDb", FlagKind::Number),
+    ("--tempo", "tempo", FlagKind::Number),
+    ("--fade-in", "audioFadeInSec", FlagKind::Number),
+    ("--fade-out", "audioFadeOutSec", FlagKind::Number),
+    ("--channel-remap", "channelRemap", FlagKind::Text),
+    ("--highpass", "highpassHz", FlagKind::Number),
+    ("--lowpass", "lowpassHz", FlagKind::Number),
+    ("--trim-silence", "trimSilence", FlagKind::Switch(true)),
+    ("--loudnorm", "loudnorm", FlagKind::Switch(true)),
+    ("--loudness-target", "loudnessTarget", FlagKind::Number),
+    ("--true-peak", "truePeak", FlagKind::Number),
+    ("--loudness-range", "loudnessRange", FlagKind::Number),
+    ("--metrics", "computeMetrics", FlagKind::Switch(true)),
+    ("--metrics-engine", "metricsEngine", FlagKind::Text),
+    ("--no-overwrite", "overwrite", FlagKind::Switch(false)),
+    ("--timeout", "timeoutSec", FlagKind::Number),
+    ("--stall-timeout", "stallTimeoutSec", FlagKind::Number),
+    ("--nice", "niceLevel", FlagKind::Number),
+    ("--priority-class", "priorityClass", FlagKind::Text),
+    ("--cpu-cores", "cpuCores", FlagKind::CoreList),
+];
+
+struct CliError {
+    code: u8,
+    message: String,
+}
+
+impl CliError {
+    fn usage(message: impl Into<String>) -> Self {
+        Self {
+            code: EXIT_USAGE,
+            message: message.into(),
+        }
+    }
+
+    fn invalid(message: impl Into<String>) -> Self {
+        Self {
+            code: EXIT_INVALID_JOB,
+            message: message.into(),
+        }
+    }
+}
+
+struct JobRequest {
+    payload: JobPayload,
+    quiet: bool,
+}
+
+fn read_json_source(source: &str) -> Result<Value, CliError> {
+    let text = if source == "-" {
+        let mut buffer = String::new();
+        std::io::stdin()
+            .read_to_string(&mut buffer)
+            .map_err(|error| CliError::usage(format!("读取 stdin 失败：{error}")))?;
+        buffer
+    } else {
+        fs::read_to_string(source).map_err(|error| CliError::usage(format!("读取 {source} 失败：{error}")))?
+    };
+
+    serde_json::from_str(&text).map_err(|error| CliError::invalid(format!("{source} 不是有效的 JSON：{error}")))
+}
+
+/// Reads a `.ffjob` document or bare JobPayload JSON; relative media paths resolve against the file.
+fn read_job_source(source: &str) -> Result<Map<String, Value>, CliError> {
+    job_from_document(source, read_json_source(source)?)
+}
+
+fn job_from_document(source: &str, value: Value) -> Result<Map<String, Value>, CliError> {
+    let base_dir = if source == "-" { None } else { Path::new(source).parent() };
+    jobfile::job_value_from_document(value, base_dir).map_err(|error| CliError::invalid(format!("{source}：{error}")))
+}
+
+fn payload_from_value(value: Value) -> Result<JobPayload, CliError> {
+    serde_json::from_value(value).map_err(|error| CliError::invalid(format!("任务定义无效：{error}")))
+}
+
+fn parse_number(flag: &str, text: &str) -> Result<Value, CliError> {
+    let number = text
+        .parse::<f64>()
+        .map_err(|_| CliError::usage(format!("{flag} 需要数字，收到 {text}")))?;
+    serde_json::Number::from_f64(number)
+        .map(Value::Number)
+        .ok_or_else(|| CliError::usage(format!("{flag} 需要有限数字")))
+}
+
+fn parse_core_list(text: &str) -> Result<Value, CliError> {
+    text.split(',')
+        .filter(|part| has_text(part))
+        .map(|part| {
+            part.trim()
+                .parse::<u32>()
+                .map(Value::from)
+                .map_err(|_| CliError::usage(format!("--cpu-cores 包含无效核心编号：{part}")))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::Array)
+}
+
+fn parse_job_request(args: &[String]) -> Result<JobRequest, CliError> {
+    let mut base: Option<Map<String, Value>> = None;
+    let mut overrides = Map::new();
+    let mut variables = Map::new();
+    let mut positionals: Vec<String> = Vec::new();
+    let mut quiet = false;
+    let mut index = 0;
+
+    while index < args.len() {
+        let arg = args[index].as_str();
+        index += 1;
+
+        let mut take_value = || -> Result<String, CliError> {
+            let value = args
+                .get(index)
+                .cloned()
+                .ok_or_else(|| CliError::usage(format!("{arg} 缺少参数值")))?;
+            index += 1;
+            Ok(value)
+        };
+
+        match arg {
+            "--job" => base = Some(read_job_source(&take_value()?)?),
+            "--quiet" => quiet = true,
+            "--raw" => {
+                overrides.insert("mode".to_string(), Value::from("raw"));
+                overrides.insert("rawArgs".to_string(), Value::from(take_value()?));
+            }
+            "--var" => {
+                let value = take_value()?;
+                let Some((name, text)) = value.split_once('=') else {
+                    return Err(CliError::usage(format!("--var 需要 name=value 格式：{value}")));
+                };
+                variables.insert(name.trim().to_string(), Value::from(text));
+            }
+            _ if arg.starts_with("--") => {
+                let Some((_, key, kind)) = JOB_FLAGS.iter().find(|(flag, _, _)| *flag == arg) else {
+                    return Err(CliError::usage(format!("未知选项：{arg}")));
+                };
+
+                let value = match kind {
+                    FlagKind::Text => Value::from(take_value()?),
+                    FlagKind::Number => parse_number(arg, &take_value()?)?,
+                    FlagKind::CoreList => parse_core_list(&take_value()?)?,
+                    FlagKind::Switch(on) => Value::from(*on),
+                };
+                overrides.insert(key.to_string(), value);
+            }
+            _ => positionals.push(arg.to_string()),
+        }
+    }
+
+    if positionals.len() > 2 {
+        return Err(CliError::usage(format!("多余的参数：{}", positionals[2..].join(" "))));
+    }
+
+    let mut positionals = positionals.into_iter();
+    if let Some(input) = positionals.next() {
+        overrides.insert("inputPath".to_string(), Value::from(input));
+    }
+    if let Some(output) = positionals.next() {
+        overrides.insert("outputPath".to_string(), Value::from(output));
+    }
+
+    // Without a job file the flags describe the job, and only the visual builder honours all of them.
+    let mut merged = base.unwrap_or_else(|| Map::from_iter([("mode".to_string(), Value::from("visual"))]));
+    merged.extend(overrides);
+
+    if !variables.is_empty() {
+        let entry = merged.entry("variables").or_insert_with(|| Value::Object(Map::new()));
+        match entry {
+            Value::Object(existing) => existing.extend(variables),
+            _ => *entry = Value::Object(variables),
+        }
+    }
+
+    Ok(JobRequest {
+        payload: payload_from_value(Value::Object(merged))?,
+        quiet,
+    })
+}
+
+fn print_json_line<T: Serialize>(event: &str, job: Option<usize>, body: &T) {
+    let mut object = match serde_json::to_value(body) {
+        Ok(Value::Object(object)) => object,
+        Ok(other) => {
+            let mut object = Map::new();
+            object.insert("value".to_string(), other);
+            object
+        }
+        Err(_) => return,
+    };
+
+    object.insert("event".to_string(), Value::from(event));
+    if let Some(job) = job {
+        object.insert("job".to_string(), Value::from(job));
+    }
+
+    println!("{}", Value::Object(object));
+}
+
+struct JsonLineEvents {
+    job: Option<usize>,
+    quiet: bool,
+}
+
+impl JobEvents for JsonLineEvents {
+    fn state(&self, event: &StateEvent) {
+        print_json_line("state", self.job, event);
+    }
+
+    fn progress(&self, event: &ProgressEvent) {
+        print_json_line("progress", self.job, event);
+    }
+
+    fn log(&self, line: &str) {
+        if !self.quiet {
+            eprintln!("{line}");
+        }
+    }
+
+    fn resources(&self, sample: &ResourceSample) {
+        print_json_line("resources", self.job, sample);
+    }
+
+    fn loudness(&self, measurement: &LoudnessMeasurement) {
+        print_json_line("loudness", self.job, measurement);
+    }
+}
+
+fn exit_code_for_state(state: &StateEvent) -> u8 {
+    match (state.status.as_str(), state.reason.as_deref()) {
+        ("completed", _) => EXIT_OK,
+        ("stopped", _) => EXIT_STOPPED,
+        ("failed", Some("timeout" | "stalled")) => EXIT_TIMEOUT,
+        _ => EXIT_JOB_FAILED,
+    }
+}
+
+fn run_payload(payload: &JobPayload, job: Option<usize>, quiet: bool) -> Result<u8, CliError> {
+    let events = Arc::new(JsonLineEvents { job, quiet });
+    let handle = runner::start_job(payload, events, |_| {}).map_err(CliError::invalid)?;
+    Ok(exit_code_for_state(&handle.wait()))
+}
+
+fn command_run(args: &[String]) -> Result<u8, CliError> {
+    let request = parse_job_request(args)?;
+    run_payload(&request.payload, None, request.quiet)
+}
+
+fn command_preview(args: &[String]) -> Result<u8, CliError> {
+    let request = parse_job_request(args)?;
+    let preview = preview_job(&request.payload).map_err(CliError::invalid)?;
+    print_json_line("preview", None, &preview);
+    Ok(EXIT_OK)
+}
+
+fn command_probe(args: &[String]) -> Result<u8, CliError> {
+    let mut configured_ffprobe = String::new();
+    let mut input_path: Option<&String> = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--ffprobe" => {
+                configured_ffprobe = iter
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| CliError::usage("--ffprobe 缺少参数值"))?;
+            }
+            _ if input_path.is_none() => input_path = Some(arg),
+            _ => return Err(CliError::usage(format!("多余的参数：{arg}"))),
+        }
+    }
+
+    let input_path = input_path.ok_or_else(|| CliError::usage("probe 需要输入文件"))?;
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let info = probe_media(&ffprobe_path, &configured_ffprobe, input_path).map_err(CliError::invalid)?;
+    print_json_line("probe", None, &info);
+    Ok(EXIT_OK)
+}
+
+fn command_cropdetect(args: &[String]) -> Result<u8, CliError> {
+    let mut payload = CropDetectPayload::default();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        if !flag.starts_with("--") {
+            if payload.input_path.is_some() {
+                return Err(CliError::usage(format!("多余的参数：{arg}")));
+            }
+            payload.input_path = Some(arg.clone());
+            continue;
+        }
+
+        let value = iter
+            .next()
+            .ok_or_else(|| CliError::usage(format!("{flag} 缺少参数值")))?;
+        let number = || parse_number(flag, value).map(|number| number.as_f64());
+        match flag {
+            "--ffmpeg" => payload.ffmpeg_path = Some(value.clone()),
+            "--ffprobe" => payload.ffprobe_path = Some(value.clone()),
+            "--samples" => payload.samples = number()?,
+            "--sample-duration" => payload.sample_duration_sec = number()?,
+            "--limit" => payload.limit = number()?,
+            "--round" => payload.round = number()?,
+            _ => return Err(CliError::usage(format!("未知选项：{flag}"))),
+        }
+    }
+
+    if payload.input_path.is_none() {
+        return Err(CliError::usage("cropdetect 需要输入文件"));
+    }
+
+    let result = detect_crop(&payload).map_err(CliError::invalid)?;
+    print_json_line("crop", None, &result);
+    Ok(EXIT_OK)
+}
+
+/// Prints the video stream's keyframe times, optionally limited to `--from`/`--to`.
+fn command_keyframes(args: &[String]) -> Result<u8, CliError> {
+    let mut payload = KeyframePayload::default();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        if !flag.starts_with("--") {
+            if payload.input_path.is_some() {
+                return Err(CliError::usage(format!("多余的参数：{arg}")));
+            }
+            payload.input_path = Some(arg.clone());
+            continue;
+        }
+
+        let value = iter
+            .next()
+            .ok_or_else(|| CliError::usage(format!("{flag} 缺少参数值")))?;
+        let number = || parse_number(flag, value).map(|number| number.as_f64());
+        match flag {
+            "--ffprobe" => payload.ffprobe_path = Some(value.clone()),
+            "--from" => payload.start_sec = number()?,
+            "--to" => payload.end_sec = number()?,
+            _ => return Err(CliError::usage(format!("未知选项：{flag}"))),
+        }
+    }
+
+    if payload.input_path.is_none() {
+        return Err(CliError::usage("keyframes 需要输入文件"));
+    }
+
+    let result = list_keyframes(&payload).map_err(CliError::invalid)?;
+    print_json_line("keyframes", None, &result);
+    Ok(EXIT_OK)
+}
+
+/// Each argument is a job file (`.ffjob` or bare JobPayload JSON) or a JSON array of bare
+/// payloads; jobs run in order.
+fn command_batch(args: &[String]) -> Result<u8, CliError> {
+    if args.is_empty() {
+        return Err(CliError::usage("batch 需要至少一个任务文件"));
+    }
+
+    let mut payloads = Vec::new();
+    for source in args {
+        match read_json_source(source)? {
+            Value::Array(items) => {
+                for item in items {
+                    payloads.push(payload_from_value(item)?);
+                }
+            }
+            document => payloads.push(payload_from_value(Value::Object(job_from_document(source, document)?))?),
+        }
+    }
+
+    let mut worst = EXIT_OK;
+    for (index, payload) in payloads.iter().enumerate() {
+        let code = match run_payload(payload, Some(index), false) {
+            Ok(code) => code,
+            Err(error) => {
+                print_json_line(
+                    "state",
+                    Some(index),
+                    &serde_json::json!({ "status": "failed", "message": error.message }),
+                );
+                error.code
+            }
+        };
+        worst = worst.max(code);
+    }
+
+    Ok(worst)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(EXIT_USAGE);
+    };
+
+    let result = match command.as_str() {
+        "run" => command_run(rest),
+        "preview" => command_preview(rest),
+        "probe" => command_probe(rest),
+        "cropdetect" => command_cropdetect(rest),
+        "keyframes" => command_keyframes(rest),
+        "batch" => command_batch(rest),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(EXIT_OK)
+        }
+        other => Err(CliError::usage(format!("未知命令：{other}"))),
+    };
+
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            eprintln!("{}", error.message);
+            if error.code == EXIT_USAGE {
+                eprintln!("\n{USAGE}");
+            }
+            ExitCode::from(error.code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_job_request_maps_flags_to_payload() {
+        let request = parse_job_request(&strings(&[
+            "--preset",
+            "h265",
+            "--crf",
+            "24",
+            "--no-audio",
+            "--cpu-cores",
+            "0,2",
+            "--var",
+            "show=Weekly=1",
+            "in.mov",
+            "out.mp4",
+        ]))
+        .unwrap_or_else(|error| panic!("{}", error.message));
+
+        let payload = request.payload;
+        assert_eq!(payload.mode.as_deref(), Some("visual"));
+        assert_eq!(payload.preset.as_deref(), Some("h265"));
+        assert_eq!(payload.crf, Some(24.0));
+        assert_eq!(payload.disable_audio, Some(true));
+        assert_eq!(payload.cpu_cores, Some(vec![0, 2]));
+        assert_eq!(
+            payload.variables.and_then(|variables| variables.get("show").cloned()).as_deref(),
+            Some("Weekly=1")
+        );
+        assert_eq!(payload.input_path.as_deref(), Some("in.mov"));
+        assert_eq!(payload.output_path.as_deref(), Some("out.mp4"));
+    }
+
+    #[test]
+    fn parse_job_request_rejects_unknown_flags() {
+        let error = parse_job_request(&strings(&["--bogus", "1"])).err().expect("should fail");
+        assert_eq!(error.code, EXIT_USAGE);
+    }
+
+    #[test]
+    fn exit_codes_distinguish_watchdog_failures() {
+        let state = |status: &str, reason: Option<&str>| StateEvent {
+            status: status.to_string(),
+            mode: None,
+            args: None,
+            message: None,
+            reason: reason.map(str::to_string),
+            resources: None,
+            metrics: None,
+        };
+
+        assert_eq!(exit_code_for_state(&state("completed", None)), EXIT_OK);
+        assert_eq!(exit_code_for_state(&state("failed", Some("stalled"))), EXIT_TIMEOUT);
+        assert_eq!(exit_code_for_state(&state("failed", None)), EXIT_JOB_FAILED);
+        assert_eq!(exit_code_for_state(&state("stopped", Some("graceful"))), EXIT_STOPPED);
+    }
+}
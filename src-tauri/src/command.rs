@@ -0,0 +1,175 @@
+use serde::Serialize;
+
+use crate::job::{build_ffmpeg_args, has_text, text_from_option, JobPayload, INPUT_PLACEHOLDER, OUTPUT_PLACEHOLDER};
+use crate::keyframes::{apply_keyframe_snap, keyframe_snap, KeyframeSnap};
+
+pub fn split_command_line(command_line: &str) -> Result<Vec<String>, String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut escape_next = false;
+
+    let push_current = |tokens: &mut Vec<String>, current: &mut String| {
+        if current.is_empty() {
+            return;
+        }
+
+        tokens.push(current.clone());
+        current.clear();
+    };
+
+    for ch in command_line.chars() {
+        if escape_next {
+            current.push(ch);
+            escape_next = false;
+            continue;
+        }
+
+        if ch == '\\' && !in_single_quote {
+            escape_next = true;
+            continue;
+        }
+
+        if ch == '"' && !in_single_quote {
+            in_double_quote = !in_double_quote;
+            continue;
+        }
+
+        if ch == '\'' && !in_double_quote {
+            in_single_quote = !in_single_quote;
+            continue;
+        }
+
+        if ch.is_whitespace() && !in_single_quote && !in_double_quote {
+            push_current(&mut tokens, &mut current);
+            continue;
+        }
+
+        current.push(ch);
+    }
+
+    if escape_next {
+        return Err("Invalid command line: trailing escape".to_string());
+    }
+
+    if in_single_quote || in_double_quote {
+        return Err("Invalid command line: unclosed quote".to_string());
+    }
+
+    push_current(&mut tokens, &mut current);
+    Ok(tokens)
+}
+
+fn is_safe_preview_arg(text: &str) -> bool {
+    text.chars().all(|ch| {
+        ch.is_ascii_alphanumeric()
+            || ch == '_'
+            || ch == '.'
+            || ch == '/'
+            || ch == ':'
+            || ch == '='
+            || ch == '+'
+            || ch == ','
+            || ch == '-'
+    })
+}
+
+pub fn quote_command_arg(value: &str) -> String {
+    if value.is_empty() {
+        return "\"\"".to_string();
+    }
+
+    if is_safe_preview_arg(value) {
+        return value.to_string();
+    }
+
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    )
+}
+
+pub fn format_command_preview(binary_path: &str, args: &[String]) -> String {
+    let binary = if has_text(binary_path) {
+        binary_path.trim()
+    } else {
+        "ffmpeg"
+    };
+
+    let mut command: Vec<String> = vec![quote_command_arg(binary)];
+    command.extend(args.iter().map(|arg| quote_command_arg(arg)));
+    command.join(" ")
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewResponse {
+    pub args: Vec<String>,
+    pub command: String,
+    /// Set when the start time was moved back onto a keyframe.
+    pub keyframe_snap: Option<KeyframeSnap>,
+}
+
+/// Builds the args for display; missing paths are shown as `{input}` / `{output}`.
+pub fn preview_job(payload: &JobPayload) -> Result<PreviewResponse, String> {
+    let ffmpeg_path = {
+        let text = text_from_option(&payload.ffmpeg_path);
+        if has_text(&text) {
+            text
+        } else {
+            "ffmpeg".to_string()
+        }
+    };
+
+    let mut preview_payload = payload.clone();
+    if !has_text(&text_from_option(&preview_payload.input_path)) {
+        preview_payload.input_path = Some(INPUT_PLACEHOLDER.to_string());
+    }
+
+    if !has_text(&text_from_option(&preview_payload.output_path)) {
+        preview_payload.output_path = Some(OUTPUT_PLACEHOLDER.to_string());
+    }
+
+    // Snapped here rather than inside the builder so the preview can report it.
+    let keyframe_snap = match text_from_option(&preview_payload.mode).as_str() {
+        "visual" => keyframe_snap(&preview_payload)?,
+        _ => None,
+    };
+    if let Some(snap) = &keyframe_snap {
+        preview_payload = apply_keyframe_snap(&preview_payload, snap)?;
+    }
+
+    let args = build_ffmpeg_args(&preview_payload)?;
+    let command = format_command_preview(&ffmpeg_path, &args);
+
+    Ok(PreviewResponse {
+        args,
+        command,
+        keyframe_snap,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_command_line_handles_quotes() {
+        let parsed = split_command_line(r#"-i "{input}" -vf "scale=1280:-1,format=yuv420p" "{output}""#)
+            .expect("parse failed");
+
+        assert_eq!(
+            parsed,
+            vec![
+                "-i".to_string(),
+                "{input}".to_string(),
+                "-vf".to_string(),
+                "scale=1280:-1,format=yuv420p".to_string(),
+                "{output}".to_string(),
+            ]
+        );
+    }
+}
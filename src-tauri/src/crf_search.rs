@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cropdetect::sample_points;
+use crate::job::{has_text, resolve_trim, text_from_option, JobPayload};
+use crate::metrics::{self, MetricsEngine, MetricsPayload};
+use crate::probe::resolve_executable_path;
+use crate::sample::{encode_window, full_duration_sec, SampleWindow};
+use crate::template::format_seconds;
+use crate::thumbnails::cache_key;
+
+const DEFAULT_MIN_CRF: f64 = 16.0;
+const DEFAULT_MAX_CRF: f64 = 40.0;
+const DEFAULT_SAMPLES: f64 = 3.0;
+const DEFAULT_SAMPLE_SEC: f64 = 5.0;
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CrfSearchPayload {
+    /// VMAF (0–100) or SSIM (0–1) score each sample must reach on average.
+    pub target_score: Option<f64>,
+    /// `auto` (default: VMAF when available), `vmaf` or `ssim`.
+    pub metric: Option<String>,
+    pub min_crf: Option<f64>,
+    pub max_crf: Option<f64>,
+    /// Windows spread over the job's range; each CRF is scored on all of them.
+    pub samples: Option<f64>,
+    pub sample_duration_sec: Option<f64>,
+    /// Start the job with the found CRF once the search finishes.
+    pub run_after_search: Option<bool>,
+    #[serde(default)]
+    pub job: JobPayload,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrfSearchStep {
+    pub step: usize,
+    pub crf: u32,
+    pub score: f64,
+    pub passed: bool,
+    /// Loaded from the per-source cache instead of encoded.
+    pub cached: bool,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrfSearchResult {
+    pub crf: u32,
+    pub score: f64,
+    pub metric: String,
+    /// False when even `min_crf` misses the target; `crf` is then `min_crf`.
+    pub met_target: bool,
+    pub steps: Vec<CrfSearchStep>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SearchMetric {
+    Vmaf,
+    Ssim,
+}
+
+impl SearchMetric {
+    fn name(self) -> &'static str {
+        match self {
+            SearchMetric::Vmaf => "vmaf",
+            SearchMetric::Ssim => "ssim",
+        }
+    }
+
+    fn engine(self) -> &'static str {
+        match self {
+            SearchMetric::Vmaf => "vmaf",
+            SearchMetric::Ssim => "ssim_psnr",
+        }
+    }
+
+    fn max_score(self) -> f64 {
+        match self {
+            SearchMetric::Vmaf => 100.0,
+            SearchMetric::Ssim => 1.0,
+        }
+    }
+}
+
+/// Scores already measured for one source file, keyed by settings fingerprint and then CRF.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct CrfCache {
+    pub entries: BTreeMap<String, BTreeMap<u32, f64>>,
+}
+
+impl CrfCache {
+    pub fn load(path: &Path) -> CrfCache {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|error| format!("创建 CRF 缓存目录失败：{error}"))?;
+        }
+
+        let text = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+        fs::write(path, text).map_err(|error| format!("写入 CRF 缓存失败：{error}"))
+    }
+}
+
+/// Identifies everything that affects a score except the CRF itself: the encode settings,
+/// the metric and where the samples are taken.
+pub fn settings_fingerprint(job: &JobPayload, metric: &str, windows: &[SampleWindow]) -> String {
+    let normalized = JobPayload {
+        crf: None,
+        output_path: None,
+        overwrite: None,
+        ffmpeg_path: None,
+        ffprobe_path: None,
+        compute_metrics: None,
+        metrics_engine: None,
+        timeout_sec: None,
+        stall_timeout_sec: None,
+        nice_level: None,
+        priority_class: None,
+        cpu_cores: None,
+        ..job.clone()
+    };
+    let windows: Vec<String> = windows
+        .iter()
+        .map(|window| {
+            format!(
+                "{}+{}",
+                format_seconds(window.start_sec),
+                format_seconds(window.duration_sec)
+            )
+        })
+        .collect();
+
+    format!(
+        "{metric}|{}|{}",
+        windows.join(","),
+        serde_json::to_string(&normalized).unwrap_or_default()
+    )
+}
+
+/// Finds the highest CRF in `min..=max` whose score reaches `target`, assuming the score
+/// falls as CRF rises. Each CRF is evaluated at most once.
+pub fn bisect_crf(
+    min: u32,
+    max: u32,
+    target: f64,
+    mut evaluate: impl FnMut(u32) -> Result<f64, String>,
+) -> Result<(u32, f64, bool), String> {
+    let mut scores = BTreeMap::new();
+    let mut score_of = |crf: u32| -> Result<f64, String> {
+        if let Some(score) = scores.get(&crf) {
+            return Ok(*score);
+        }
+        let score = evaluate(crf)?;
+        scores.insert(crf, score);
+        Ok(score)
+    };
+
+    let lowest = score_of(min)?;
+    if lowest < target {
+        return Ok((min, lowest, false));
+    }
+
+    let (mut low, mut high) = (min, max);
+    while low < high {
+        let middle = low + (high - low).div_ceil(2);
+        if score_of(middle)? >= target {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+
+    Ok((low, score_of(low)?, true))
+}
+
+fn checked_integer(value: Option<f64>, default: f64, min: f64, max: f64, field: &str) -> Result<u32, String> {
+    let value = value.unwrap_or(default);
+    if !value.is_finite() || value < min || value > max || value.fract() != 0.0 {
+        return Err(format!("{field} must be an integer between {min} and {max}"));
+    }
+
+    Ok(value as u32)
+}
+
+fn resolve_metric(requested: &str, job: &JobPayload) -> Result<SearchMetric, String> {
+    match requested {
+        "vmaf" => Ok(SearchMetric::Vmaf),
+        "ssim" => Ok(SearchMetric::Ssim),
+        "" | "auto" => {
+            let configured_ffmpeg = text_from_option(&job.ffmpeg_path);
+            let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+            Ok(
+                match metrics::resolve_engine("auto", &ffmpeg_path, &configured_ffmpeg)? {
+                    MetricsEngine::Vmaf => SearchMetric::Vmaf,
+                    MetricsEngine::SsimPsnr => SearchMetric::Ssim,
+                },
+            )
+        }
+        other => Err(format!("Unsupported metric: {other}")),
+    }
+}
+
+/// Encodes every window at `crf` and returns the mean score; the sample files are removed.
+fn score_crf(job: &JobPayload, crf: u32, metric: SearchMetric, windows: &[SampleWindow]) -> Result<f64, String> {
+    let job = JobPayload {
+        crf: Some(f64::from(crf)),
+        ..job.clone()
+    };
+
+    let mut total = 0.0;
+    for window in windows {
+        let (path, _, _) = encode_window(&job, window)?;
+        let measured = metrics::compute_metrics(&MetricsPayload {
+            reference_path: job.input_path.clone(),
+            distorted_path: Some(path.to_string_lossy().to_string()),
+            ffmpeg_path: job.ffmpeg_path.clone(),
+            ffprobe_path: job.ffprobe_path.clone(),
+            engine: Some(metric.engine().to_string()),
+            reference_start_sec: Some(window.start_sec),
+            reference_duration_sec: Some(window.duration_sec),
+            reference_crop: metrics::job_reference_crop(&job)?,
+        });
+        let _ = fs::remove_file(&path);
+
+        let measured = measured?;
+        let score = match metric {
+            SearchMetric::Vmaf => measured.vmaf,
+            SearchMetric::Ssim => measured.ssim,
+        };
+        total += score
+            .ok_or_else(|| format!("样片没有得到 {} 评分", metric.name()))?
+            .mean;
+    }
+
+    Ok(total / windows.len() as f64)
+}
+
+/// Runs the search, reporting each evaluated CRF through `on_step`. Scores are cached in
+/// `cache_dir`, one file per source file version.
+pub fn search_crf(
+    payload: &CrfSearchPayload,
+    cache_dir: &Path,
+    mut on_step: impl FnMut(&CrfSearchStep),
+) -> Result<CrfSearchResult, String> {
+    let job = &payload.job;
+    let input_path = text_from_option(&job.input_path);
+    if text_from_option(&job.mode) == "raw" {
+        return Err("raw 模式无法自动设置 CRF".to_string());
+    }
+    if !has_text(&input_path) || !has_text(&text_from_option(&job.output_path)) {
+        return Err("inputPath and outputPath are required".to_string());
+    }
+    if job.disable_video.unwrap_or(false) || text_from_option(&job.video_codec) == "copy" {
+        return Err("CRF 搜索需要重新编码视频".to_string());
+    }
+
+    let min_crf = checked_integer(payload.min_crf, DEFAULT_MIN_CRF, 0.0, 63.0, "minCrf")?;
+    let max_crf = checked_integer(payload.max_crf, DEFAULT_MAX_CRF, 0.0, 63.0, "maxCrf")?;
+    if min_crf > max_crf {
+        return Err("minCrf must not exceed maxCrf".to_string());
+    }
+
+    let metric = resolve_metric(&text_from_option(&payload.metric), job)?;
+    let target = payload
+        .target_score
+        .ok_or_else(|| "targetScore is required".to_string())?;
+    if !target.is_finite() || target <= 0.0 || target > metric.max_score() {
+        return Err(format!(
+            "targetScore must be between 0 and {} for {}",
+            metric.max_score(),
+            metric.name()
+        ));
+    }
+
+    let sample_count = checked_integer(payload.samples, DEFAULT_SAMPLES, 1.0, 10.0, "samples")?;
+    let sample_sec = payload.sample_duration_sec.unwrap_or(DEFAULT_SAMPLE_SEC);
+    if !sample_sec.is_finite() || !(1.0..=60.0).contains(&sample_sec) {
+        return Err("sampleDurationSec must be between 1 and 60".to_string());
+    }
+
+    let job_start = resolve_trim(job)?.start_sec.unwrap_or(0.0);
+    let full_duration = full_duration_sec(job, job_start)?;
+    let duration_sec = sample_sec.min(full_duration);
+    let windows: Vec<SampleWindow> = sample_points(full_duration, sample_count as usize, duration_sec)
+        .into_iter()
+        .map(|offset| SampleWindow {
+            start_sec: job_start + offset,
+            duration_sec,
+        })
+        .collect();
+
+    let cache_path = cache_dir.join(format!("{}.json", cache_key(&input_path)?));
+    let mut cache = CrfCache::load(&cache_path);
+    let fingerprint = settings_fingerprint(job, metric.name(), &windows);
+
+    let mut steps = Vec::new();
+    let outcome = bisect_crf(min_crf, max_crf, target, |crf| {
+        let cached_score = cache
+            .entries
+            .get(&fingerprint)
+            .and_then(|scores| scores.get(&crf))
+            .copied();
+        let score = match cached_score {
+            Some(score) => score,
+            None => {
+                let score = score_crf(job, crf, metric, &windows)?;
+                cache.entries.entry(fingerprint.clone()).or_default().insert(crf, score);
+                score
+            }
+        };
+
+        let step = CrfSearchStep {
+            step: steps.len() + 1,
+            crf,
+            score,
+            passed: score >= target,
+            cached: cached_score.is_some(),
+        };
+        on_step(&step);
+        steps.push(step);
+        Ok(score)
+    });
+
+    // Scores measured before a failure are still worth keeping.
+    cache.save(&cache_path)?;
+    let (crf, score, met_target) = outcome?;
+
+    Ok(CrfSearchResult {
+        crf,
+        score,
+        metric: metric.name().to_string(),
+        met_target,
+        steps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bisect_finds_highest_passing_crf_with_few_encodes() {
+        let mut evaluated = Vec::new();
+        let (crf, score, met) = bisect_crf(16, 40, 93.0, |crf| {
+            evaluated.push(crf);
+            Ok(100.0 - f64::from(crf - 16) * 0.5)
+        })
+        .unwrap();
+
+        assert_eq!((crf, score, met), (30, 93.0, true));
+        assert!(evaluated.len() <= 7, "evaluated {evaluated:?}");
+
+        let (crf, _, met) = bisect_crf(16, 40, 99.5, |crf| Ok(90.0 - f64::from(crf))).unwrap();
+        assert_eq!((crf, met), (16, false));
+        assert_eq!(bisect_crf(16, 40, 1.0, |_| Ok(50.0)).unwrap().0, 40);
+    }
+
+    #[test]
+    fn fingerprint_ignores_crf_and_output_location() {
+        let windows = [SampleWindow {
+            start_sec: 10.0,
+            duration_sec: 5.0,
+        }];
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("a.mp4".to_string()),
+            speed_preset: Some("slow".to_string()),
+            crf: Some(20.0),
+            ..Default::default()
+        };
+        let moved = JobPayload {
+            output_path: Some("/other/b.mp4".to_string()),
+            crf: Some(28.0),
+            ..job.clone()
+        };
+        let slower = JobPayload {
+            speed_preset: Some("veryslow".to_string()),
+            ..job.clone()
+        };
+
+        let fingerprint = settings_fingerprint(&job, "vmaf", &windows);
+        assert_eq!(fingerprint, settings_fingerprint(&moved, "vmaf", &windows));
+        assert_ne!(fingerprint, settings_fingerprint(&slower, "vmaf", &windows));
+        assert_ne!(fingerprint, settings_fingerprint(&job, "ssim", &windows));
+    }
+
+    #[test]
+    fn crf_cache_round_trips_through_disk() {
+        let path = std::env::temp_dir()
+            .join(format!("ffmpeg-gui-crf-{}", std::process::id()))
+            .join("cache.json");
+        let mut cache = CrfCache::default();
+        cache.entries.entry("key".to_string()).or_default().insert(23, 95.25);
+        cache.save(&path).unwrap();
+
+        assert_eq!(CrfCache::load(&path), cache);
+        assert_eq!(
+            CrfCache::load(&path.with_file_name("missing.json")),
+            CrfCache::default()
+        );
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::job::{has_text, text_from_option};
+use crate::probe::{probe_media, resolve_executable_path, run_command};
+use crate::template::format_seconds;
+use crate::video_filter::VideoFilterNode;
+
+const DEFAULT_SAMPLES: f64 = 6.0;
+const DEFAULT_SAMPLE_DURATION_SEC: f64 = 2.0;
+/// ffmpeg's own defaults for cropdetect.
+const DEFAULT_LIMIT: f64 = 24.0;
+const DEFAULT_ROUND: f64 = 16.0;
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CropDetectPayload {
+    pub input_path: Option<String>,
+    pub ffmpeg_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    /// Number of seek points spread evenly over the input.
+    pub samples: Option<f64>,
+    /// Seconds of video analysed at each seek point.
+    pub sample_duration_sec: Option<f64>,
+    /// Black level threshold (0–255); pixels at or below it count as border.
+    pub limit: Option<f64>,
+    /// The detected width and height are multiples of this.
+    pub round: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct CropRect {
+    pub width: u64,
+    pub height: u64,
+    pub x: u64,
+    pub y: u64,
+}
+
+impl CropRect {
+    fn area(&self) -> u64 {
+        self.width * self.height
+    }
+
+    pub fn to_node(self) -> VideoFilterNode {
+        VideoFilterNode::Crop {
+            width: self.width as f64,
+            height: self.height as f64,
+            x: Some(self.x as f64),
+            y: Some(self.y as f64),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CropDetectResult {
+    pub crop: CropRect,
+    pub filter: String,
+    /// Ready to insert into `videoFilters`.
+    pub node: VideoFilterNode,
+    /// Samples that agreed on `crop`, out of `samples` that produced a result.
+    pub votes: usize,
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropDetectSettings {
+    pub samples: usize,
+    pub sample_duration_sec: f64,
+    pub limit: f64,
+    pub round: u64,
+}
+
+fn checked(value: Option<f64>, default: f64, min: f64, max: f64, field: &str) -> Result<f64, String> {
+    let value = value.unwrap_or(default);
+    if !value.is_finite() || value < min || value > max {
+        return Err(format!("{field} must be between {min} and {max}"));
+    }
+
+    Ok(value)
+}
+
+impl CropDetectSettings {
+    pub fn from_payload(payload: &CropDetectPayload) -> Result<Self, String> {
+        let samples = checked(payload.samples, DEFAULT_SAMPLES, 1.0, 50.0, "samples")?;
+        let round = checked(payload.round, DEFAULT_ROUND, 1.0, 256.0, "round")?;
+        if samples.fract() != 0.0 || round.fract() != 0.0 {
+            return Err("samples and round must be integers".to_string());
+        }
+
+        Ok(Self {
+            samples: samples as usize,
+            sample_duration_sec: checked(
+                payload.sample_duration_sec,
+                DEFAULT_SAMPLE_DURATION_SEC,
+                0.1,
+                60.0,
+                "sampleDurationSec",
+            )?,
+            limit: checked(payload.limit, DEFAULT_LIMIT, 0.0, 255.0, "limit")?,
+            round: round as u64,
+        })
+    }
+}
+
+/// Seek points at the middle of `count` equal slices, pulled back so every sample ends
+/// before the input does. Skipping the very start avoids black intro frames.
+pub fn sample_points(duration_sec: f64, count: usize, sample_duration_sec: f64) -> Vec<f64> {
+    let latest = (duration_sec - sample_duration_sec).max(0.0);
+    (0..count)
+        .map(|index| ((index as f64 + 0.5) * duration_sec / count as f64).min(latest))
+        .collect()
+}
+
+pub fn cropdetect_args(input_path: &str, seek_sec: f64, settings: &CropDetectSettings) -> Vec<String> {
+    vec![
+        "-hide_banner".to_string(),
+        "-nostats".to_string(),
+        "-ss".to_string(),
+        format_seconds(seek_sec),
+        "-i".to_string(),
+        input_path.to_string(),
+        "-t".to_string(),
+        format_seconds(settings.sample_duration_sec),
+        "-map".to_string(),
+        "0:v:0".to_string(),
+        // reset=0 grows one bounding box over the whole sample, so the last line is its result.
+        "-vf".to_string(),
+        format!(
+            "cropdetect=limit={}:round={}:reset=0",
+            format_seconds(settings.limit),
+            settings.round
+        ),
+        "-an".to_string(),
+        "-sn".to_string(),
+        "-dn".to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ]
+}
+
+fn parse_crop(text: &str) -> Option<CropRect> {
+    let mut parts = text.split(':').map(|part| part.parse::<u64>().ok());
+    let rect = CropRect {
+        width: parts.next()??,
+        height: parts.next()??,
+        x: parts.next()??,
+        y: parts.next()??,
+    };
+
+    (parts.next().is_none() && rect.area() > 0).then_some(rect)
+}
+
+/// Collects the `crop=w:h:x:y` values cropdetect logs. All-black frames produce negative
+/// sizes and are skipped.
+pub fn parse_crop_lines(stderr: &str) -> Vec<CropRect> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let value = &line[line.rfind("crop=")? + "crop=".len()..];
+            parse_crop(value.split_whitespace().next()?)
+        })
+        .collect()
+}
+
+/// The crop most samples agree on; ties go to the larger area so no picture is cut off.
+pub fn most_stable_crop(crops: &[CropRect]) -> Option<(CropRect, usize)> {
+    let mut votes: HashMap<CropRect, usize> = HashMap::new();
+    for crop in crops {
+        *votes.entry(*crop).or_default() += 1;
+    }
+
+    votes
+        .into_iter()
+        .max_by_key(|(crop, count)| (*count, crop.area(), crop.width))
+}
+
+/// Runs cropdetect at each sample point and returns the most stable crop.
+pub fn detect_crop(payload: &CropDetectPayload) -> Result<CropDetectResult, String> {
+    let input_path = text_from_option(&payload.input_path);
+    if !has_text(&input_path) {
+        return Err("inputPath is required".to_string());
+    }
+
+    let settings = CropDetectSettings::from_payload(payload)?;
+    let configured_ffprobe = text_from_option(&payload.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let duration = probe_media(&ffprobe_path, &configured_ffprobe, &input_path)?
+        .duration_sec
+        .filter(|duration| *duration > 0.0)
+        .ok_or_else(|| "无法读取输入时长，不能选择采样位置".to_string())?;
+
+    let configured_ffmpeg = text_from_option(&payload.ffmpeg_path);
+    let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+    let mut per_sample = Vec::new();
+    for seek in sample_points(duration, settings.samples, settings.sample_duration_sec) {
+        let args = cropdetect_args(&input_path, seek, &settings);
+        let (_, stderr) = run_command(&ffmpeg_path, &args, "ffmpeg", &configured_ffmpeg)?;
+        if let Some(crop) = parse_crop_lines(&stderr).last() {
+            per_sample.push(*crop);
+        }
+    }
+
+    let (crop, votes) = most_stable_crop(&per_sample).ok_or_else(|| "没有检测到可用的裁剪区域".to_string())?;
+    let node = crop.to_node();
+
+    Ok(CropDetectResult {
+        crop,
+        filter: node.to_filter()?,
+        node,
+        votes,
+        samples: per_sample.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_crop_lines_skips_black_frames() {
+        let stderr = "\
+[Parsed_cropdetect_0 @ 0x5581] x1:1919 x2:0 y1:1079 y2:0 w:-1904 h:-1072 x:1912 y:1080 pts:0 t:0.000000 crop=-1904:-1072:1912:1080
+[Parsed_cropdetect_0 @ 0x5581] x1:0 x2:1919 y1:138 y2:941 w:1920 h:800 x:0 y:140 pts:1001 t:0.041708 crop=1920:800:0:140
+frame=   48 fps=0.0 q=-0.0 Lsize=N/A time=00:00:02.00 bitrate=N/A speed=10x";
+
+        assert_eq!(
+            parse_crop_lines(stderr),
+            vec![CropRect {
+                width: 1920,
+                height: 800,
+                x: 0,
+                y: 140,
+            }]
+        );
+    }
+
+    #[test]
+    fn most_stable_crop_prefers_majority_then_larger_area() {
+        let wide = CropRect {
+            width: 1920,
+            height: 800,
+            x: 0,
+            y: 140,
+        };
+        let full = CropRect {
+            width: 1920,
+            height: 1072,
+            x: 0,
+            y: 4,
+        };
+        let dark = CropRect {
+            width: 1440,
+            height: 800,
+            x: 240,
+            y: 140,
+        };
+
+        assert_eq!(most_stable_crop(&[wide, dark, wide, full]), Some((wide, 2)));
+        assert_eq!(most_stable_crop(&[dark, full]), Some((full, 1)));
+        assert_eq!(most_stable_crop(&[]), None);
+        assert_eq!(wide.to_node().to_filter().unwrap(), "crop=1920:800:0:140");
+    }
+
+    #[test]
+    fn sample_points_stay_inside_the_input() {
+        assert_eq!(sample_points(60.0, 3, 2.0), vec![10.0, 30.0, 50.0]);
+        assert_eq!(sample_points(3.0, 2, 2.0), vec![0.75, 1.0]);
+
+        let settings = CropDetectSettings::from_payload(&CropDetectPayload {
+            limit: Some(16.0),
+            round: Some(2.0),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(cropdetect_args("in.mkv", 12.5, &settings)
+            .join(" ")
+            .contains("-ss 12.5 -i in.mkv -t 2 -map 0:v:0 -vf cropdetect=limit=16:round=2:reset=0"));
+        assert!(CropDetectSettings::from_payload(&CropDetectPayload {
+            samples: Some(2.5),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
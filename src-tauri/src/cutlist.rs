@@ -0,0 +1,319 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::job::{has_text, text_from_option, JobPayload};
+use crate::probe::{probe_media, resolve_executable_path};
+use crate::template::format_seconds;
+use crate::thumbnails::fnv1a;
+
+/// Pieces shorter than this (e.g. left between two adjacent removed ranges) are dropped.
+const MIN_RANGE_SEC: f64 = 0.001;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeRange {
+    pub start_sec: f64,
+    pub end_sec: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutMethod {
+    /// trim/atrim + concat in a filter graph: frame-accurate, re-encoded.
+    Filter,
+    /// Concat demuxer with inpoint/outpoint over the same file: lossless, each piece starts at
+    /// the keyframe at or before its start.
+    Copy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CutPlan {
+    pub method: CutMethod,
+    /// Input ranges to keep, sorted and non-overlapping.
+    pub keep: Vec<TimeRange>,
+}
+
+pub fn has_cut_list(job: &JobPayload) -> bool {
+    job.cut_ranges.as_ref().is_some_and(|ranges| !ranges.is_empty())
+}
+
+/// Validates the ranges against the input length and turns them into the ranges to keep.
+pub fn resolve_keep_ranges(ranges: &[TimeRange], mode: &str, duration_sec: f64) -> Result<Vec<TimeRange>, String> {
+    for (index, range) in ranges.iter().enumerate() {
+        if !range.start_sec.is_finite() || !range.end_sec.is_finite() || range.start_sec < 0.0 {
+            return Err(format!("cutRanges[{index}] must have finite, non-negative times"));
+        }
+        if range.end_sec <= range.start_sec {
+            return Err(format!("cutRanges[{index}] must end after it starts"));
+        }
+        if range.end_sec > duration_sec + MIN_RANGE_SEC {
+            return Err(format!(
+                "cutRanges[{index}] ends at {}s, after the end of the input ({}s)",
+                format_seconds(range.end_sec),
+                format_seconds(duration_sec)
+            ));
+        }
+    }
+
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by(|left, right| left.start_sec.total_cmp(&right.start_sec));
+    if let Some(pair) = sorted.windows(2).find(|pair| pair[1].start_sec < pair[0].end_sec) {
+        return Err(format!(
+            "cutRanges overlap: {}–{}s and {}–{}s",
+            format_seconds(pair[0].start_sec),
+            format_seconds(pair[0].end_sec),
+            format_seconds(pair[1].start_sec),
+            format_seconds(pair[1].end_sec)
+        ));
+    }
+
+    let keep = match mode {
+        "" | "keep" => sorted,
+        "remove" => {
+            let mut keep = Vec::new();
+            let mut cursor = 0.0;
+            for range in &sorted {
+                keep.push(TimeRange {
+                    start_sec: cursor,
+                    end_sec: range.start_sec,
+                });
+                cursor = range.end_sec;
+            }
+            keep.push(TimeRange {
+                start_sec: cursor,
+                end_sec: duration_sec,
+            });
+            keep.retain(|range| range.end_sec - range.start_sec >= MIN_RANGE_SEC);
+            keep
+        }
+        other => return Err(format!("Unsupported cutMode: {other}")),
+    };
+
+    if keep.is_empty() {
+        return Err("cutRanges remove the whole input".to_string());
+    }
+    Ok(keep)
+}
+
+/// Probes the input and resolves the job's cut list; `None` when the job has none.
+pub fn cut_plan(job: &JobPayload) -> Result<Option<CutPlan>, String> {
+    let Some(ranges) = job.cut_ranges.as_ref().filter(|ranges| !ranges.is_empty()) else {
+        return Ok(None);
+    };
+
+    let method = match text_from_option(&job.cut_method).as_str() {
+        "" | "filter" => CutMethod::Filter,
+        "copy" => CutMethod::Copy,
+        other => return Err(format!("Unsupported cutMethod: {other}")),
+    };
+    // The ranges are absolute input times, so a second, single-range trim would be ambiguous.
+    if [&job.start_time, &job.duration, &job.end_time]
+        .into_iter()
+        .any(|value| has_text(&text_from_option(value)))
+    {
+        return Err("cutRanges cannot be combined with startTime, endTime or duration".to_string());
+    }
+
+    let configured_ffprobe = text_from_option(&job.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let duration_sec = probe_media(&ffprobe_path, &configured_ffprobe, &text_from_option(&job.input_path))?
+        .duration_sec
+        .ok_or_else(|| "无法读取输入时长，不能校验剪辑区间".to_string())?;
+
+    let keep = resolve_keep_ranges(ranges, &text_from_option(&job.cut_mode), duration_sec)?;
+    Ok(Some(CutPlan { method, keep }))
+}
+
+/// Length of the output once the cut list is applied; `None` when the job has none.
+pub fn cut_duration_sec(job: &JobPayload) -> Result<Option<f64>, String> {
+    Ok(cut_plan(job)?.map(|plan| plan.keep.iter().map(|range| range.end_sec - range.start_sec).sum()))
+}
+
+/// Trims every kept range from the first video/audio stream, joins them with `concat` and
+/// runs the job's own filter chains on the result. Outputs are labelled `[vout]` / `[aout]`.
+pub fn cut_filter_graph(keep: &[TimeRange], video_chain: Option<&[String]>, audio_chain: Option<&[String]>) -> String {
+    let mut graph = Vec::new();
+    let mut concat_inputs = String::new();
+    for (index, range) in keep.iter().enumerate() {
+        let bounds = format!(
+            "start={}:end={}",
+            format_seconds(range.start_sec),
+            format_seconds(range.end_sec)
+        );
+        if video_chain.is_some() {
+            graph.push(format!("[0:v:0]trim={bounds},setpts=PTS-STARTPTS[v{index}]"));
+            concat_inputs.push_str(&format!("[v{index}]"));
+        }
+        if audio_chain.is_some() {
+            graph.push(format!("[0:a:0]atrim={bounds},asetpts=PTS-STARTPTS[a{index}]"));
+            concat_inputs.push_str(&format!("[a{index}]"));
+        }
+    }
+
+    let mut concat = format!(
+        "{concat_inputs}concat=n={}:v={}:a={}",
+        keep.len(),
+        u8::from(video_chain.is_some()),
+        u8::from(audio_chain.is_some())
+    );
+    let mut tails = Vec::new();
+    for (kind, chain) in [("v", video_chain), ("a", audio_chain)] {
+        let Some(chain) = chain else { continue };
+        if chain.is_empty() {
+            concat.push_str(&format!("[{kind}out]"));
+        } else {
+            concat.push_str(&format!("[{kind}cut]"));
+            tails.push(format!("[{kind}cut]{}[{kind}out]", chain.join(",")));
+        }
+    }
+    graph.push(concat);
+    graph.extend(tails);
+
+    graph.join(";")
+}
+
+/// ffconcat quoting: the path goes in single quotes, and a literal quote closes, escapes and reopens.
+fn quote_concat_path(path: &str) -> String {
+    format!("'{}'", path.replace('\'', r"'\''"))
+}
+
+pub fn concat_list(input_path: &str, keep: &[TimeRange]) -> String {
+    let mut text = String::from("ffconcat version 1.0\n");
+    for range in keep {
+        text.push_str(&format!(
+            "file {}\ninpoint {}\noutpoint {}\n",
+            quote_concat_path(input_path),
+            format_seconds(range.start_sec),
+            format_seconds(range.end_sec)
+        ));
+    }
+    text
+}
+
+/// Where the concat list for a copy cut lives in the temp dir, and its contents. The name is a
+/// hash of the contents, so building the args only needs the path and the runner writes it.
+pub fn concat_list_file(input_path: &str, keep: &[TimeRange]) -> (PathBuf, String) {
+    // Relative entries would resolve against the list's folder, not the working directory.
+    let absolute = fs::canonicalize(input_path).unwrap_or_else(|_| PathBuf::from(input_path));
+    let text = concat_list(&absolute.to_string_lossy(), keep);
+    let path = std::env::temp_dir()
+        .join("ffmpeg-gui-cuts")
+        .join(format!("{:016x}.ffconcat", fnv1a(text.as_bytes())));
+
+    (path, text)
+}
+
+/// Writes the concat list a copy cut reads; does nothing for other jobs. Called right before
+/// ffmpeg starts so previews and exports never touch the disk.
+pub fn write_copy_cut_list(job: &JobPayload) -> Result<(), String> {
+    let Some(CutPlan {
+        method: CutMethod::Copy,
+        keep,
+    }) = cut_plan(job)?
+    else {
+        return Ok(());
+    };
+
+    let (path, text) = concat_list_file(&text_from_option(&job.input_path), &keep);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|error| format!("创建剪辑列表目录失败：{error}"))?;
+    }
+    fs::write(&path, text).map_err(|error| format!("写入剪辑列表失败：{error}"))
+}
+
+/// Stream-copies every stream through the concat demuxer. Encoding settings and filters don't
+/// apply; only the stream toggles and muxer options carry over.
+pub fn copy_cut_args(job: &JobPayload, list_path: &Path, video: bool, audio: bool) -> Vec<String> {
+    let mut args = vec![if job.overwrite.unwrap_or(true) { "-y" } else { "-n" }.to_string()];
+    args.extend([
+        "-f".to_string(),
+        "concat".to_string(),
+        "-safe".to_string(),
+        "0".to_string(),
+        "-i".to_string(),
+        list_path.to_string_lossy().to_string(),
+        "-map".to_string(),
+        "0".to_string(),
+        "-c".to_string(),
+        "copy".to_string(),
+    ]);
+    if !video {
+        args.push("-vn".to_string());
+    }
+    if !audio {
+        args.push("-an".to_string());
+    }
+    if job.movflags_faststart.unwrap_or(false) {
+        args.push("-movflags".to_string());
+        args.push("+faststart".to_string());
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start_sec: f64, end_sec: f64) -> TimeRange {
+        TimeRange { start_sec, end_sec }
+    }
+
+    #[test]
+    fn resolve_keep_ranges_validates_and_inverts() {
+        let ranges = [range(300.0, 360.0), range(0.0, 10.5)];
+        assert_eq!(
+            resolve_keep_ranges(&ranges, "keep", 600.0).unwrap(),
+            vec![range(0.0, 10.5), range(300.0, 360.0)]
+        );
+        assert_eq!(
+            resolve_keep_ranges(&ranges, "remove", 600.0).unwrap(),
+            vec![range(10.5, 300.0), range(360.0, 600.0)]
+        );
+
+        assert_eq!(
+            resolve_keep_ranges(&[range(0.0, 20.0), range(15.0, 30.0)], "keep", 600.0).unwrap_err(),
+            "cutRanges overlap: 0–20s and 15–30s"
+        );
+        assert!(resolve_keep_ranges(&[range(590.0, 620.0)], "keep", 600.0).is_err());
+        assert!(resolve_keep_ranges(&[range(20.0, 10.0)], "keep", 600.0).is_err());
+        assert!(resolve_keep_ranges(&[range(0.0, 600.0)], "remove", 600.0).is_err());
+    }
+
+    #[test]
+    fn filter_graph_trims_concats_then_runs_job_filters() {
+        let keep = [range(0.0, 10.5), range(300.0, 360.0)];
+        let video = vec!["scale=1280:-1:flags=lanczos".to_string()];
+
+        assert_eq!(
+            cut_filter_graph(&keep, Some(&video), Some(&[])),
+            "[0:v:0]trim=start=0:end=10.5,setpts=PTS-STARTPTS[v0];\
+             [0:a:0]atrim=start=0:end=10.5,asetpts=PTS-STARTPTS[a0];\
+             [0:v:0]trim=start=300:end=360,setpts=PTS-STARTPTS[v1];\
+             [0:a:0]atrim=start=300:end=360,asetpts=PTS-STARTPTS[a1];\
+             [v0][a0][v1][a1]concat=n=2:v=1:a=1[vcut][aout];\
+             [vcut]scale=1280:-1:flags=lanczos[vout]"
+        );
+        assert_eq!(
+            cut_filter_graph(&keep[..1], None, Some(&["volume=2".to_string()])),
+            "[0:a:0]atrim=start=0:end=10.5,asetpts=PTS-STARTPTS[a0];[a0]concat=n=1:v=0:a=1[acut];[acut]volume=2[aout]"
+        );
+    }
+
+    #[test]
+    fn concat_list_uses_in_and_out_points() {
+        assert_eq!(
+            concat_list("/videos/it's.mp4", &[range(0.0, 10.5), range(300.0, 360.0)]),
+            "ffconcat version 1.0\n\
+             file '/videos/it'\\''s.mp4'\ninpoint 0\noutpoint 10.5\n\
+             file '/videos/it'\\''s.mp4'\ninpoint 300\noutpoint 360\n"
+        );
+
+        // Naming the list is pure; only the runner writes it.
+        let keep = [range(0.0, 10.5)];
+        let (path, text) = concat_list_file("/videos/missing.mp4", &keep);
+        assert_eq!(text, concat_list("/videos/missing.mp4", &keep));
+        assert!(path.ends_with(format!("ffmpeg-gui-cuts/{:016x}.ffconcat", fnv1a(text.as_bytes()))));
+        assert!(!path.exists());
+    }
+}
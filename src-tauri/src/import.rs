@@ -0,0 +1,377 @@
+use serde::Serialize;
+
+use crate::command::split_command_line;
+use crate::job::{ExtraArg, JobPayload};
+
+/// Preset with no defaults, so an imported command is rebuilt from its own options only.
+pub const IMPORTED_PRESET: &str = "custom";
+
+/// Options without a value that may appear in scripts but have no visual field.
+const VALUELESS_OPTIONS: &[&str] = &[
+    "-hide_banner",
+    "-nostdin",
+    "-stats",
+    "-nostats",
+    "-shortest",
+    "-dn",
+    "-sn",
+    "-re",
+    "-copyts",
+    "-start_at_zero",
+    "-benchmark",
+];
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedCommand {
+    pub job: JobPayload,
+    pub warnings: Vec<String>,
+}
+
+fn is_ffmpeg_binary(token: &str) -> bool {
+    let name = token.rsplit(['/', '\\']).next().unwrap_or(token).to_ascii_lowercase();
+    name == "ffmpeg" || name == "ffmpeg.exe"
+}
+
+fn looks_like_option(token: &str) -> bool {
+    token.len() > 1 && token.starts_with('-') && !token[1..].starts_with(|ch: char| ch.is_ascii_digit() || ch == '.')
+}
+
+fn parse_number(option: &str, value: &str) -> Result<f64, String> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("{option} 的值不是数字：{value}"))
+}
+
+fn parse_positive_integer(value: &str) -> Option<f64> {
+    let number = value.trim().parse::<u64>().ok()?;
+    (number > 0).then_some(number as f64)
+}
+
+fn next_value(tokens: &[String], index: &mut usize, option: &str) -> Result<String, String> {
+    let value = tokens.get(*index).ok_or_else(|| format!("{option} 缺少参数值"))?;
+    *index += 1;
+    Ok(value.clone())
+}
+
+/// Splits a filter chain on top-level commas, honouring `\,` escapes and single quotes.
+fn split_filter_chain(chain: &str) -> Vec<String> {
+    let mut filters = Vec::new();
+    let mut current = String::new();
+    let mut in_quote = false;
+    let mut escape_next = false;
+
+    for ch in chain.chars() {
+        if escape_next {
+            current.push(ch);
+            escape_next = false;
+            continue;
+        }
+
+        match ch {
+            '\\' => {
+                current.push(ch);
+                escape_next = true;
+            }
+            '\'' => {
+                current.push(ch);
+                in_quote = !in_quote;
+            }
+            ',' if !in_quote => filters.push(std::mem::take(&mut current)),
+            _ => current.push(ch),
+        }
+    }
+
+    filters.push(current);
+    filters
+}
+
+fn parse_scale_dimension(text: &str) -> Option<Option<f64>> {
+    if text == "-1" {
+        return Some(None);
+    }
+
+    parse_positive_integer(text).map(Some)
+}
+
+/// Peels the leading `fps=` / `scale=W:H:flags=lanczos` filters that `build_visual_args`
+/// generates back into fields; anything else stays free text.
+fn absorb_video_filters(job: &mut JobPayload, chain: &str) {
+    let mut filters = split_filter_chain(chain).into_iter().peekable();
+
+    if let Some(fps) = filters
+        .peek()
+        .and_then(|filter| filter.strip_prefix("fps="))
+        .and_then(parse_positive_integer)
+    {
+        job.fps = Some(fps);
+        filters.next();
+    }
+
+    let scale = filters.peek().and_then(|filter| {
+        let params = filter.strip_prefix("scale=")?.strip_suffix(":flags=lanczos")?;
+        let (width, height) = params.split_once(':')?;
+        let width = parse_scale_dimension(width)?;
+        let height = parse_scale_dimension(height)?;
+        (width.is_some() || height.is_some()).then_some((width, height))
+    });
+
+    if let Some((width, height)) = scale {
+        job.scale_width = width;
+        job.scale_height = height;
+        filters.next();
+    }
+
+    let rest: Vec<String> = filters.collect();
+    if !rest.is_empty() {
+        job.video_filter = Some(rest.join(","));
+    }
+}
+
+/// Maps a legacy ffmpeg command line onto visual-mode fields. Options without a visual
+/// field are kept, in order, as `extra_args`.
+pub fn import_command_line(command_line: &str) -> Result<ImportedCommand, String> {
+    // Shell scripts wrap long commands with backslash-newline continuations.
+    let joined = command_line.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut tokens = split_command_line(&joined)?;
+    let mut warnings = Vec::new();
+
+    let mut job = JobPayload {
+        mode: Some("visual".to_string()),
+        preset: Some(IMPORTED_PRESET.to_string()),
+        overwrite: Some(false),
+        ..Default::default()
+    };
+
+    if tokens.first().is_some_and(|token| is_ffmpeg_binary(token)) {
+        let binary = tokens.remove(0);
+        if binary.contains(['/', '\\']) {
+            job.ffmpeg_path = Some(binary);
+        }
+    }
+
+    let mut extra_args: Vec<ExtraArg> = Vec::new();
+    let mut seen_input = false;
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let token = tokens[index].clone();
+        index += 1;
+
+        if !looks_like_option(&token) {
+            if index == tokens.len() {
+                job.output_path = Some(token);
+            } else {
+                warnings.push(format!("忽略了无法识别的参数：{token}"));
+            }
+            continue;
+        }
+
+        // Everything except the last token may be an option value.
+        let has_value = index < tokens.len().saturating_sub(1) || (index < tokens.len() && token == "-i");
+
+        match token.as_str() {
+            "-y" => job.overwrite = Some(true),
+            "-n" => job.overwrite = Some(false),
+            "-vn" => job.disable_video = Some(true),
+            "-an" => job.disable_audio = Some(true),
+            "-i" => {
+                if seen_input {
+                    return Err("可视化模式只支持一个输入文件".to_string());
+                }
+                seen_input = true;
+                job.input_path = Some(next_value(&tokens, &mut index, &token)?);
+            }
+            "-ss" if !seen_input => job.start_time = Some(next_value(&tokens, &mut index, &token)?),
+            "-t" if !seen_input => job.duration = Some(next_value(&tokens, &mut index, &token)?),
+            "-to" if !seen_input => job.end_time = Some(next_value(&tokens, &mut index, &token)?),
+            "-c:v" | "-codec:v" | "-vcodec" => job.video_codec = Some(next_value(&tokens, &mut index, &token)?),
+            "-c:a" | "-codec:a" | "-acodec" => job.audio_codec = Some(next_value(&tokens, &mut index, &token)?),
+            "-preset" => job.speed_preset = Some(next_value(&tokens, &mut index, &token)?),
+            "-crf" => job.crf = Some(parse_number("-crf", &next_value(&tokens, &mut index, &token)?)?),
+            "-b:v" => job.video_bitrate = Some(next_value(&tokens, &mut index, &token)?),
+            "-b:a" => job.audio_bitrate = Some(next_value(&tokens, &mut index, &token)?),
+            "-q:a" | "-aq" => job.audio_quality = Some(next_value(&tokens, &mut index, &token)?),
+            "-ar" => job.sample_rate = Some(parse_number("-ar", &next_value(&tokens, &mut index, &token)?)?),
+            "-ac" => job.channels = Some(parse_number("-ac", &next_value(&tokens, &mut index, &token)?)?),
+            "-threads" => job.threads = Some(parse_number("-threads", &next_value(&tokens, &mut index, &token)?)?),
+            "-pix_fmt" => job.pixel_format = Some(next_value(&tokens, &mut index, &token)?),
+            "-f" if seen_input => job.format = Some(next_value(&tokens, &mut index, &token)?),
+            "-map" if job.map_field.is_none() => job.map_field = Some(next_value(&tokens, &mut index, &token)?),
+            "-loop" if seen_input => job.loop_value = Some(next_value(&tokens, &mut index, &token)?),
+            "-vf" | "-filter:v" if job.video_filter.is_none() && job.fps.is_none() => {
+                absorb_video_filters(&mut job, &next_value(&tokens, &mut index, &token)?);
+            }
+            "-af" | "-filter:a" if job.audio_filter.is_none() => {
+                job.audio_filter = Some(next_value(&tokens, &mut index, &token)?);
+            }
+            "-movflags" if tokens.get(index).map(String::as_str) == Some("+faststart") => {
+                index += 1;
+                job.movflags_faststart = Some(true);
+            }
+            // The visual fps field is applied as an `fps=` filter, which only takes whole rates.
+            "-r" if seen_input
+                && tokens
+                    .get(index)
+                    .and_then(|value| parse_positive_integer(value))
+                    .is_some() =>
+            {
+                job.fps = tokens.get(index).and_then(|value| parse_positive_integer(value));
+                index += 1;
+            }
+            "-s" if seen_input => {
+                let value = next_value(&tokens, &mut index, &token)?;
+                let (width, height) = value
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((parse_positive_integer(width)?, parse_positive_integer(height)?)))
+                    .ok_or_else(|| format!("-s 的值无效：{value}"))?;
+                job.scale_width = Some(width);
+                job.scale_height = Some(height);
+            }
+            _ => {
+                if !seen_input {
+                    warnings.push(format!("输入前的选项 {token} 在可视化模式中会作为输出选项追加，请确认"));
+                }
+
+                let value = if VALUELESS_OPTIONS.contains(&token.as_str())
+                    || !has_value
+                    || tokens.get(index).is_some_and(|next| looks_like_option(next))
+                {
+                    None
+                } else {
+                    Some(next_value(&tokens, &mut index, &token)?)
+                };
+
+                extra_args.push(ExtraArg {
+                    key: Some(token),
+                    value,
+                    enabled: Some(true),
+                });
+            }
+        }
+    }
+
+    if !seen_input {
+        warnings.push("命令中没有 -i 输入文件".to_string());
+    }
+
+    if job.output_path.is_none() {
+        warnings.push("命令中没有输出文件".to_string());
+    }
+
+    if !extra_args.is_empty() {
+        job.extra_args = Some(extra_args);
+    }
+
+    Ok(ImportedCommand { job, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::format_command_preview;
+    use crate::job::build_visual_args;
+
+    fn import(command_line: &str) -> JobPayload {
+        import_command_line(command_line).expect("import failed").job
+    }
+
+    /// Importing the preview of a rebuilt job must give back the same job.
+    fn assert_round_trip(command_line: &str) {
+        let job = import(command_line);
+        let rebuilt = build_visual_args(&job).expect("build failed");
+        let preview = format_command_preview("ffmpeg", &rebuilt);
+        assert_eq!(import(&preview), job, "preview: {preview}");
+    }
+
+    #[test]
+    fn import_maps_recognized_options_to_visual_fields() {
+        let job = import(
+            "/usr/local/bin/ffmpeg -y -ss 00:01:00 -t 30 -i \"in put.mov\" -c:v libx264 -preset slow -crf 20 \
+             -b:a 128k -r 30 -s 1280x720 -pix_fmt yuv420p -movflags +faststart -map 0 -tune film out.mp4",
+        );
+
+        assert_eq!(job.ffmpeg_path.as_deref(), Some("/usr/local/bin/ffmpeg"));
+        assert_eq!(job.overwrite, Some(true));
+        assert_eq!(job.start_time.as_deref(), Some("00:01:00"));
+        assert_eq!(job.duration.as_deref(), Some("30"));
+        assert_eq!(job.input_path.as_deref(), Some("in put.mov"));
+        assert_eq!(job.video_codec.as_deref(), Some("libx264"));
+        assert_eq!(job.speed_preset.as_deref(), Some("slow"));
+        assert_eq!(job.crf, Some(20.0));
+        assert_eq!(job.audio_bitrate.as_deref(), Some("128k"));
+        assert_eq!(job.fps, Some(30.0));
+        assert_eq!((job.scale_width, job.scale_height), (Some(1280.0), Some(720.0)));
+        assert_eq!(job.pixel_format.as_deref(), Some("yuv420p"));
+        assert_eq!(job.movflags_faststart, Some(true));
+        assert_eq!(job.map_field.as_deref(), Some("0"));
+        assert_eq!(job.output_path.as_deref(), Some("out.mp4"));
+        assert_eq!(
+            job.extra_args,
+            Some(vec![ExtraArg {
+                key: Some("-tune".to_string()),
+                value: Some("film".to_string()),
+                enabled: Some(true),
+            }])
+        );
+    }
+
+    #[test]
+    fn import_keeps_unknown_options_and_output_seeks_as_extra_args() {
+        let imported =
+            import_command_line("ffmpeg -hide_banner -i in.mkv -ss 10 -c copy -shortest -metadata title=Demo out.mkv")
+                .expect("import failed");
+        let keys: Vec<(Option<String>, Option<String>)> = imported
+            .job
+            .extra_args
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|arg| (arg.key, arg.value))
+            .collect();
+
+        assert_eq!(
+            keys,
+            vec![
+                (Some("-hide_banner".to_string()), None),
+                (Some("-ss".to_string()), Some("10".to_string())),
+                (Some("-c".to_string()), Some("copy".to_string())),
+                (Some("-shortest".to_string()), None),
+                (Some("-metadata".to_string()), Some("title=Demo".to_string())),
+            ]
+        );
+        assert_eq!(imported.warnings.len(), 1);
+        assert_eq!(imported.job.start_time, None);
+    }
+
+    #[test]
+    fn import_splits_generated_filter_chain_back_into_fields() {
+        let job = import("ffmpeg -i a.mov -vf \"fps=24,scale=640:-1:flags=lanczos,eq=contrast=1.1\" b.mp4");
+        assert_eq!(job.fps, Some(24.0));
+        assert_eq!((job.scale_width, job.scale_height), (Some(640.0), None));
+        assert_eq!(job.video_filter.as_deref(), Some("eq=contrast=1.1"));
+        assert_eq!(import("ffmpeg -i a.wav -af volume=2 b.mp3").audio_filter.as_deref(), Some("volume=2"));
+    }
+
+    #[test]
+    fn imported_commands_round_trip_through_visual_builder() {
+        for command_line in [
+            "ffmpeg -y -i in.mov -c:v libx265 -preset medium -crf 28 -c:a aac -b:a 160k out.mp4",
+            "ffmpeg -ss 5 -t 10 -i in.mov -vn -c:a libmp3lame -q:a 2 -ar 44100 -ac 2 out.mp3",
+            "ffmpeg -i in.mov -an -vf \"fps=12,scale=480:-1:flags=lanczos\" -loop 0 out.gif",
+            "ffmpeg -i in.mov -vf \"drawtext=text='a\\,b':x=10,format=yuv420p\" -threads 4 -f mp4 -map 0:v:0 out",
+            "ffmpeg -i in.mov -r 25 -s 1920x1080 -movflags +faststart -metadata title=x -x264-params keyint=60 o.mp4",
+            "ffmpeg -y \\\n  -i in.mov \\\n  -c:v copy -c:a copy out.mkv",
+        ] {
+            assert_round_trip(command_line);
+        }
+    }
+
+    #[test]
+    fn import_rejects_multiple_inputs() {
+        assert!(import_command_line("ffmpeg -i a.mov -i b.wav out.mp4").is_err());
+    }
+}
@@ -0,0 +1,1256 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::audio_filter::build_audio_filters;
+use crate::command::split_command_line;
+use crate::cutlist::{
+    concat_list_file, copy_cut_args, cut_filter_graph, cut_plan, has_cut_list, CutMethod, CutPlan, TimeRange,
+};
+use crate::loudnorm::{loudness_target, loudnorm_filter, LoudnessMeasurement, LOUDNORM_SAMPLE_RATE};
+use crate::probe::{probe_media, resolve_executable_path};
+use crate::subtitle::{
+    burn_in_filter, push_soft_subtitle_args, subtitle_plan, SubtitleMode, SubtitlePlan, SubtitleSource,
+};
+use crate::template::{
+    current_date, format_seconds, insert_path_values, is_valid_variable_name, parse_templates, MEDIA_PLACEHOLDERS,
+    PATH_PLACEHOLDERS,
+};
+use crate::timecode::{needs_frame_rate, parse_timecode};
+use crate::video_filter::{build_filter_nodes, VideoFilterNode};
+
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+pub const OUTPUT_PLACEHOLDER: &str = "{output}";
+/// How far before the start a hybrid seek lands; the rest is decoded and dropped.
+const HYBRID_PREROLL_SEC: f64 = 10.0;
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtraArg {
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobPayload {
+    pub mode: Option<String>,
+    pub ffmpeg_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    pub input_path: Option<String>,
+    pub output_path: Option<String>,
+    pub raw_args: Option<String>,
+    pub preset: Option<String>,
+    pub start_time: Option<String>,
+    pub duration: Option<String>,
+    /// Absolute end of the trim, emitted as `-to`; an alternative to `duration`.
+    pub end_time: Option<String>,
+    /// `input` (default) seeks before `-i`, `output` decodes from the start and drops frames
+    /// until the start time, `hybrid` seeks fast to shortly before it and decodes the rest.
+    pub seek_mode: Option<String>,
+    pub overwrite: Option<bool>,
+    pub crf: Option<f64>,
+    pub speed_preset: Option<String>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub pixel_format: Option<String>,
+    pub video_bitrate: Option<String>,
+    pub audio_bitrate: Option<String>,
+    pub audio_quality: Option<String>,
+    pub fps: Option<f64>,
+    pub scale_width: Option<f64>,
+    pub scale_height: Option<f64>,
+    pub sample_rate: Option<f64>,
+    pub channels: Option<f64>,
+    pub threads: Option<f64>,
+    pub format: Option<String>,
+    #[serde(rename = "map")]
+    pub map_field: Option<String>,
+    #[serde(rename = "loop")]
+    pub loop_value: Option<String>,
+    pub video_filter: Option<String>,
+    pub movflags_faststart: Option<bool>,
+    pub disable_video: Option<bool>,
+    pub disable_audio: Option<bool>,
+    pub extra_args: Option<Vec<ExtraArg>>,
+    pub timeout_sec: Option<f64>,
+    pub stall_timeout_sec: Option<f64>,
+    pub nice_level: Option<f64>,
+    pub priority_class: Option<String>,
+    pub cpu_cores: Option<Vec<u32>>,
+    /// User-defined `{name}` placeholders for raw mode.
+    pub variables: Option<BTreeMap<String, String>>,
+    pub subtitle_path: Option<String>,
+    pub subtitle_stream: Option<f64>,
+    /// `soft` (default) muxes a subtitle stream, `burn` renders it into the video.
+    pub subtitle_mode: Option<String>,
+    pub subtitle_language: Option<String>,
+    pub subtitle_default: Option<bool>,
+    /// Two-pass EBU R128 normalization; the first pass fills `loudness_measured`.
+    pub loudnorm: Option<bool>,
+    pub loudness_target: Option<f64>,
+    pub true_peak: Option<f64>,
+    pub loudness_range: Option<f64>,
+    pub loudness_measured: Option<LoudnessMeasurement>,
+    pub volume_gain_db: Option<f64>,
+    /// Playback speed factor for audio; chained `atempo` stages past 0.5–2.0.
+    pub tempo: Option<f64>,
+    pub audio_fade_in_sec: Option<f64>,
+    pub audio_fade_out_sec: Option<f64>,
+    /// `mono` / `stereo` downmix, `swap`, or `left` / `right` to keep one channel.
+    pub channel_remap: Option<String>,
+    pub highpass_hz: Option<f64>,
+    pub lowpass_hz: Option<f64>,
+    pub trim_silence: Option<bool>,
+    pub silence_threshold_db: Option<f64>,
+    /// Free-text audio filters appended after the structured ones.
+    pub audio_filter: Option<String>,
+    /// Typed video filters, applied in order before fps/scale and the free-text `video_filter`.
+    pub video_filters: Option<Vec<VideoFilterNode>>,
+    /// Scores the output against the input after a successful encode.
+    pub compute_metrics: Option<bool>,
+    /// `auto`, `vmaf` or `ssim_psnr`.
+    pub metrics_engine: Option<String>,
+    /// FFMETADATA file whose chapters are muxed into the output, e.g. from scene detection.
+    pub chapters_path: Option<String>,
+    /// Input ranges to keep (or remove, see `cut_mode`), joined in time order. Replaces
+    /// `start_time` / `duration` when set.
+    pub cut_ranges: Option<Vec<TimeRange>>,
+    /// `keep` (default) or `remove`.
+    pub cut_mode: Option<String>,
+    /// `filter` (default) re-encodes with frame-accurate trims, `copy` cuts losslessly at keyframes.
+    pub cut_method: Option<String>,
+    /// Moves `start_time` back to the keyframe at or before it, so stream-copied trims start
+    /// where the preview says instead of wherever the demuxer lands.
+    pub snap_to_keyframe: Option<bool>,
+}
+
+#[derive(Clone, Default)]
+struct VisualPresetDefaults {
+    video_codec: Option<&'static str>,
+    speed_preset: Option<&'static str>,
+    crf: Option<f64>,
+    audio_codec: Option<&'static str>,
+    audio_bitrate: Option<&'static str>,
+    audio_quality: Option<&'static str>,
+    disable_video: bool,
+    disable_audio: bool,
+    fps: Option<f64>,
+    scale_width: Option<f64>,
+    scale_height: Option<f64>,
+    loop_value: Option<&'static str>,
+}
+
+pub fn text_from_option(value: &Option<String>) -> String {
+    value
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+pub fn has_text(value: &str) -> bool {
+    !value.trim().is_empty()
+}
+
+pub(crate) fn round_positive(value: Option<f64>) -> Option<i64> {
+    let number = value?;
+    if !number.is_finite() || number <= 0.0 {
+        return None;
+    }
+
+    Some(number.round() as i64)
+}
+
+/// Writes the trim in plain seconds, whatever form the user typed it in, as input options.
+/// Analysis runs use this whatever the job's seek mode, since they only read the range.
+pub(crate) fn push_trim_args(args: &mut Vec<String>, trim: &TrimRange) {
+    let input_seek = TrimRange {
+        seek: SeekMode::Input,
+        ..*trim
+    };
+    args.extend(seek_trim_args(&input_seek).0);
+}
+
+/// The trim split into options placed before `-i` and options placed after the last input.
+pub(crate) fn seek_trim_args(trim: &TrimRange) -> (Vec<String>, Vec<String>) {
+    let mut before = Vec::new();
+    let mut after = Vec::new();
+    if trim.start_sec.is_some() {
+        let input_seek = trim.input_seek_sec();
+        if trim.seek == SeekMode::Input || input_seek > 0.0 {
+            before.push("-ss".to_string());
+            before.push(format_seconds(input_seek));
+        }
+        if trim.seek != SeekMode::Input {
+            after.push("-ss".to_string());
+            after.push(format_seconds(trim.filter_offset_sec()));
+        }
+    }
+
+    let end = if trim.seek == SeekMode::Input { &mut before } else { &mut after };
+    if let Some(duration) = trim.duration_sec {
+        end.push("-t".to_string());
+        end.push(format_seconds(duration));
+    } else if let Some(end_sec) = trim.end_sec {
+        // `-to` as an input option stays absolute; after a hybrid seek, output timestamps
+        // count from where the input seek landed.
+        let origin = if trim.seek == SeekMode::Input { 0.0 } else { trim.input_seek_sec() };
+        end.push("-to".to_string());
+        end.push(format_seconds(end_sec - origin));
+    }
+
+    (before, after)
+}
+
+pub(crate) fn push_option_if_value(args: &mut Vec<String>, key: &str, value: &Option<String>) {
+    let text = text_from_option(value);
+    if !has_text(&text) {
+        return;
+    }
+
+    args.push(key.to_string());
+    args.push(text);
+}
+
+/// A copy cut only remuxes, so anything that needs decoding or extra inputs is an error rather
+/// than silently dropped. Audio filters go last since a fade-out may probe the input.
+fn reject_copy_cut_settings(job: &JobPayload, defaults: &VisualPresetDefaults) -> Result<(), String> {
+    let video_filters = job.video_filters.as_ref().is_some_and(|nodes| !nodes.is_empty())
+        || has_text(&text_from_option(&job.video_filter))
+        || job.fps.or(defaults.fps).is_some()
+        || job.scale_width.or(defaults.scale_width).is_some()
+        || job.scale_height.or(defaults.scale_height).is_some();
+    let conflict = if subtitle_plan(job)?.is_some() {
+        Some("subtitles")
+    } else if has_text(&text_from_option(&job.chapters_path)) {
+        Some("chaptersPath")
+    } else if has_text(&text_from_option(&job.map_field)) {
+        Some("map")
+    } else if loudness_target(job)?.is_some() {
+        Some("loudnorm")
+    } else if video_filters {
+        Some("video filters")
+    } else if !build_audio_filters(job)?.is_empty() {
+        Some("audio filters")
+    } else {
+        None
+    };
+
+    match conflict {
+        Some(setting) => Err(format!("cutMethod copy cannot be combined with {setting}")),
+        None => Ok(()),
+    }
+}
+
+fn visual_defaults(preset: &str) -> VisualPresetDefaults {
+    match preset {
+        "h265" => VisualPresetDefaults {
+            video_codec: Some("libx265"),
+            speed_preset: Some("medium"),
+            crf: Some(28.0),
+            audio_codec: Some("aac"),
+            audio_bitrate: Some("160k"),
+            ..Default::default()
+        },
+        "mp3" => VisualPresetDefaults {
+            audio_codec: Some("libmp3lame"),
+            audio_quality: Some("2"),
+            disable_video: true,
+            ..Default::default()
+        },
+        "gif" => VisualPresetDefaults {
+            disable_audio: true,
+            fps: Some(12.0),
+            scale_width: Some(480.0),
+            loop_value: Some("0"),
+            ..Default::default()
+        },
+        // Imported command lines carry every option explicitly.
+        "custom" => VisualPresetDefaults::default(),
+        _ => VisualPresetDefaults {
+            video_codec: Some("libx264"),
+            speed_preset: Some("medium"),
+            crf: Some(23.0),
+            audio_codec: Some("aac"),
+            audio_bitrate: Some("192k"),
+            ..Default::default()
+        },
+    }
+}
+
+/// Where the trim's start is found; see `JobPayload::seek_mode`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SeekMode {
+    #[default]
+    Input,
+    Output,
+    Hybrid,
+}
+
+/// The job's trim fields in seconds; the start and end are always absolute input times.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrimRange {
+    pub start_sec: Option<f64>,
+    pub duration_sec: Option<f64>,
+    pub end_sec: Option<f64>,
+    pub seek: SeekMode,
+}
+
+impl TrimRange {
+    /// Output length the trim asks for, from `duration` or `end - start`.
+    pub fn length_sec(&self) -> Option<f64> {
+        self.duration_sec
+            .or_else(|| Some(self.end_sec? - self.start_sec.unwrap_or(0.0)))
+    }
+
+    /// Where the seek before `-i` lands; input timestamps restart there.
+    pub fn input_seek_sec(&self) -> f64 {
+        let start = self.start_sec.unwrap_or(0.0);
+        match self.seek {
+            SeekMode::Input => start,
+            SeekMode::Output => 0.0,
+            SeekMode::Hybrid => (start - HYBRID_PREROLL_SEC).max(0.0),
+        }
+    }
+
+    /// Time of the output's first frame as filters see it. Output-side `-ss` cuts after the
+    /// filter graph, so with output or hybrid seeking the frames before the start still pass
+    /// through it; time-based filters have to be shifted by this much.
+    pub fn filter_offset_sec(&self) -> f64 {
+        self.start_sec.unwrap_or(0.0) - self.input_seek_sec()
+    }
+
+    /// Output length for an input of `total_sec`: the requested length, cut short by the end of
+    /// the input, or everything after the start when nothing is requested.
+    pub fn effective_length_sec(&self, total_sec: Option<f64>) -> Option<f64> {
+        let remaining = total_sec.map(|total| (total - self.start_sec.unwrap_or(0.0)).max(0.0));
+        match (self.length_sec(), remaining) {
+            (Some(length), Some(remaining)) => Some(length.min(remaining)),
+            (length, remaining) => length.or(remaining),
+        }
+    }
+}
+
+/// Parses the trim fields. The input is probed only when needed: frame timecodes need its
+/// frame rate, and a negative start or end counts back from its end.
+pub(crate) fn resolve_trim(job: &JobPayload) -> Result<TrimRange, String> {
+    let start_text = text_from_option(&job.start_time);
+    let duration_text = text_from_option(&job.duration);
+    let end_text = text_from_option(&job.end_time);
+    let seek = match text_from_option(&job.seek_mode).as_str() {
+        "" | "input" => SeekMode::Input,
+        "output" => SeekMode::Output,
+        "hybrid" => SeekMode::Hybrid,
+        other => return Err(format!("Unsupported seekMode: {other}")),
+    };
+    if has_text(&duration_text) && has_text(&end_text) {
+        return Err("endTime cannot be combined with duration".to_string());
+    }
+
+    let needs_probe = [&start_text, &duration_text, &end_text]
+        .iter()
+        .any(|text| needs_frame_rate(text))
+        || start_text.trim_start().starts_with('-')
+        || end_text.trim_start().starts_with('-');
+
+    let (fps, total) = if needs_probe {
+        let input_path = text_from_option(&job.input_path);
+        if !has_text(&input_path) || input_path == INPUT_PLACEHOLDER {
+            return Err("帧时间码和负数开始/结束时间需要先选择输入文件".to_string());
+        }
+        let configured_ffprobe = text_from_option(&job.ffprobe_path);
+        let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+        let info = probe_media(&ffprobe_path, &configured_ffprobe, &input_path)?;
+        let fps = info
+            .streams
+            .iter()
+            .find(|stream| stream.codec_type.as_deref() == Some("video"))
+            .and_then(|stream| stream.frame_rate);
+        (fps, info.duration_sec)
+    } else {
+        (None, None)
+    };
+
+    let absolute = |text: &str| -> Result<Option<f64>, String> {
+        match has_text(text).then(|| parse_timecode(text, fps)).transpose()? {
+            Some(time) if time < 0.0 => {
+                let total = total.ok_or_else(|| "无法读取输入时长，不能从结尾倒数时间".to_string())?;
+                Ok(Some((total + time).max(0.0)))
+            }
+            time => Ok(time),
+        }
+    };
+    let start_sec = absolute(&start_text)?;
+    let end_sec = absolute(&end_text)?;
+    let duration_sec = has_text(&duration_text)
+        .then(|| parse_timecode(&duration_text, fps))
+        .transpose()?;
+    if duration_sec.is_some_and(|duration| duration <= 0.0) {
+        return Err("duration must be greater than 0".to_string());
+    }
+    if end_sec.is_some_and(|end| end <= start_sec.unwrap_or(0.0)) {
+        return Err("endTime must be after startTime".to_string());
+    }
+
+    Ok(TrimRange {
+        start_sec,
+        duration_sec,
+        end_sec,
+        seek,
+    })
+}
+
+/// Probes the input for `{duration}`, `{width}` and `{height}`.
+fn insert_media_values(values: &mut BTreeMap<String, String>, job: &JobPayload, input_path: &str) -> Result<(), String> {
+    let configured_ffprobe = text_from_option(&job.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let info = probe_media(&ffprobe_path, configured_ffprobe.as_str(), input_path)?;
+
+    if let Some(duration) = info.duration_sec {
+        values.insert("duration".to_string(), format_seconds(duration));
+    }
+
+    if let Some(video) = info.streams.iter().find(|stream| stream.width.is_some() && stream.height.is_some()) {
+        values.insert("width".to_string(), video.width.unwrap_or_default().to_string());
+        values.insert("height".to_string(), video.height.unwrap_or_default().to_string());
+    }
+
+    Ok(())
+}
+
+pub fn build_raw_args(job: &JobPayload) -> Result<Vec<String>, String> {
+    let raw_args = text_from_option(&job.raw_args);
+    if !has_text(&raw_args) {
+        return Err("rawArgs is required for raw mode".to_string());
+    }
+
+    let tokens = split_command_line(&raw_args)?;
+    let (templates, used) = parse_templates(&tokens)?;
+    let input_path = text_from_option(&job.input_path);
+    let output_path = text_from_option(&job.output_path);
+
+    let mut values: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in job.variables.iter().flatten() {
+        if !is_valid_variable_name(name) {
+            return Err(format!("Invalid variable name: {name}"));
+        }
+
+        if PATH_PLACEHOLDERS.contains(&name.as_str()) || MEDIA_PLACEHOLDERS.contains(&name.as_str()) {
+            return Err(format!("Variable {name} conflicts with a built-in placeholder"));
+        }
+
+        values.insert(name.clone(), value.clone());
+    }
+
+    let input_placeholder = used
+        .iter()
+        .find(|name| ["input", "stem", "dir", "ext"].contains(&name.as_str()) || MEDIA_PLACEHOLDERS.contains(&name.as_str()));
+    if let Some(name) = input_placeholder.filter(|_| !has_text(&input_path)) {
+        return Err(format!("inputPath is required because raw args contain {{{name}}}"));
+    }
+
+    if used.contains("output") && !has_text(&output_path) {
+        return Err("outputPath is required because raw args contain {output}".to_string());
+    }
+
+    values.insert("input".to_string(), input_path.clone());
+    values.insert("output".to_string(), output_path);
+    values.insert("date".to_string(), current_date());
+
+    if input_path == INPUT_PLACEHOLDER {
+        // Previews without an input keep the input-derived placeholders visible.
+        for name in ["stem", "dir", "ext"].iter().chain(MEDIA_PLACEHOLDERS) {
+            values.insert(name.to_string(), format!("{{{name}}}"));
+        }
+    } else {
+        insert_path_values(&mut values, &input_path);
+        if MEDIA_PLACEHOLDERS.iter().any(|name| used.contains(*name)) {
+            insert_media_values(&mut values, job, &input_path)?;
+        }
+
+        if let Some(name) = MEDIA_PLACEHOLDERS.iter().find(|name| used.contains(**name) && !values.contains_key(**name)) {
+            return Err(format!("ffprobe 没有返回 {{{name}}} 所需的媒体信息"));
+        }
+    }
+
+    let env = |name: &str| std::env::var(name).ok();
+    let mut args = templates
+        .iter()
+        .map(|template| template.render(&values, &env))
+        .collect::<Result<Vec<_>, _>>()?;
+    // ffmpeg's stdin is a pipe kept open for the quit key, so an overwrite prompt would wait forever.
+    if !args.iter().any(|arg| arg == "-y" || arg == "-n") {
+        args.insert(0, "-n".to_string());
+    }
+    Ok(args)
+}
+
+pub fn build_preset_args(job: &JobPayload) -> Result<Vec<String>, String> {
+    let preset = {
+        let text = text_from_option(&job.preset);
+        if has_text(&text) {
+            text
+        } else {
+            "h264".to_string()
+        }
+    };
+
+    let input_path = text_from_option(&job.input_path);
+    let output_path = text_from_option(&job.output_path);
+
+    if !has_text(&input_path) || !has_text(&output_path) {
+        return Err("inputPath and outputPath are required".to_string());
+    }
+
+    if has_cut_list(job) {
+        return Err("剪辑列表需要使用 visual 模式".to_string());
+    }
+
+    let trim = resolve_trim(job)?;
+    let (input_trim, output_trim) = seek_trim_args(&trim);
+    let mut args = vec!["-y".to_string()];
+    args.extend(input_trim);
+    args.push("-i".to_string());
+    args.push(input_path.clone());
+    args.extend(output_trim);
+
+    match preset.as_str() {
+        "h264" => {
+            let crf = job.crf.unwrap_or(23.0).round() as i64;
+            args.extend([
+                "-c:v".to_string(),
+                "libx264".to_string(),
+                "-preset".to_string(),
+                "medium".to_string(),
+                "-crf".to_string(),
+                crf.to_string(),
+                "-c:a".to_string(),
+                "aac".to_string(),
+                "-b:a".to_string(),
+                "192k".to_string(),
+            ]);
+        }
+        "h265" => {
+            let crf = job.crf.unwrap_or(28.0).round() as i64;
+            args.extend([
+                "-c:v".to_string(),
+                "libx265".to_string(),
+                "-preset".to_string(),
+                "medium".to_string(),
+                "-crf".to_string(),
+                crf.to_string(),
+                "-c:a".to_string(),
+                "aac".to_string(),
+                "-b:a".to_string(),
+                "160k".to_string(),
+            ]);
+        }
+        "mp3" => {
+            args.extend([
+                "-vn".to_string(),
+                "-c:a".to_string(),
+                "libmp3lame".to_string(),
+                "-q:a".to_string(),
+                "2".to_string(),
+            ]);
+        }
+        "gif" => {
+            let fps = round_positive(job.fps).unwrap_or(12);
+            let width = round_positive(job.scale_width).unwrap_or(480);
+            args.extend([
+                "-vf".to_string(),
+                format!("fps={fps},scale={width}:-1:flags=lanczos"),
+                "-loop".to_string(),
+                "0".to_string(),
+            ]);
+        }
+        _ => return Err(format!("Unsupported preset: {preset}")),
+    }
+
+    args.push(output_path);
+    Ok(args)
+}
+
+/// Mirrors the `-an` decision in `build_visual_args`.
+pub(crate) fn job_audio_enabled(job: &JobPayload) -> bool {
+    let preset = text_from_option(&job.preset);
+    let defaults = visual_defaults(if has_text(&preset) { &preset } else { "h264" });
+    let audio_codec = text_from_option(&job.audio_codec);
+    let audio_codec = if has_text(&audio_codec) {
+        audio_codec
+    } else {
+        defaults.audio_codec.unwrap_or_default().to_string()
+    };
+
+    !(job.disable_audio.unwrap_or(false) || defaults.disable_audio || audio_codec == "none")
+}
+
+/// `snapToKeyframe` is left to the callers that start or preview the job, which snap once
+/// with `apply_keyframe_snap`; building the args never reads keyframes.
+pub fn build_visual_args(job: &JobPayload) -> Result<Vec<String>, String> {
+    let input_path = text_from_option(&job.input_path);
+    let output_path = text_from_option(&job.output_path);
+
+    if !has_text(&input_path) || !has_text(&output_path) {
+        return Err("inputPath and outputPath are required".to_string());
+    }
+
+    let preset = {
+        let text = text_from_option(&job.preset);
+        if has_text(&text) {
+            text
+        } else {
+            "h264".to_string()
+        }
+    };
+
+    let defaults = visual_defaults(&preset);
+    if has_cut_list(job) && text_from_option(&job.cut_method) == "copy" {
+        reject_copy_cut_settings(job, &defaults)?;
+    }
+    // The analysis pass measures the plain input, including the removed segments.
+    if has_cut_list(job) && job.loudnorm.unwrap_or(false) {
+        return Err("cutRanges cannot be combined with loudnorm".to_string());
+    }
+    let cuts = cut_plan(job)?;
+    if let Some(CutPlan {
+        method: CutMethod::Copy,
+        keep,
+    }) = &cuts
+    {
+        let (list_path, _) = concat_list_file(&input_path, keep);
+        let mut args = copy_cut_args(
+            job,
+            &list_path,
+            !(job.disable_video.unwrap_or(false) || defaults.disable_video),
+            !(job.disable_audio.unwrap_or(false) || defaults.disable_audio),
+        );
+        push_option_if_value(&mut args, "-f", &job.format);
+        push_extra_args(&mut args, job);
+        args.push(output_path);
+        return Ok(args);
+    }
+
+    let trim = resolve_trim(job)?;
+
+    // `-n` rather than nothing: without either flag ffmpeg would prompt on stdin.
+    let mut args = vec![if job.overwrite.unwrap_or(true) { "-y" } else { "-n" }.to_string()];
+
+    let (input_trim, output_trim) = seek_trim_args(&trim);
+    args.extend(input_trim.iter().cloned());
+    args.push("-i".to_string());
+    args.push(input_path.clone());
+
+    let subtitles = subtitle_plan(job)?;
+    if cuts.is_some() {
+        // Neither subtitle mode follows the cut: soft streams keep the input's timeline and
+        // burned-in cues are timed against it.
+        if subtitles.is_some() {
+            return Err("剪辑列表暂不支持同时添加字幕".to_string());
+        }
+        if has_text(&text_from_option(&job.map_field)) {
+            return Err("cutRanges cannot be combined with map".to_string());
+        }
+    }
+    if let Some(SubtitlePlan {
+        source: SubtitleSource::File(path),
+        mode: SubtitleMode::Soft,
+    }) = &subtitles
+    {
+        // Cues are timed against the source, so the file is seeked and cut like the main input.
+        args.extend(input_trim);
+        args.push("-i".to_string());
+        args.push(path.clone());
+    }
+
+    let chapters_path = text_from_option(&job.chapters_path);
+    let chapters_input = has_text(&chapters_path).then(|| {
+        let index = args.iter().filter(|arg| *arg == "-i").count();
+        args.extend(["-f".to_string(), "ffmetadata".to_string(), "-i".to_string(), chapters_path]);
+        index
+    });
+    // Output options only once every input is in place, or they would bind to the next one.
+    args.extend(output_trim);
+
+    let disable_video = job.disable_video.unwrap_or(false) || defaults.disable_video;
+    let disable_audio = job.disable_audio.unwrap_or(false) || defaults.disable_audio;
+
+    let video_codec = {
+        let explicit = text_from_option(&job.video_codec);
+        if has_text(&explicit) {
+            explicit
+        } else {
+            defaults.video_codec.unwrap_or_default().to_string()
+        }
+    };
+
+    let audio_codec = {
+        let explicit = text_from_option(&job.audio_codec);
+        if has_text(&explicit) {
+            explicit
+        } else {
+            defaults.audio_codec.unwrap_or_default().to_string()
+        }
+    };
+
+    let video_enabled = !(disable_video || video_codec == "none");
+    let audio_enabled = !(disable_audio || audio_codec == "none");
+    if cuts.is_some() && (video_codec == "copy" || audio_codec == "copy") {
+        return Err("精确剪辑需要重新编码，不能使用 copy 编码器（可改用 copy 剪辑方式）".to_string());
+    }
+
+    let mut audio_filters = Vec::new();
+    if !video_enabled {
+        args.push("-vn".to_string());
+    } else {
+        if has_text(&video_codec) && video_codec != "auto" {
+            args.push("-c:v".to_string());
+            args.push(video_codec.clone());
+        }
+
+        let speed_preset = {
+            let explicit = text_from_option(&job.speed_preset);
+            if has_text(&explicit) {
+                explicit
+            } else {
+                defaults.speed_preset.unwrap_or_default().to_string()
+            }
+        };
+
+        if has_text(&speed_preset) && video_codec != "copy" {
+            args.push("-preset".to_string());
+            args.push(speed_preset);
+        }
+
+        let crf = job.crf.or(defaults.crf);
+        if let Some(crf_value) = crf {
+            if crf_value.is_finite() && video_codec != "copy" {
+                args.push("-crf".to_string());
+                args.push((crf_value.round() as i64).to_string());
+            }
+        }
+
+        push_option_if_value(&mut args, "-b:v", &job.video_bitrate);
+    }
+
+    if !audio_enabled {
+        args.push("-an".to_string());
+    } else {
+        if has_text(&audio_codec) && audio_codec != "auto" {
+            args.push("-c:a".to_string());
+            args.push(audio_codec.clone());
+        }
+
+        let audio_bitrate = {
+            let explicit = text_from_option(&job.audio_bitrate);
+            if has_text(&explicit) {
+                explicit
+            } else {
+                defaults.audio_bitrate.unwrap_or_default().to_string()
+            }
+        };
+
+        if has_text(&audio_bitrate) {
+            args.push("-b:a".to_string());
+            args.push(audio_bitrate);
+        }
+
+        let audio_quality = {
+            let explicit = text_from_option(&job.audio_quality);
+            if has_text(&explicit) {
+                explicit
+            } else {
+                defaults.audio_quality.unwrap_or_default().to_string()
+            }
+        };
+
+        if has_text(&audio_quality) {
+            args.push("-q:a".to_string());
+            args.push(audio_quality);
+        }
+
+        if let Some(sample_rate) = round_positive(job.sample_rate) {
+            args.push("-ar".to_string());
+            args.push(sample_rate.to_string());
+        }
+
+        if let Some(channels) = round_positive(job.channels) {
+            args.push("-ac".to_string());
+            args.push(channels.to_string());
+        }
+
+        audio_filters = build_audio_filters(job)?;
+        if let Some(target) = loudness_target(job)? {
+            audio_filters.push(loudnorm_filter(&target, job.loudness_measured.as_ref()));
+
+            if round_positive(job.sample_rate).is_none() {
+                args.push("-ar".to_string());
+                args.push(LOUDNORM_SAMPLE_RATE.to_string());
+            }
+        }
+    }
+
+    let mut filters: Vec<String> =
+        build_filter_nodes(job.video_filters.as_deref().unwrap_or_default(), trim.filter_offset_sec())?;
+
+    if let Some(fps) = job.fps.or(defaults.fps) {
+        if fps.is_finite() && fps > 0.0 {
+            filters.push(format!("fps={}", fps.round() as i64));
+        }
+    }
+
+    let scale_width = job.scale_width.or(defaults.scale_width);
+    let scale_height = job.scale_height.or(defaults.scale_height);
+
+    if scale_width.is_some() || scale_height.is_some() {
+        let width = round_positive(scale_width).unwrap_or(-1);
+        let height = round_positive(scale_height).unwrap_or(-1);
+        filters.push(format!("scale={width}:{height}:flags=lanczos"));
+    }
+
+    let video_filter = text_from_option(&job.video_filter);
+    if has_text(&video_filter) {
+        filters.push(video_filter);
+    }
+
+    // Subtitles render last so they land on the final frame size.
+    if let Some(plan) = subtitles.as_ref().filter(|plan| plan.mode == SubtitleMode::Burn) {
+        if disable_video || video_codec == "none" || video_codec == "copy" {
+            return Err("烧录字幕需要重新编码视频，不能禁用视频或使用 copy".to_string());
+        }
+
+        filters.push(burn_in_filter(&plan.source, &input_path, trim.input_seek_sec()));
+    }
+
+    match &cuts {
+        Some(plan) => {
+            args.push("-filter_complex".to_string());
+            args.push(cut_filter_graph(
+                &plan.keep,
+                video_enabled.then_some(filters.as_slice()),
+                audio_enabled.then_some(audio_filters.as_slice()),
+            ));
+        }
+        None => {
+            if !audio_filters.is_empty() {
+                args.push("-af".to_string());
+                args.push(audio_filters.join(","));
+            }
+
+            if !filters.is_empty() {
+                args.push("-vf".to_string());
+                args.push(filters.join(","));
+            }
+        }
+    }
+
+    let loop_value = {
+        let explicit = text_from_option(&job.loop_value);
+        if has_text(&explicit) {
+            explicit
+        } else {
+            defaults.loop_value.unwrap_or_default().to_string()
+        }
+    };
+
+    if has_text(&loop_value) {
+        args.push("-loop".to_string());
+        args.push(loop_value);
+    }
+
+    push_option_if_value(&mut args, "-pix_fmt", &job.pixel_format);
+
+    if job.movflags_faststart.unwrap_or(false) {
+        args.push("-movflags".to_string());
+        args.push("+faststart".to_string());
+    }
+
+    // Pinning to a core set without an explicit thread count keeps ffmpeg from oversubscribing it.
+    let pinned_cores = job.cpu_cores.as_ref().map(|cores| cores.len() as f64).filter(|count| *count > 0.0);
+    if let Some(threads) = round_positive(job.threads).or_else(|| round_positive(pinned_cores)) {
+        args.push("-threads".to_string());
+        args.push(threads.to_string());
+    }
+
+    push_option_if_value(&mut args, "-f", &job.format);
+
+    match subtitles.as_ref().filter(|plan| plan.mode == SubtitleMode::Soft) {
+        Some(plan) => push_soft_subtitle_args(
+            &mut args,
+            job,
+            &plan.source,
+            &output_path,
+            video_enabled,
+            audio_enabled,
+        )?,
+        None if cuts.is_some() => {
+            for (enabled, label) in [(video_enabled, "[vout]"), (audio_enabled, "[aout]")] {
+                if enabled {
+                    args.push("-map".to_string());
+                    args.push(label.to_string());
+                }
+            }
+        }
+        None => push_option_if_value(&mut args, "-map", &job.map_field),
+    }
+
+    // Only the chapters are taken from the metadata file; -map_metadata would also replace
+    // the source's global tags with the file's (empty) ones.
+    if let Some(index) = chapters_input {
+        args.push("-map_chapters".to_string());
+        args.push(index.to_string());
+    }
+
+    push_extra_args(&mut args, job);
+
+    args.push(output_path);
+    Ok(args)
+}
+
+fn push_extra_args(args: &mut Vec<String>, job: &JobPayload) {
+    let Some(extra_args) = &job.extra_args else {
+        return;
+    };
+
+    for option in extra_args {
+        if option.enabled == Some(false) {
+            continue;
+        }
+
+        let key_raw = text_from_option(&option.key);
+        if !has_text(&key_raw) {
+            continue;
+        }
+
+        let key = if key_raw.starts_with('-') {
+            key_raw
+        } else {
+            format!("-{key_raw}")
+        };
+
+        args.push(key);
+
+        let value = text_from_option(&option.value);
+        if has_text(&value) {
+            args.push(value);
+        }
+    }
+}
+
+pub fn build_ffmpeg_args(job: &JobPayload) -> Result<Vec<String>, String> {
+    let mode = text_from_option(&job.mode);
+    // Only the visual builder adds the loudnorm filter and subtitles; other modes would
+    // silently skip them.
+    if mode != "visual" && job.loudnorm.unwrap_or(false) {
+        return Err("响度标准化需要使用 visual 模式".to_string());
+    }
+    if mode != "visual" && subtitle_plan(job)?.is_some() {
+        return Err("字幕需要使用 visual 模式".to_string());
+    }
+
+    if mode == "raw" {
+        return build_raw_args(job);
+    }
+
+    if mode == "visual" {
+        return build_visual_args(job);
+    }
+
+    build_preset_args(job)
+}
+
+pub(crate) fn extension_for_preset(preset: &str) -> &'static str {
+    match preset {
+        "mp3" => ".mp3",
+        "gif" => ".gif",
+        _ => ".mp4",
+    }
+}
+
+pub fn suggest_output_path(input_path: &str, preset: &str) -> String {
+    if !has_text(input_path) {
+        return String::new();
+    }
+
+    let source = Path::new(input_path);
+    let stem = source
+        .file_stem()
+        .and_then(|value| value.to_str())
+        .filter(|value| has_text(value))
+        .unwrap_or("output");
+
+    let file_name = format!("{stem}_converted{}", extension_for_preset(preset));
+
+    match source.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.join(file_name).to_string_lossy().to_string(),
+        _ => file_name,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_raw_args_expands_path_and_user_placeholders() {
+        let job = JobPayload {
+            input_path: Some("/media/talk.wav".to_string()),
+            output_path: Some("/exports/out.mp3".to_string()),
+            raw_args: Some("-i {input} -metadata title={show}_{stem}.{ext} -metadata dir={dir} {{raw}} {output}".to_string()),
+            variables: Some(BTreeMap::from([("show".to_string(), "Weekly".to_string())])),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            build_raw_args(&job).expect("build failed"),
+            vec![
+                "-n",
+                "-i",
+                "/media/talk.wav",
+                "-metadata",
+                "title=Weekly_talk.wav",
+                "-metadata",
+                "dir=/media",
+                "{raw}",
+                "/exports/out.mp3",
+            ]
+        );
+
+        let unknown = JobPayload {
+            raw_args: Some("-i {input} {nope}".to_string()),
+            input_path: Some("a.mov".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(build_raw_args(&unknown).unwrap_err(), "Unknown placeholder {nope} in raw args");
+    }
+
+    #[test]
+    fn suggest_output_path_uses_preset_extension() {
+        assert_eq!(
+            suggest_output_path("/Users/me/video.mov", "h264"),
+            "/Users/me/video_converted.mp4"
+        );
+        assert_eq!(
+            suggest_output_path("/Users/me/audio.wav", "mp3"),
+            "/Users/me/audio_converted.mp3"
+        );
+    }
+
+    #[test]
+    fn build_visual_args_defaults_threads_to_pinned_core_count() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            cpu_cores: Some(vec![0, 1, 2]),
+            ..Default::default()
+        };
+        let args = build_visual_args(&job).expect("build failed");
+        let index = args.iter().position(|arg| arg == "-threads").expect("threads missing");
+        assert_eq!(args[index + 1], "3");
+
+        let explicit = JobPayload {
+            threads: Some(8.0),
+            ..job
+        };
+        let args = build_visual_args(&explicit).expect("build failed");
+        let index = args.iter().position(|arg| arg == "-threads").expect("threads missing");
+        assert_eq!(args[index + 1], "8");
+    }
+
+    #[test]
+    fn trim_times_are_normalized_to_seconds() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            start_time: Some("1h2m3.5s".to_string()),
+            duration: Some("1:30".to_string()),
+            ..Default::default()
+        };
+        assert!(build_visual_args(&job).unwrap().join(" ").starts_with("-y -ss 3723.5 -t 90 -i in.mov"));
+
+        let zero = JobPayload {
+            duration: Some("0:00".to_string()),
+            ..job.clone()
+        };
+        assert_eq!(build_visual_args(&zero).unwrap_err(), "duration must be greater than 0");
+
+        let frames_without_input = JobPayload {
+            input_path: Some(INPUT_PLACEHOLDER.to_string()),
+            start_time: Some("00:00:10:12".to_string()),
+            ..job
+        };
+        assert!(resolve_trim(&frames_without_input).is_err());
+    }
+
+    #[test]
+    fn seek_modes_place_the_trim_around_the_input() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            start_time: Some("1:00".to_string()),
+            end_time: Some("1:30".to_string()),
+            chapters_path: Some("chapters.txt".to_string()),
+            ..Default::default()
+        };
+        let args_for = |seek: &str| {
+            let job = JobPayload {
+                seek_mode: Some(seek.to_string()),
+                ..job.clone()
+            };
+            build_visual_args(&job).unwrap().join(" ")
+        };
+
+        assert!(args_for("input").starts_with("-y -ss 60 -to 90 -i in.mov -f ffmetadata -i chapters.txt"));
+        assert!(args_for("output").starts_with("-y -i in.mov -f ffmetadata -i chapters.txt -ss 60 -to 90"));
+        assert!(args_for("hybrid").starts_with("-y -ss 50 -i in.mov -f ffmetadata -i chapters.txt -ss 10 -to 40"));
+
+        let near_start = JobPayload {
+            start_time: Some("4".to_string()),
+            duration: Some("5".to_string()),
+            end_time: None,
+            seek_mode: Some("hybrid".to_string()),
+            ..job.clone()
+        };
+        assert!(build_visual_args(&near_start).unwrap().join(" ").contains("-i chapters.txt -ss 4 -t 5 "));
+
+        let both = JobPayload {
+            duration: Some("10".to_string()),
+            ..job.clone()
+        };
+        assert_eq!(build_visual_args(&both).unwrap_err(), "endTime cannot be combined with duration");
+        let backwards = JobPayload {
+            end_time: Some("0:30".to_string()),
+            ..job
+        };
+        assert_eq!(build_visual_args(&backwards).unwrap_err(), "endTime must be after startTime");
+    }
+
+    #[test]
+    fn output_side_seeking_shifts_filter_times_to_the_start() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            start_time: Some("1:00".to_string()),
+            duration: Some("30".to_string()),
+            audio_fade_in_sec: Some(2.0),
+            audio_fade_out_sec: Some(3.0),
+            video_filters: Some(vec![VideoFilterNode::Fade {
+                direction: "in".to_string(),
+                start_sec: None,
+                duration_sec: 1.0,
+                color: None,
+            }]),
+            ..Default::default()
+        };
+        let filters_for = |seek: &str| {
+            let args = build_visual_args(&JobPayload {
+                seek_mode: Some(seek.to_string()),
+                ..job.clone()
+            })
+            .unwrap();
+            let value = |key: &str| args[args.iter().position(|arg| arg == key).unwrap() + 1].clone();
+            (value("-vf"), value("-af"))
+        };
+
+        assert_eq!(
+            filters_for("input"),
+            ("fade=t=in:st=0:d=1".to_string(), "afade=t=in:st=0:d=2,afade=t=out:st=27:d=3".to_string())
+        );
+        assert_eq!(
+            filters_for("output"),
+            ("fade=t=in:st=60:d=1".to_string(), "afade=t=in:st=60:d=2,afade=t=out:st=87:d=3".to_string())
+        );
+        assert_eq!(
+            filters_for("hybrid"),
+            ("fade=t=in:st=10:d=1".to_string(), "afade=t=in:st=10:d=2,afade=t=out:st=37:d=3".to_string())
+        );
+
+        let silence = JobPayload {
+            seek_mode: Some("hybrid".to_string()),
+            trim_silence: Some(true),
+            ..job
+        };
+        assert_eq!(
+            build_visual_args(&silence).unwrap_err(),
+            "trimSilence needs seekMode input when startTime is set"
+        );
+    }
+
+    #[test]
+    fn copy_cuts_reject_settings_they_would_drop() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            preset: Some("custom".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            cut_ranges: Some(vec![TimeRange {
+                start_sec: 0.0,
+                end_sec: 10.0,
+            }]),
+            cut_method: Some("copy".to_string()),
+            ..Default::default()
+        };
+        let error_with = |job: JobPayload| build_visual_args(&job).unwrap_err();
+
+        assert_eq!(
+            error_with(JobPayload {
+                subtitle_path: Some("subs.srt".to_string()),
+                ..job.clone()
+            }),
+            "cutMethod copy cannot be combined with subtitles"
+        );
+        assert_eq!(
+            error_with(JobPayload {
+                chapters_path: Some("chapters.txt".to_string()),
+                ..job.clone()
+            }),
+            "cutMethod copy cannot be combined with chaptersPath"
+        );
+        assert_eq!(
+            error_with(JobPayload {
+                loudnorm: Some(true),
+                ..job.clone()
+            }),
+            "cutMethod copy cannot be combined with loudnorm"
+        );
+        assert_eq!(
+            error_with(JobPayload {
+                video_filter: Some("hflip".to_string()),
+                ..job.clone()
+            }),
+            "cutMethod copy cannot be combined with video filters"
+        );
+        assert_eq!(
+            error_with(JobPayload {
+                tempo: Some(1.5),
+                ..job
+            }),
+            "cutMethod copy cannot be combined with audio filters"
+        );
+    }
+
+    #[test]
+    fn effective_length_covers_every_trim_combination() {
+        let trim = |start_sec: Option<f64>, duration_sec: Option<f64>, end_sec: Option<f64>| TrimRange {
+            start_sec,
+            duration_sec,
+            end_sec,
+            seek: SeekMode::Output,
+        };
+
+        assert_eq!(trim(None, None, None).effective_length_sec(Some(120.0)), Some(120.0));
+        assert_eq!(trim(Some(30.0), None, None).effective_length_sec(Some(120.0)), Some(90.0));
+        assert_eq!(trim(Some(30.0), Some(20.0), None).effective_length_sec(Some(120.0)), Some(20.0));
+        assert_eq!(trim(Some(110.0), Some(20.0), None).effective_length_sec(Some(120.0)), Some(10.0));
+        assert_eq!(trim(Some(30.0), None, Some(45.0)).effective_length_sec(None), Some(15.0));
+        assert_eq!(trim(None, None, Some(45.0)).effective_length_sec(Some(120.0)), Some(45.0));
+        assert_eq!(trim(Some(30.0), None, None).effective_length_sec(None), None);
+    }
+}
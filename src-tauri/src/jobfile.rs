@@ -0,0 +1,201 @@
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::job::{has_text, JobPayload};
+
+pub const JOB_FILE_EXTENSION: &str = "ffjob";
+pub const JOB_FILE_FORMAT: &str = "ffjob";
+pub const JOB_FILE_VERSION: u64 = 1;
+
+/// Job fields holding media paths; these are the ones rewritten for relative job files.
+const PATH_FIELDS: &[&str] = &["inputPath", "outputPath", "subtitlePath", "chaptersPath"];
+
+fn strip_nulls(object: &mut Map<String, Value>) {
+    object.retain(|_, value| !value.is_null());
+}
+
+fn is_portable_path(text: &str) -> bool {
+    // Placeholders such as `{input}` are resolved at run time, not against the job file.
+    has_text(text) && !text.contains('{')
+}
+
+fn relativize_paths(job: &mut Map<String, Value>, base_dir: &Path) {
+    for field in PATH_FIELDS {
+        let Some(Value::String(text)) = job.get(*field) else {
+            continue;
+        };
+
+        if !is_portable_path(text) {
+            continue;
+        }
+
+        if let Ok(relative) = Path::new(text).strip_prefix(base_dir) {
+            let relative = relative.to_string_lossy().to_string();
+            job.insert(field.to_string(), Value::from(relative));
+        }
+    }
+}
+
+fn resolve_paths(job: &mut Map<String, Value>, base_dir: &Path) {
+    for field in PATH_FIELDS {
+        let Some(Value::String(text)) = job.get(*field) else {
+            continue;
+        };
+
+        if !is_portable_path(text) || Path::new(text).is_absolute() {
+            continue;
+        }
+
+        let resolved = base_dir.join(text).to_string_lossy().to_string();
+        job.insert(field.to_string(), Value::from(resolved));
+    }
+}
+
+/// Serializes a job as a versioned `.ffjob` document. With `relative_paths`, media paths
+/// under `base_dir` are stored relative to it so the file can travel with the media.
+pub fn export_job_document(job: &JobPayload, base_dir: Option<&Path>, relative_paths: bool) -> Result<String, String> {
+    let Value::Object(mut job_object) = serde_json::to_value(job).map_err(|error| error.to_string())? else {
+        return Err("任务序列化失败".to_string());
+    };
+    strip_nulls(&mut job_object);
+
+    let relative_paths = relative_paths && base_dir.is_some();
+    if let (true, Some(base_dir)) = (relative_paths, base_dir) {
+        relativize_paths(&mut job_object, base_dir);
+    }
+
+    let document = serde_json::json!({
+        "format": JOB_FILE_FORMAT,
+        "version": JOB_FILE_VERSION,
+        "relativePaths": relative_paths,
+        "job": job_object,
+    });
+
+    serde_json::to_string_pretty(&document).map_err(|error| error.to_string())
+}
+
+/// Upgrades a job document to the current version, one step at a time.
+fn migrate_job_document(value: Value) -> Result<Map<String, Value>, String> {
+    let Value::Object(mut document) = value else {
+        return Err("任务文件必须是 JSON 对象".to_string());
+    };
+
+    let mut version = match document.get("format").and_then(|format| format.as_str()) {
+        Some(JOB_FILE_FORMAT) => document
+            .get("version")
+            .and_then(|version| version.as_u64())
+            .ok_or_else(|| "任务文件缺少 version 字段".to_string())?,
+        Some(other) => return Err(format!("不支持的任务文件格式：{other}")),
+        None => 0,
+    };
+
+    if version > JOB_FILE_VERSION {
+        return Err(format!(
+            "任务文件版本 {version} 高于当前支持的版本 {JOB_FILE_VERSION}，请升级应用"
+        ));
+    }
+
+    while version < JOB_FILE_VERSION {
+        if version == 0 {
+            // Version 0 is a bare JobPayload object, as sent by the UI or written for the CLI.
+            let job = Value::Object(std::mem::take(&mut document));
+            document.insert("format".to_string(), Value::from(JOB_FILE_FORMAT));
+            document.insert("relativePaths".to_string(), Value::from(false));
+            document.insert("job".to_string(), job);
+        }
+
+        version += 1;
+    }
+
+    document.insert("version".to_string(), Value::from(JOB_FILE_VERSION));
+    Ok(document)
+}
+
+/// Returns the job object of a document (any supported version) with relative paths
+/// resolved against `base_dir`, ready to be merged or deserialized into a `JobPayload`.
+pub fn job_value_from_document(value: Value, base_dir: Option<&Path>) -> Result<Map<String, Value>, String> {
+    let document = migrate_job_document(value)?;
+    let relative_paths = document
+        .get("relativePaths")
+        .and_then(|flag| flag.as_bool())
+        .unwrap_or(false);
+
+    let Some(Value::Object(mut job)) = document.get("job").cloned() else {
+        return Err("任务文件缺少 job 对象".to_string());
+    };
+
+    if let (true, Some(base_dir)) = (relative_paths, base_dir) {
+        resolve_paths(&mut job, base_dir);
+    }
+
+    Ok(job)
+}
+
+pub fn import_job_document(text: &str, base_dir: Option<&Path>) -> Result<JobPayload, String> {
+    let value: Value = serde_json::from_str(text).map_err(|_| "任务文件不是有效的 JSON".to_string())?;
+    let job = job_value_from_document(value, base_dir)?;
+    serde_json::from_value(Value::Object(job)).map_err(|error| format!("任务文件内容无效：{error}"))
+}
+
+pub fn save_job_file(path: &Path, job: &JobPayload, relative_paths: bool) -> Result<(), String> {
+    let text = export_job_document(job, path.parent(), relative_paths)?;
+    fs::write(path, text).map_err(|error| format!("写入任务文件失败：{error}"))
+}
+
+pub fn load_job_file(path: &Path) -> Result<JobPayload, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("读取任务文件失败：{error}"))?;
+    import_job_document(&text, path.parent())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::ExtraArg;
+
+    #[test]
+    fn job_document_round_trips_with_relative_paths() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            input_path: Some("/projects/show/raw/ep1.mov".to_string()),
+            output_path: Some("/exports/ep1.mp4".to_string()),
+            crf: Some(21.0),
+            extra_args: Some(vec![ExtraArg {
+                key: Some("-tune".to_string()),
+                value: Some("film".to_string()),
+                enabled: Some(true),
+            }]),
+            ..Default::default()
+        };
+
+        let text = export_job_document(&job, Some(Path::new("/projects/show")), true).expect("export failed");
+        let document: Value = serde_json::from_str(&text).expect("json");
+        assert_eq!(document["version"], JOB_FILE_VERSION);
+        assert_eq!(document["job"]["inputPath"], "raw/ep1.mov");
+        assert_eq!(document["job"]["outputPath"], "/exports/ep1.mp4");
+        assert!(document["job"].get("preset").is_none());
+
+        let imported = import_job_document(&text, Some(Path::new("/projects/show"))).expect("import failed");
+        assert_eq!(imported, job);
+    }
+
+    #[test]
+    fn bare_payload_documents_migrate_to_current_version() {
+        let imported = import_job_document(
+            r#"{ "mode": "raw", "rawArgs": "-i {input} {output}", "inputPath": "clip.mov" }"#,
+            Some(Path::new("/media")),
+        )
+        .expect("import failed");
+
+        assert_eq!(imported.raw_args.as_deref(), Some("-i {input} {output}"));
+        // Bare payloads never declared relative paths, so nothing is rewritten.
+        assert_eq!(imported.input_path.as_deref(), Some("clip.mov"));
+    }
+
+    #[test]
+    fn newer_job_documents_are_rejected() {
+        let error = import_job_document(r#"{ "format": "ffjob", "version": 99, "job": {} }"#, None)
+            .expect_err("should reject");
+        assert!(error.contains("99"));
+    }
+}
@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+
+use crate::job::{has_text, resolve_trim, text_from_option, JobPayload, INPUT_PLACEHOLDER};
+use crate::probe::{probe_media, resolve_executable_path, run_command};
+use crate::template::format_seconds;
+
+/// How far before the requested start to look. ffprobe seeks to the keyframe at or before the
+/// interval start, so this only has to cover reading a little past it.
+const SNAP_LOOKBACK_SEC: f64 = 30.0;
+const TIME_EPSILON: f64 = 1e-6;
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyframePayload {
+    pub input_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    /// Optional window; without it the whole video stream is scanned.
+    pub start_sec: Option<f64>,
+    pub end_sec: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyframeList {
+    pub keyframes: Vec<f64>,
+}
+
+/// A start time moved back onto a keyframe, for the preview to show.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyframeSnap {
+    pub requested_sec: f64,
+    pub snapped_sec: f64,
+}
+
+/// Reads packet flags rather than decoding frames (`-skip_frame nokey -show_frames`), which is
+/// much faster and gives the same timestamps. `start_sec` / `end_sec` are relative to the
+/// container start `origin_sec`, like `-ss`; packet timestamps and `-read_intervals` are not.
+pub fn keyframe_args(input_path: &str, start_sec: Option<f64>, end_sec: Option<f64>, origin_sec: f64) -> Vec<String> {
+    let mut args = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-select_streams".to_string(),
+        "v:0".to_string(),
+        "-show_entries".to_string(),
+        "packet=pts_time,flags".to_string(),
+        "-of".to_string(),
+        "csv=p=0".to_string(),
+    ];
+    if start_sec.is_some() || end_sec.is_some() {
+        args.push("-read_intervals".to_string());
+        args.push(format!(
+            "{}%{}",
+            start_sec.map(|start| format_seconds(start + origin_sec)).unwrap_or_default(),
+            end_sec.map(|end| format_seconds(end + origin_sec)).unwrap_or_default()
+        ));
+    }
+    args.push(input_path.to_string());
+    args
+}
+
+/// Parses `pts_time,flags` rows such as `12.345000,K__` into times relative to the container
+/// start `origin_sec`; rows without a timestamp are skipped.
+pub fn parse_keyframes(stdout: &str, origin_sec: f64) -> Vec<f64> {
+    let mut keyframes: Vec<f64> = stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().split(',');
+            let time = fields.next()?.parse::<f64>().ok()?;
+            fields.any(|flags| flags.starts_with('K')).then_some(time - origin_sec)
+        })
+        .filter(|time| time.is_finite())
+        .collect();
+    keyframes.sort_by(f64::total_cmp);
+    keyframes.dedup();
+    keyframes
+}
+
+/// `keyframes` must be sorted, as `parse_keyframes` returns them.
+pub fn prior_keyframe(keyframes: &[f64], target: f64) -> Option<f64> {
+    keyframes
+        .iter()
+        .rev()
+        .copied()
+        .find(|time| *time <= target + TIME_EPSILON)
+}
+
+fn probe_keyframes(
+    ffprobe_path: &str,
+    configured_ffprobe: &str,
+    input_path: &str,
+    start_sec: Option<f64>,
+    end_sec: Option<f64>,
+) -> Result<Vec<f64>, String> {
+    let origin_sec = probe_media(ffprobe_path, configured_ffprobe, input_path)?
+        .start_sec
+        .unwrap_or(0.0);
+    let args = keyframe_args(input_path, start_sec, end_sec, origin_sec);
+    let (stdout, _) = run_command(ffprobe_path, &args, "ffprobe", configured_ffprobe)?;
+    Ok(parse_keyframes(&stdout, origin_sec))
+}
+
+pub fn list_keyframes(payload: &KeyframePayload) -> Result<KeyframeList, String> {
+    let input_path = text_from_option(&payload.input_path);
+    if !has_text(&input_path) {
+        return Err("inputPath is required".to_string());
+    }
+    for value in [payload.start_sec, payload.end_sec].into_iter().flatten() {
+        if !value.is_finite() || value < 0.0 {
+            return Err("startSec and endSec must be non-negative numbers".to_string());
+        }
+    }
+
+    let configured_ffprobe = text_from_option(&payload.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let keyframes = probe_keyframes(
+        &ffprobe_path,
+        &configured_ffprobe,
+        &input_path,
+        payload.start_sec,
+        payload.end_sec,
+    )?;
+
+    Ok(KeyframeList { keyframes })
+}
+
+/// Where the job's start lands once snapped; `None` when snapping is off, there is no start
+/// time, or the input is still a placeholder (preview without a file).
+pub fn keyframe_snap(job: &JobPayload) -> Result<Option<KeyframeSnap>, String> {
+    let input_path = text_from_option(&job.input_path);
+    if !job.snap_to_keyframe.unwrap_or(false) || !has_text(&input_path) || input_path == INPUT_PLACEHOLDER {
+        return Ok(None);
+    }
+    let Some(requested_sec) = resolve_trim(job)?.start_sec.filter(|start| *start > 0.0) else {
+        return Ok(None);
+    };
+
+    let configured_ffprobe = text_from_option(&job.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let keyframes = probe_keyframes(
+        &ffprobe_path,
+        &configured_ffprobe,
+        &input_path,
+        Some((requested_sec - SNAP_LOOKBACK_SEC).max(0.0)),
+        // A millisecond past, since a keyframe exactly at the start must be read too.
+        Some(requested_sec + 0.001),
+    )?;
+    let snapped_sec = prior_keyframe(&keyframes, requested_sec).ok_or_else(|| {
+        format!(
+            "在 {} 秒之前没有找到关键帧，无法对齐开始时间",
+            format_seconds(requested_sec)
+        )
+    })?;
+
+    Ok(Some(KeyframeSnap {
+        requested_sec,
+        snapped_sec,
+    }))
+}
+
+/// Copy of the job starting at the snapped keyframe. A set duration grows by the same amount so
+/// the end point doesn't move.
+pub fn apply_keyframe_snap(job: &JobPayload, snap: &KeyframeSnap) -> Result<JobPayload, String> {
+    let shift = snap.requested_sec - snap.snapped_sec;
+    Ok(JobPayload {
+        start_time: Some(format_seconds(snap.snapped_sec)),
+        duration: resolve_trim(job)?
+            .duration_sec
+            .map(|duration| format_seconds(duration + shift)),
+        snap_to_keyframe: Some(false),
+        ..job.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keyframe_packets_only() {
+        let stdout = "0.000000,K__\n0.041708,___\nN/A,K__\n10.010000,K_\n5.005000,K__,\n10.010000,K__\n";
+        let keyframes = parse_keyframes(stdout, 0.0);
+        assert_eq!(keyframes, vec![0.0, 5.005, 10.01]);
+        assert_eq!(prior_keyframe(&keyframes, 9.5), Some(5.005));
+        assert_eq!(prior_keyframe(&keyframes, 10.01), Some(10.01));
+        assert_eq!(prior_keyframe(&keyframes[1..], 2.0), None);
+    }
+
+    #[test]
+    fn snapping_keeps_the_end_point() {
+        let job = JobPayload {
+            start_time: Some("00:01:10".to_string()),
+            duration: Some("20".to_string()),
+            snap_to_keyframe: Some(true),
+            ..Default::default()
+        };
+        let snap = KeyframeSnap {
+            requested_sec: 70.0,
+            snapped_sec: 67.5,
+        };
+
+        let snapped = apply_keyframe_snap(&job, &snap).unwrap();
+        assert_eq!(snapped.start_time.as_deref(), Some("67.5"));
+        assert_eq!(snapped.duration.as_deref(), Some("22.5"));
+        assert_eq!(snapped.snap_to_keyframe, Some(false));
+
+        // Building the args leaves snapping to the caller and never runs ffprobe.
+        let unsnapped = JobPayload {
+            mode: Some("visual".to_string()),
+            input_path: Some("in.mp4".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            ffprobe_path: Some("/nonexistent/ffprobe".to_string()),
+            video_codec: Some("copy".to_string()),
+            ..job
+        };
+        assert!(crate::job::build_ffmpeg_args(&unsnapped)
+            .expect("build failed")
+            .join(" ")
+            .contains("-ss 70 -t 20 -i in.mp4"));
+        assert_eq!(
+            keyframe_args("in.mp4", Some(40.0), Some(70.001), 0.0).join(" "),
+            "-v error -select_streams v:0 -show_entries packet=pts_time,flags -of csv=p=0 -read_intervals 40%70.001 in.mp4"
+        );
+    }
+
+    #[test]
+    fn keyframes_are_relative_to_the_container_start() {
+        // MPEG-TS captures commonly start around 1.4s; `-ss 10` means 11.4s in packet time.
+        assert_eq!(
+            keyframe_args("in.ts", Some(10.0), Some(20.0), 1.4).join(" "),
+            "-v error -select_streams v:0 -show_entries packet=pts_time,flags -of csv=p=0 -read_intervals 11.4%21.4 in.ts"
+        );
+        let keyframes = parse_keyframes("1.400000,K__\n11.400000,K__\n12.000000,___\n", 1.4);
+        assert_eq!(keyframes.len(), 2);
+        assert!(keyframes[0].abs() < 1e-9 && (keyframes[1] - 10.0).abs() < 1e-9);
+        assert_eq!(prior_keyframe(&keyframes, 10.0), Some(keyframes[1]));
+    }
+}
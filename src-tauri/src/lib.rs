@@ -0,0 +1,26 @@
+pub mod audio_filter;
+pub mod autotrim;
+pub mod command;
+pub mod crf_search;
+pub mod cutlist;
+pub mod cropdetect;
+pub mod import;
+pub mod job;
+pub mod jobfile;
+pub mod keyframes;
+pub mod loudnorm;
+pub mod metrics;
+pub mod probe;
+pub mod process;
+pub mod resources;
+pub mod runner;
+pub mod scenes;
+pub mod sample;
+pub mod settings;
+pub mod shell;
+pub mod subtitle;
+pub mod template;
+pub mod thumbnails;
+pub mod timecode;
+pub mod video_filter;
+pub mod watchdog;
@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::audio_filter::build_audio_filters_at;
+use crate::job::{has_text, job_audio_enabled, push_trim_args, resolve_trim, text_from_option, JobPayload};
+
+/// ffmpeg prefixes the measurement JSON with `[Parsed_loudnorm_N @ 0x...]`.
+pub const LOUDNORM_LOG_MARKER: &str = "Parsed_loudnorm";
+
+const DEFAULT_INTEGRATED: f64 = -23.0;
+const DEFAULT_TRUE_PEAK: f64 = -1.0;
+const DEFAULT_RANGE: f64 = 7.0;
+/// loudnorm resamples to 192 kHz internally, so the output rate is pinned unless set.
+pub const LOUDNORM_SAMPLE_RATE: &str = "48000";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LoudnessMeasurement {
+    pub input_i: f64,
+    pub input_tp: f64,
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessTarget {
+    pub integrated: f64,
+    pub true_peak: f64,
+    pub range: f64,
+}
+
+fn checked_value(value: Option<f64>, default: f64, min: f64, max: f64, field: &str) -> Result<f64, String> {
+    let value = value.unwrap_or(default);
+    if !value.is_finite() || value < min || value > max {
+        return Err(format!("{field} must be between {min} and {max}"));
+    }
+
+    Ok(value)
+}
+
+/// Returns the requested loudness target, or `None` when normalization is off.
+pub fn loudness_target(job: &JobPayload) -> Result<Option<LoudnessTarget>, String> {
+    if !job.loudnorm.unwrap_or(false) {
+        return Ok(None);
+    }
+
+    Ok(Some(LoudnessTarget {
+        integrated: checked_value(job.loudness_target, DEFAULT_INTEGRATED, -70.0, -5.0, "loudnessTarget")?,
+        true_peak: checked_value(job.true_peak, DEFAULT_TRUE_PEAK, -9.0, 0.0, "truePeak")?,
+        range: checked_value(job.loudness_range, DEFAULT_RANGE, 1.0, 50.0, "loudnessRange")?,
+    }))
+}
+
+/// A visual job with normalization on and no stored measurement needs the analysis pass first.
+pub fn needs_measurement(job: &JobPayload) -> Result<bool, String> {
+    Ok(text_from_option(&job.mode) == "visual"
+        && job_audio_enabled(job)
+        && job.loudness_measured.is_none()
+        && loudness_target(job)?.is_some())
+}
+
+/// Without a measurement this is single-pass dynamic normalization; with one it is the
+/// linear second pass.
+pub fn loudnorm_filter(target: &LoudnessTarget, measured: Option<&LoudnessMeasurement>) -> String {
+    let mut filter = format!("loudnorm=I={}:TP={}:LRA={}", target.integrated, target.true_peak, target.range);
+
+    if let Some(measured) = measured {
+        filter.push_str(&format!(
+            ":measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            measured.input_i, measured.input_tp, measured.input_lra, measured.input_thresh, measured.target_offset
+        ));
+    }
+
+    filter
+}
+
+/// Args for the analysis pass: decode audio only, print the JSON stats, discard the output.
+pub fn measurement_args(job: &JobPayload) -> Result<Vec<String>, String> {
+    let input_path = text_from_option(&job.input_path);
+    if !has_text(&input_path) {
+        return Err("inputPath is required".to_string());
+    }
+
+    let Some(target) = loudness_target(job)? else {
+        return Err("loudnorm is not enabled".to_string());
+    };
+
+    // Measures what the encode will normalize, i.e. after the structured audio filters.
+    // The trim below is a plain input seek, so filter times start at zero whatever the
+    // encode's seek mode.
+    let mut filters = build_audio_filters_at(job, 0.0)?;
+    filters.push(format!("{}:print_format=json", loudnorm_filter(&target, None)));
+
+    let mut args = vec!["-hide_banner".to_string()];
+    push_trim_args(&mut args, &resolve_trim(job)?);
+    args.extend([
+        "-i".to_string(),
+        input_path,
+        "-vn".to_string(),
+        "-sn".to_string(),
+        "-dn".to_string(),
+        "-af".to_string(),
+        filters.join(","),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ]);
+
+    Ok(args)
+}
+
+fn measured_field(stats: &Value, key: &str) -> Result<f64, String> {
+    let value = stats
+        .get(key)
+        .and_then(|value| value.as_str().map(str::trim).map(str::parse::<f64>).and_then(Result::ok).or(value.as_f64()))
+        .ok_or_else(|| format!("loudnorm 测量结果缺少 {key}"))?;
+
+    if !value.is_finite() {
+        return Err("输入音频几乎是静音，无法测量响度".to_string());
+    }
+
+    Ok(value)
+}
+
+/// Extracts the measurement JSON that loudnorm prints to stderr at the end of the pass.
+pub fn parse_measurement(stderr: &str) -> Result<LoudnessMeasurement, String> {
+    let missing = || "没有在 ffmpeg 输出中找到 loudnorm 测量结果".to_string();
+    let tail = &stderr[stderr.rfind(LOUDNORM_LOG_MARKER).ok_or_else(missing)?..];
+    let start = tail.find('{').ok_or_else(missing)?;
+    let end = tail.rfind('}').filter(|end| *end > start).ok_or_else(missing)?;
+    let stats: Value = serde_json::from_str(&tail[start..=end]).map_err(|_| "loudnorm 测量结果不是有效的 JSON".to_string())?;
+
+    Ok(LoudnessMeasurement {
+        input_i: measured_field(&stats, "input_i")?,
+        input_tp: measured_field(&stats, "input_tp")?,
+        input_lra: measured_field(&stats, "input_lra")?,
+        input_thresh: measured_field(&stats, "input_thresh")?,
+        target_offset: measured_field(&stats, "target_offset")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_STDERR: &str = "size=N/A time=00:01:00.00 bitrate=N/A speed= 250x
+[Parsed_loudnorm_0 @ 0x7f8b4c004a80]
+{
+\t\"input_i\" : \"-27.61\",
+\t\"input_tp\" : \"-4.47\",
+\t\"input_lra\" : \"18.06\",
+\t\"input_thresh\" : \"-39.20\",
+\t\"output_i\" : \"-16.58\",
+\t\"output_tp\" : \"-1.50\",
+\t\"output_lra\" : \"14.78\",
+\t\"output_thresh\" : \"-27.71\",
+\t\"normalization_type\" : \"dynamic\",
+\t\"target_offset\" : \"0.58\"
+}
+";
+
+    #[test]
+    fn parse_measurement_reads_loudnorm_json_from_stderr() {
+        let measured = parse_measurement(SAMPLE_STDERR).expect("parse failed");
+        assert_eq!(
+            measured,
+            LoudnessMeasurement {
+                input_i: -27.61,
+                input_tp: -4.47,
+                input_lra: 18.06,
+                input_thresh: -39.2,
+                target_offset: 0.58,
+            }
+        );
+
+        let silent = SAMPLE_STDERR.replace("\"-27.61\"", "\"-inf\"");
+        assert!(parse_measurement(&silent).unwrap_err().contains("静音"));
+        assert!(parse_measurement("no stats here").is_err());
+    }
+
+    #[test]
+    fn second_pass_filter_uses_measured_values() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            loudnorm: Some(true),
+            loudness_target: Some(-16.0),
+            true_peak: Some(-1.5),
+            loudness_range: Some(11.0),
+            ..Default::default()
+        };
+        let target = loudness_target(&job).expect("target").expect("enabled");
+        assert!(needs_measurement(&job).expect("check"));
+
+        let measured = parse_measurement(SAMPLE_STDERR).expect("parse failed");
+        assert_eq!(
+            loudnorm_filter(&target, Some(&measured)),
+            "loudnorm=I=-16:TP=-1.5:LRA=11:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:\
+             measured_thresh=-39.2:offset=0.58:linear=true"
+        );
+
+        let out_of_range = JobPayload {
+            loudness_target: Some(-2.0),
+            ..job.clone()
+        };
+        assert!(loudness_target(&out_of_range).is_err());
+
+        let output_seek = JobPayload {
+            input_path: Some("in.mov".to_string()),
+            start_time: Some("60".to_string()),
+            duration: Some("30".to_string()),
+            seek_mode: Some("output".to_string()),
+            audio_fade_in_sec: Some(2.0),
+            ..job.clone()
+        };
+        let args = measurement_args(&output_seek).expect("args").join(" ");
+        assert!(args.starts_with("-hide_banner -ss 60 -t 30 -i in.mov"));
+        assert!(args.contains("-af afade=t=in:st=0:d=2,loudnorm="));
+
+        let cut = JobPayload {
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            cut_ranges: Some(vec![crate::cutlist::TimeRange {
+                start_sec: 0.0,
+                end_sec: 10.0,
+            }]),
+            ..job.clone()
+        };
+        assert_eq!(
+            crate::job::build_ffmpeg_args(&cut).unwrap_err(),
+            "cutRanges cannot be combined with loudnorm"
+        );
+
+        let preset = JobPayload {
+            mode: Some("preset".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            ..out_of_range
+        };
+        assert!(!needs_measurement(&preset).expect("check"));
+        assert_eq!(
+            crate::job::build_ffmpeg_args(&preset).unwrap_err(),
+            "响度标准化需要使用 visual 模式"
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cropdetect::CropRect;
+use crate::job::{has_text, resolve_trim, text_from_option, JobPayload};
+use crate::probe::{probe_media, resolve_executable_path, run_command};
+use crate::template::format_seconds;
+use crate::video_filter::{escape_filter_value, VideoFilterNode};
+
+/// Filters log their summaries as `[Parsed_ssim_N @ 0x...] SSIM ...` when the graph closes.
+pub const METRICS_LOG_MARKER: &str = "Parsed_";
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsPayload {
+    /// The source the output was encoded from.
+    pub reference_path: Option<String>,
+    /// The encoded output being scored.
+    pub distorted_path: Option<String>,
+    pub ffmpeg_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    /// `auto` (default) uses VMAF when ffmpeg has libvmaf, otherwise SSIM and PSNR.
+    pub engine: Option<String>,
+    /// The part of the reference the output covers, i.e. the job's trim range.
+    pub reference_start_sec: Option<f64>,
+    pub reference_duration_sec: Option<f64>,
+    /// The job's crop nodes in order, applied to the reference too so both sides show the
+    /// same picture.
+    pub reference_crop: Option<Vec<VideoFilterNode>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricsEngine {
+    Vmaf,
+    SsimPsnr,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricScore {
+    pub mean: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Per-frame scores in output order. Identical frames have infinite PSNR, sent as `null`.
+    pub frames: Vec<f64>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsResult {
+    pub engine: String,
+    pub vmaf: Option<MetricScore>,
+    pub ssim: Option<MetricScore>,
+    pub psnr: Option<MetricScore>,
+}
+
+/// A ready-to-run comparison; the log files live in `work_dir` until the results are collected.
+#[derive(Debug, Clone)]
+pub struct MetricsPlan {
+    pub engine: MetricsEngine,
+    pub args: Vec<String>,
+    pub work_dir: PathBuf,
+}
+
+impl MetricsPlan {
+    fn vmaf_log(&self) -> PathBuf {
+        self.work_dir.join("vmaf.json")
+    }
+
+    fn ssim_stats(&self) -> PathBuf {
+        self.work_dir.join("ssim.log")
+    }
+
+    fn psnr_stats(&self) -> PathBuf {
+        self.work_dir.join("psnr.log")
+    }
+
+    pub fn cleanup(&self) {
+        let _ = fs::remove_dir_all(&self.work_dir);
+    }
+}
+
+/// Compares the job's output with its input over the range and picture the job encoded.
+pub fn payload_for_job(job: &JobPayload) -> Result<MetricsPayload, String> {
+    let trim = resolve_trim(job)?;
+    Ok(MetricsPayload {
+        reference_path: job.input_path.clone(),
+        distorted_path: job.output_path.clone(),
+        ffmpeg_path: job.ffmpeg_path.clone(),
+        ffprobe_path: job.ffprobe_path.clone(),
+        engine: job.metrics_engine.clone(),
+        reference_start_sec: trim.start_sec,
+        reference_duration_sec: trim.length_sec(),
+        reference_crop: job_reference_crop(job)?,
+    })
+}
+
+/// True when a filter chain such as `[in]crop=640:360,scale=...` contains a `crop` filter.
+fn has_crop_filter(chain: &str) -> bool {
+    chain.split([',', ';']).any(|filter| {
+        let mut rest = filter.trim();
+        while let Some(labelled) = rest.strip_prefix('[') {
+            rest = labelled.split_once(']').map(|(_, tail)| tail).unwrap_or_default();
+        }
+        rest.split('=').next().map(str::trim) == Some("crop")
+    })
+}
+
+/// The job's crops, to mirror on the reference. Only crops that still see the source picture
+/// can be mirrored, so one after a geometry change or inside free-text filters is an error.
+pub fn job_reference_crop(job: &JobPayload) -> Result<Option<Vec<VideoFilterNode>>, String> {
+    let mut crops = Vec::new();
+    let mut reshaped = false;
+    for node in job.video_filters.iter().flatten() {
+        match node {
+            VideoFilterNode::Crop { .. } if reshaped => {
+                return Err("computeMetrics cannot mirror a crop that follows scale, pad, rotate or transpose".to_string())
+            }
+            VideoFilterNode::Crop { .. } => crops.push(node.clone()),
+            VideoFilterNode::Scale { .. }
+            | VideoFilterNode::Pad { .. }
+            | VideoFilterNode::Rotate { .. }
+            | VideoFilterNode::Transpose { .. } => reshaped = true,
+            VideoFilterNode::Raw { filter } if has_crop_filter(filter) => {
+                return Err("computeMetrics cannot mirror a crop inside a raw filter".to_string())
+            }
+            _ => {}
+        }
+    }
+    if has_crop_filter(&text_from_option(&job.video_filter)) {
+        return Err("computeMetrics cannot mirror a crop inside videoFilter".to_string());
+    }
+
+    Ok((!crops.is_empty()).then_some(crops))
+}
+
+/// Folds a chain of crop nodes into one rect of a `width`×`height` picture; omitted offsets
+/// centre the crop, as ffmpeg does.
+pub fn resolve_reference_crop(nodes: &[VideoFilterNode], width: u64, height: u64) -> Result<CropRect, String> {
+    let mut rect = CropRect {
+        width,
+        height,
+        x: 0,
+        y: 0,
+    };
+    for node in nodes {
+        let VideoFilterNode::Crop { width, height, x, y } = node else {
+            return Err("referenceCrop may only contain crop nodes".to_string());
+        };
+        let pixels = |value: f64| (value.is_finite() && value >= 0.0).then(|| value.round() as u64);
+        let (Some(crop_width), Some(crop_height)) = (pixels(*width), pixels(*height)) else {
+            return Err("referenceCrop has an invalid size".to_string());
+        };
+        if crop_width == 0 || crop_height == 0 || crop_width > rect.width || crop_height > rect.height {
+            return Err("裁剪区域超出参考视频画面，无法评估画质".to_string());
+        }
+        let crop_x = x.and_then(pixels).unwrap_or((rect.width - crop_width) / 2);
+        let crop_y = y.and_then(pixels).unwrap_or((rect.height - crop_height) / 2);
+        if crop_x + crop_width > rect.width || crop_y + crop_height > rect.height {
+            return Err("裁剪区域超出参考视频画面，无法评估画质".to_string());
+        }
+        rect = CropRect {
+            width: crop_width,
+            height: crop_height,
+            x: rect.x + crop_x,
+            y: rect.y + crop_y,
+        };
+    }
+
+    Ok(rect)
+}
+
+/// Looks for the `libvmaf` row in `ffmpeg -filters` output (` ... libvmaf  VV->V  ...`).
+pub fn supports_libvmaf(filters_listing: &str) -> bool {
+    filters_listing
+        .lines()
+        .any(|line| line.split_whitespace().nth(1) == Some("libvmaf"))
+}
+
+pub fn resolve_engine(requested: &str, ffmpeg_path: &str, configured_ffmpeg: &str) -> Result<MetricsEngine, String> {
+    let has_vmaf = || {
+        let args = ["-hide_banner".to_string(), "-filters".to_string()];
+        run_command(ffmpeg_path, &args, "ffmpeg", configured_ffmpeg).map(|(stdout, _)| supports_libvmaf(&stdout))
+    };
+
+    match requested {
+        "" | "auto" => Ok(if has_vmaf()? {
+            MetricsEngine::Vmaf
+        } else {
+            MetricsEngine::SsimPsnr
+        }),
+        "vmaf" if has_vmaf()? => Ok(MetricsEngine::Vmaf),
+        "vmaf" => Err("当前 ffmpeg 未编译 libvmaf，请改用 ssim_psnr".to_string()),
+        "ssim_psnr" => Ok(MetricsEngine::SsimPsnr),
+        other => Err(format!("Unsupported metrics engine: {other}")),
+    }
+}
+
+fn checked_range(value: Option<f64>, field: &str) -> Result<Option<f64>, String> {
+    match value {
+        Some(number) if !number.is_finite() || number < 0.0 => Err(format!("{field} must be a non-negative number")),
+        _ => Ok(value.filter(|number| *number > 0.0)),
+    }
+}
+
+/// Geometry of the reference video that both sides are aligned to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReferenceVideo {
+    /// Size after `crop`, which the output is scaled to.
+    pub width: u64,
+    pub height: u64,
+    pub frame_rate: Option<f64>,
+    /// Cut from the reference first, matching the job's own crop.
+    pub crop: Option<CropRect>,
+}
+
+/// Builds the comparison: the output is scaled to the reference size, both sides are
+/// resampled to the reference frame rate and their timestamps restart at zero.
+pub fn metrics_args(
+    payload: &MetricsPayload,
+    engine: MetricsEngine,
+    reference: &ReferenceVideo,
+    work_dir: PathBuf,
+) -> Result<MetricsPlan, String> {
+    let reference_path = text_from_option(&payload.reference_path);
+    let distorted_path = text_from_option(&payload.distorted_path);
+    if !has_text(&reference_path) || !has_text(&distorted_path) {
+        return Err("referencePath and distortedPath are required".to_string());
+    }
+
+    let mut args = vec!["-hide_banner".to_string(), "-i".to_string(), distorted_path];
+    if let Some(start) = checked_range(payload.reference_start_sec, "referenceStartSec")? {
+        args.push("-ss".to_string());
+        args.push(format_seconds(start));
+    }
+    if let Some(duration) = checked_range(payload.reference_duration_sec, "referenceDurationSec")? {
+        args.push("-t".to_string());
+        args.push(format_seconds(duration));
+    }
+    args.push("-i".to_string());
+    args.push(reference_path);
+
+    let fps = reference
+        .frame_rate
+        .map(|rate| format!("fps={rate},"))
+        .unwrap_or_default();
+    let align = format!("{fps}setpts=PTS-STARTPTS,format=yuv420p");
+    let crop = reference
+        .crop
+        .map(|rect| format!("crop={}:{}:{}:{},", rect.width, rect.height, rect.x, rect.y))
+        .unwrap_or_default();
+    let mut graph = format!(
+        "[0:v]scale={}:{}:flags=bicubic,{align}[dist];[1:v]{crop}{align}[ref];",
+        reference.width, reference.height
+    );
+
+    let plan = MetricsPlan {
+        engine,
+        args: Vec::new(),
+        work_dir,
+    };
+    let log_path = |path: PathBuf| escape_filter_value(&path.to_string_lossy());
+    match engine {
+        MetricsEngine::Vmaf => {
+            // libvmaf takes the distorted stream first and the reference second.
+            let threads = std::thread::available_parallelism().map(usize::from).unwrap_or(1);
+            graph.push_str(&format!(
+                "[dist][ref]libvmaf=log_fmt=json:log_path={}:n_threads={threads}",
+                log_path(plan.vmaf_log())
+            ));
+        }
+        MetricsEngine::SsimPsnr => {
+            graph.push_str(&format!(
+                "[dist]split[dist1][dist2];[ref]split[ref1][ref2];\
+                 [dist1][ref1]ssim=stats_file={};[dist2][ref2]psnr=stats_file={}",
+                log_path(plan.ssim_stats()),
+                log_path(plan.psnr_stats())
+            ));
+        }
+    }
+
+    args.extend([
+        "-filter_complex".to_string(),
+        graph,
+        "-an".to_string(),
+        "-sn".to_string(),
+        "-dn".to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ]);
+
+    Ok(MetricsPlan { args, ..plan })
+}
+
+fn score_from_frames(frames: Vec<f64>, mean: Option<f64>) -> Option<MetricScore> {
+    let finite: Vec<f64> = frames.iter().copied().filter(|value| value.is_finite()).collect();
+    let mean = mean.or_else(|| (!finite.is_empty()).then(|| finite.iter().sum::<f64>() / finite.len() as f64))?;
+
+    Some(MetricScore {
+        mean,
+        min: finite.iter().copied().reduce(f64::min),
+        max: finite.iter().copied().reduce(f64::max),
+        frames,
+    })
+}
+
+/// Reads a libvmaf JSON log; handles both the v2 `pooled_metrics` layout and the older
+/// top-level `VMAF score`.
+pub fn parse_vmaf_log(text: &str) -> Result<MetricScore, String> {
+    let log: Value = serde_json::from_str(text).map_err(|_| "VMAF 日志不是有效的 JSON".to_string())?;
+    let frames: Vec<f64> = log
+        .get("frames")
+        .and_then(Value::as_array)
+        .map(|frames| {
+            frames
+                .iter()
+                .filter_map(|frame| frame.get("metrics")?.get("vmaf")?.as_f64())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let pooled = log.get("pooled_metrics").and_then(|pooled| pooled.get("vmaf"));
+    let mean = pooled
+        .and_then(|vmaf| vmaf.get("mean"))
+        .or_else(|| log.get("VMAF score"))
+        .and_then(Value::as_f64);
+
+    let mut score = score_from_frames(frames, mean).ok_or_else(|| "VMAF 日志中没有评分".to_string())?;
+    if let Some(pooled) = pooled {
+        score.min = pooled.get("min").and_then(Value::as_f64).or(score.min);
+        score.max = pooled.get("max").and_then(Value::as_f64).or(score.max);
+    }
+
+    Ok(score)
+}
+
+fn field_value(line: &str, key: &str) -> Option<f64> {
+    line.split_whitespace()
+        .find_map(|token| token.strip_prefix(key))
+        .and_then(|value| value.parse::<f64>().ok())
+}
+
+/// Per-frame values of `key` from an ssim/psnr `stats_file` (`n:1 ... All:0.98 (17.2)`).
+pub fn parse_stats_file(text: &str, key: &str) -> Vec<f64> {
+    text.lines().filter_map(|line| field_value(line, key)).collect()
+}
+
+/// The aggregate ffmpeg logs when the graph closes, e.g. `SSIM Y:... All:0.962 (14.2)` or
+/// `PSNR y:... average:43.2 min:38.1 max:inf`.
+pub fn parse_summary(stderr: &str, label: &str, key: &str) -> Option<f64> {
+    stderr
+        .lines()
+        .rev()
+        .filter(|line| line.contains(&format!("] {label} ")))
+        .find_map(|line| field_value(line, key))
+}
+
+/// Reads the logs written by the pass and removes the work directory.
+pub fn collect_results(plan: &MetricsPlan, stderr: &str) -> Result<MetricsResult, String> {
+    let read = |path: PathBuf| fs::read_to_string(path).map_err(|error| format!("读取质量评估日志失败：{error}"));
+    let result = match plan.engine {
+        MetricsEngine::Vmaf => read(plan.vmaf_log())
+            .and_then(|text| parse_vmaf_log(&text))
+            .map(|vmaf| MetricsResult {
+                engine: "vmaf".to_string(),
+                vmaf: Some(vmaf),
+                ssim: None,
+                psnr: None,
+            }),
+        MetricsEngine::SsimPsnr => read(plan.ssim_stats()).and_then(|ssim_stats| {
+            let psnr_stats = read(plan.psnr_stats())?;
+            let ssim = score_from_frames(
+                parse_stats_file(&ssim_stats, "All:"),
+                parse_summary(stderr, "SSIM", "All:"),
+            );
+            let psnr = score_from_frames(
+                parse_stats_file(&psnr_stats, "psnr_avg:"),
+                parse_summary(stderr, "PSNR", "average:"),
+            );
+            if ssim.is_none() && psnr.is_none() {
+                return Err("质量评估没有产生任何帧的评分".to_string());
+            }
+
+            Ok(MetricsResult {
+                engine: "ssim_psnr".to_string(),
+                vmaf: None,
+                ssim,
+                psnr,
+            })
+        }),
+    };
+
+    plan.cleanup();
+    result
+}
+
+/// Picks the engine, probes the reference and builds the pass, without running it.
+pub fn prepare_metrics(payload: &MetricsPayload) -> Result<MetricsPlan, String> {
+    let configured_ffmpeg = text_from_option(&payload.ffmpeg_path);
+    let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+    let engine = resolve_engine(&text_from_option(&payload.engine), &ffmpeg_path, &configured_ffmpeg)?;
+
+    let configured_ffprobe = text_from_option(&payload.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let info = probe_media(
+        &ffprobe_path,
+        &configured_ffprobe,
+        &text_from_option(&payload.reference_path),
+    )?;
+    let stream = info
+        .streams
+        .iter()
+        .find(|stream| stream.codec_type.as_deref() == Some("video"))
+        .ok_or_else(|| "参考文件没有视频流，无法评估画质".to_string())?;
+    let width = stream.width.ok_or_else(|| "无法读取参考视频的分辨率".to_string())?;
+    let height = stream.height.ok_or_else(|| "无法读取参考视频的分辨率".to_string())?;
+    let crop = payload
+        .reference_crop
+        .as_deref()
+        .filter(|nodes| !nodes.is_empty())
+        .map(|nodes| resolve_reference_crop(nodes, width, height))
+        .transpose()?;
+    let reference = ReferenceVideo {
+        width: crop.map_or(width, |rect| rect.width),
+        height: crop.map_or(height, |rect| rect.height),
+        frame_rate: stream.frame_rate,
+        crop,
+    };
+
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    let work_dir = std::env::temp_dir().join(format!("ffmpeg-gui-metrics-{}-{stamp}", std::process::id()));
+    fs::create_dir_all(&work_dir).map_err(|error| format!("创建质量评估临时目录失败：{error}"))?;
+
+    metrics_args(payload, engine, &reference, work_dir)
+}
+
+/// Runs the comparison to completion.
+pub fn compute_metrics(payload: &MetricsPayload) -> Result<MetricsResult, String> {
+    let plan = prepare_metrics(payload)?;
+    let configured_ffmpeg = text_from_option(&payload.ffmpeg_path);
+    let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+
+    match run_command(&ffmpeg_path, &plan.args, "ffmpeg", &configured_ffmpeg) {
+        Ok((_, stderr)) => collect_results(&plan, &stderr),
+        Err(error) => {
+            plan.cleanup();
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supports_libvmaf_reads_filter_listing() {
+        let listing = " T.C psnr              VV->V      Calculate the PSNR between two video streams.\n \
+                       ... libvmaf           VV->V      Calculate the VMAF between two video streams.\n";
+        assert!(supports_libvmaf(listing));
+        assert!(!supports_libvmaf(
+            " T.C ssim  VV->V  Calculate the SSIM between two video streams.\n"
+        ));
+    }
+
+    #[test]
+    fn parse_vmaf_log_reads_both_layouts() {
+        let v2 = r#"{"frames":[{"frameNum":0,"metrics":{"vmaf":90.5}},{"frameNum":1,"metrics":{"vmaf":94.5}}],
+                     "pooled_metrics":{"vmaf":{"min":90.5,"max":94.5,"mean":92.5,"harmonic_mean":92.4}}}"#;
+        let score = parse_vmaf_log(v2).unwrap();
+        assert_eq!(score.mean, 92.5);
+        assert_eq!((score.min, score.max), (Some(90.5), Some(94.5)));
+        assert_eq!(score.frames, vec![90.5, 94.5]);
+
+        let v1 = r#"{"frames":[{"frameNum":0,"metrics":{"vmaf":80.0}}],"VMAF score":80.0}"#;
+        assert_eq!(parse_vmaf_log(v1).unwrap().mean, 80.0);
+        assert!(parse_vmaf_log(r#"{"frames":[]}"#).is_err());
+    }
+
+    #[test]
+    fn ssim_and_psnr_use_stats_files_and_summary() {
+        let ssim_stats = "n:1 Y:0.957771 U:0.968979 V:0.971698 All:0.962 (14.232487)\n\
+                          n:2 Y:0.967771 U:0.978979 V:0.981698 All:0.972 (15.5)\n";
+        let psnr_stats = "n:1 mse_avg:3.10 mse_y:3.82 mse_u:1.54 mse_v:1.78 psnr_avg:43.22 psnr_y:42.31\n\
+                          n:2 mse_avg:0.00 mse_y:0.00 mse_u:0.00 mse_v:0.00 psnr_avg:inf psnr_y:inf\n";
+        let stderr = "[Parsed_ssim_4 @ 0x55] SSIM Y:0.96 (13.7) U:0.97 (15.0) V:0.97 (15.5) All:0.967 (14.8)\n\
+                      [Parsed_psnr_5 @ 0x55] PSNR y:42.31 u:46.25 v:45.63 average:45.0 min:43.22 max:inf\n";
+
+        let ssim = score_from_frames(
+            parse_stats_file(ssim_stats, "All:"),
+            parse_summary(stderr, "SSIM", "All:"),
+        )
+        .unwrap();
+        assert_eq!(ssim.mean, 0.967);
+        assert_eq!((ssim.min, ssim.max), (Some(0.962), Some(0.972)));
+
+        let psnr = score_from_frames(
+            parse_stats_file(psnr_stats, "psnr_avg:"),
+            parse_summary(stderr, "PSNR", "average:"),
+        )
+        .unwrap();
+        assert_eq!(psnr.mean, 45.0);
+        assert_eq!(psnr.max, Some(43.22));
+        assert!(psnr.frames[1].is_infinite());
+    }
+
+    #[test]
+    fn metrics_args_align_output_to_reference() {
+        let payload = MetricsPayload {
+            reference_path: Some("source.mov".to_string()),
+            distorted_path: Some("out.mp4".to_string()),
+            reference_start_sec: Some(30.0),
+            reference_duration_sec: Some(10.0),
+            ..Default::default()
+        };
+        let reference = ReferenceVideo {
+            width: 1920,
+            height: 1080,
+            frame_rate: Some(25.0),
+            crop: None,
+        };
+
+        let plan = metrics_args(&payload, MetricsEngine::SsimPsnr, &reference, PathBuf::from("/tmp/m")).unwrap();
+        let text = plan.args.join(" ");
+        assert!(text.starts_with("-hide_banner -i out.mp4 -ss 30 -t 10 -i source.mov -filter_complex "));
+        assert!(text.contains(
+            "[0:v]scale=1920:1080:flags=bicubic,fps=25,setpts=PTS-STARTPTS,format=yuv420p[dist];\
+             [1:v]fps=25,setpts=PTS-STARTPTS,format=yuv420p[ref];"
+        ));
+        assert!(
+            text.contains("[dist1][ref1]ssim=stats_file=/tmp/m/ssim.log;[dist2][ref2]psnr=stats_file=/tmp/m/psnr.log")
+        );
+        assert!(text.ends_with("-an -sn -dn -f null -"));
+    }
+
+    #[test]
+    fn reference_is_cropped_like_the_job() {
+        let crop = |width, height, x| VideoFilterNode::Crop { width, height, x, y: None };
+        let job = JobPayload {
+            input_path: Some("source.mov".to_string()),
+            video_filters: Some(vec![crop(1600.0, 1000.0, Some(0.0)), crop(1280.0, 720.0, None)]),
+            ..Default::default()
+        };
+        let nodes = payload_for_job(&job).unwrap().reference_crop.unwrap();
+        let rect = resolve_reference_crop(&nodes, 1920, 1080).unwrap();
+        assert_eq!((rect.width, rect.height, rect.x, rect.y), (1280, 720, 160, 180));
+        assert!(resolve_reference_crop(&nodes, 1280, 720).is_err());
+
+        let reference = ReferenceVideo {
+            width: rect.width,
+            height: rect.height,
+            frame_rate: None,
+            crop: Some(rect),
+        };
+        let payload = MetricsPayload {
+            distorted_path: Some("out.mp4".to_string()),
+            ..payload_for_job(&job).unwrap()
+        };
+        let plan = metrics_args(&payload, MetricsEngine::SsimPsnr, &reference, PathBuf::from("/tmp/m")).unwrap();
+        assert!(plan.args.join(" ").contains(
+            "[0:v]scale=1280:720:flags=bicubic,setpts=PTS-STARTPTS,format=yuv420p[dist];\
+             [1:v]crop=1280:720:160:180,setpts=PTS-STARTPTS,format=yuv420p[ref];"
+        ));
+
+        let scaled_first = JobPayload {
+            video_filters: Some(vec![
+                VideoFilterNode::Scale { width: Some(1280.0), height: None },
+                crop(640.0, 360.0, None),
+            ]),
+            ..job.clone()
+        };
+        assert!(payload_for_job(&scaled_first).is_err());
+        let text_crop = JobPayload {
+            video_filter: Some("[in]crop=640:360".to_string()),
+            ..job
+        };
+        assert!(payload_for_job(&text_crop).is_err());
+    }
+}
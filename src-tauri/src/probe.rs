@@ -0,0 +1,208 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+use crate::job::has_text;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeStream {
+    pub index: Option<u64>,
+    pub codec_type: Option<String>,
+    pub codec_name: Option<String>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub sample_rate: Option<u64>,
+    pub channels: Option<u64>,
+    pub bit_rate: Option<f64>,
+    /// `avg_frame_rate`, falling back to `r_frame_rate`; `None` for still images and audio.
+    pub frame_rate: Option<f64>,
+    /// `tags.language`, used to pick subtitle and audio tracks.
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeInfo {
+    pub file: String,
+    pub format_name: String,
+    pub duration_sec: Option<f64>,
+    /// `format.start_time`. ffmpeg's `-ss` counts from here, while packet timestamps don't;
+    /// non-zero for e.g. MPEG-TS and broadcast captures.
+    pub start_sec: Option<f64>,
+    pub size_bytes: Option<f64>,
+    pub bit_rate: Option<f64>,
+    pub streams: Vec<ProbeStream>,
+}
+
+fn is_explicit_path(path: &str) -> bool {
+    path.contains('/') || path.contains('\\') || path.starts_with('.')
+}
+
+pub fn resolve_executable_path(raw_path: Option<&str>, tool_name: &str) -> String {
+    let fallback = if tool_name == "ffprobe" {
+        "ffprobe"
+    } else {
+        "ffmpeg"
+    };
+
+    let configured = raw_path
+        .map(|value| value.trim().to_string())
+        .filter(|value| has_text(value))
+        .unwrap_or_else(|| fallback.to_string());
+
+    if is_explicit_path(&configured) {
+        return configured;
+    }
+
+    let candidates: &[&str] = if tool_name == "ffprobe" {
+        &["/opt/homebrew/bin/ffprobe", "/usr/local/bin/ffprobe"]
+    } else {
+        &["/opt/homebrew/bin/ffmpeg", "/usr/local/bin/ffmpeg"]
+    };
+
+    for candidate in candidates {
+        if Path::new(candidate).exists() {
+            return (*candidate).to_string();
+        }
+    }
+
+    configured
+}
+
+pub fn format_spawn_error(error: &std::io::Error, tool_name: &str, configured_path: &str) -> String {
+    let name = if tool_name == "ffprobe" {
+        "ffprobe"
+    } else {
+        "ffmpeg"
+    };
+
+    if error.kind() == std::io::ErrorKind::NotFound {
+        let example = if cfg!(target_os = "windows") {
+            format!("C:\\\\ffmpeg\\\\bin\\\\{name}.exe")
+        } else {
+            format!("/opt/homebrew/bin/{name}")
+        };
+
+        return format!(
+            "未找到 {name} 可执行文件。请先安装 FFmpeg（brew install ffmpeg），或在界面里填写 {name} 的完整路径（例如 {example}）。当前配置：{configured_path}"
+        );
+    }
+
+    error.to_string()
+}
+
+fn value_to_f64(value: Option<&Value>) -> Option<f64> {
+    match value {
+        Some(Value::Number(number)) => number.as_f64(),
+        Some(Value::String(text)) => text.parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+/// ffprobe reports rates as fractions such as `30000/1001`; `0/0` means unknown.
+fn rate_to_f64(value: Option<&Value>) -> Option<f64> {
+    let text = value?.as_str()?;
+    let rate = match text.split_once('/') {
+        Some((numerator, denominator)) => numerator.parse::<f64>().ok()? / denominator.parse::<f64>().ok()?,
+        None => text.parse::<f64>().ok()?,
+    };
+
+    (rate.is_finite() && rate > 0.0).then_some(rate)
+}
+
+fn value_to_u64(value: Option<&Value>) -> Option<u64> {
+    value_to_f64(value).and_then(|number| {
+        if number.is_finite() && number >= 0.0 {
+            Some(number.round() as u64)
+        } else {
+            None
+        }
+    })
+}
+
+pub fn run_command(binary_path: &str, args: &[String], tool_name: &str, configured_path: &str) -> Result<(String, String), String> {
+    let output = Command::new(binary_path)
+        .args(args)
+        .output()
+        .map_err(|error| format_spawn_error(&error, tool_name, configured_path))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+    if !output.status.success() {
+        let error_message = stderr.trim();
+        if has_text(error_message) {
+            return Err(error_message.to_string());
+        }
+
+        return Err(format!("{tool_name} 退出码 {}", output.status.code().unwrap_or(-1)));
+    }
+
+    Ok((stdout, stderr))
+}
+
+pub fn probe_media(ffprobe_path: &str, configured_path: &str, input_path: &str) -> Result<ProbeInfo, String> {
+    let args = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-print_format".to_string(),
+        "json".to_string(),
+        "-show_format".to_string(),
+        "-show_streams".to_string(),
+        input_path.to_string(),
+    ];
+
+    let (stdout, _) = run_command(ffprobe_path, &args, "ffprobe", configured_path)?;
+    let parsed: Value = serde_json::from_str(&stdout)
+        .map_err(|_| "ffprobe 返回了无效的 JSON 输出".to_string())?;
+
+    let format_node = parsed.get("format");
+    let streams_node = parsed.get("streams").and_then(|value| value.as_array());
+
+    let streams = streams_node
+        .map(|list| {
+            list
+                .iter()
+                .map(|stream| ProbeStream {
+                    index: stream.get("index").and_then(|value| value.as_u64()),
+                    codec_type: stream
+                        .get("codec_type")
+                        .and_then(|value| value.as_str())
+                        .map(|value| value.to_string()),
+                    codec_name: stream
+                        .get("codec_name")
+                        .and_then(|value| value.as_str())
+                        .map(|value| value.to_string()),
+                    width: value_to_u64(stream.get("width")),
+                    height: value_to_u64(stream.get("height")),
+                    sample_rate: value_to_u64(stream.get("sample_rate")),
+                    channels: value_to_u64(stream.get("channels")),
+                    bit_rate: value_to_f64(stream.get("bit_rate")),
+                    frame_rate: rate_to_f64(stream.get("avg_frame_rate"))
+                        .or_else(|| rate_to_f64(stream.get("r_frame_rate"))),
+                    language: stream
+                        .get("tags")
+                        .and_then(|tags| tags.get("language"))
+                        .and_then(|value| value.as_str())
+                        .map(|value| value.to_string()),
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    Ok(ProbeInfo {
+        file: input_path.to_string(),
+        format_name: format_node
+            .and_then(|value| value.get("format_name"))
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        duration_sec: value_to_f64(format_node.and_then(|value| value.get("duration"))),
+        start_sec: value_to_f64(format_node.and_then(|value| value.get("start_time"))),
+        size_bytes: value_to_f64(format_node.and_then(|value| value.get("size"))),
+        bit_rate: value_to_f64(format_node.and_then(|value| value.get("bit_rate"))),
+        streams,
+    })
+}
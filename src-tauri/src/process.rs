@@ -0,0 +1,412 @@
+use serde::Deserialize;
+use std::io::Write;
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::job::{has_text, text_from_option};
+
+const DEFAULT_GRACEFUL_TIMEOUT_SEC: f64 = 8.0;
+const DEFAULT_TERMINATE_TIMEOUT_SEC: f64 = 3.0;
+/// Longer waits are clamped; the deadline is computed as `Instant::now() + timeout`.
+const MAX_STOP_TIMEOUT_SEC: f64 = 3600.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopMode {
+    /// Ask ffmpeg to quit so it can finalize the container (moov atom, trailers).
+    Graceful,
+    /// Kill the process immediately; the output is left as-is.
+    Kill,
+}
+
+impl StopMode {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            StopMode::Graceful => "graceful",
+            StopMode::Kill => "cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StopPayload {
+    mode: Option<String>,
+    graceful_timeout_sec: Option<f64>,
+    terminate_timeout_sec: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopOptions {
+    pub mode: StopMode,
+    pub graceful_timeout: Duration,
+    pub terminate_timeout: Duration,
+}
+
+fn timeout_or_default(value: Option<f64>, fallback: f64) -> Duration {
+    match value {
+        Some(seconds) if seconds.is_finite() && seconds >= 0.0 => {
+            Duration::from_secs_f64(seconds.min(MAX_STOP_TIMEOUT_SEC))
+        }
+        _ => Duration::from_secs_f64(fallback),
+    }
+}
+
+impl StopOptions {
+    pub fn from_payload(payload: Option<&StopPayload>) -> Self {
+        let payload = payload.cloned().unwrap_or_default();
+        let mode = match text_from_option(&payload.mode).as_str() {
+            "graceful" | "finish" => StopMode::Graceful,
+            _ => StopMode::Kill,
+        };
+
+        Self {
+            mode,
+            graceful_timeout: timeout_or_default(payload.graceful_timeout_sec, DEFAULT_GRACEFUL_TIMEOUT_SEC),
+            terminate_timeout: timeout_or_default(payload.terminate_timeout_sec, DEFAULT_TERMINATE_TIMEOUT_SEC),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PriorityPayload {
+    pub nice_level: Option<f64>,
+    pub priority_class: Option<String>,
+}
+
+/// Scheduling knobs applied to the ffmpeg child. `nice` uses the Unix scale (-20 highest, 19 lowest).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProcessTuning {
+    pub nice: Option<i32>,
+    pub cpu_cores: Vec<u32>,
+}
+
+impl ProcessTuning {
+    pub fn is_empty(&self) -> bool {
+        self.nice.is_none() && self.cpu_cores.is_empty()
+    }
+}
+
+/// An explicit nice level wins over a named priority class.
+pub fn resolve_nice(nice_level: Option<f64>, priority_class: &Option<String>) -> Result<Option<i32>, String> {
+    if let Some(level) = nice_level {
+        if !level.is_finite() || !(-20.0..=19.0).contains(&level) {
+            return Err("niceLevel must be between -20 and 19".to_string());
+        }
+
+        return Ok(Some(level.round() as i32));
+    }
+
+    let class = text_from_option(priority_class);
+    if !has_text(&class) {
+        return Ok(None);
+    }
+
+    match class.as_str() {
+        "idle" => Ok(Some(19)),
+        "below_normal" | "low" => Ok(Some(10)),
+        "normal" => Ok(Some(0)),
+        "above_normal" => Ok(Some(-5)),
+        "high" => Ok(Some(-10)),
+        _ => Err(format!("Unsupported priorityClass: {class}")),
+    }
+}
+
+#[cfg(windows)]
+fn windows_priority_class(nice: i32) -> u32 {
+    const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    const NORMAL_PRIORITY_CLASS: u32 = 0x0000_0020;
+    const ABOVE_NORMAL_PRIORITY_CLASS: u32 = 0x0000_8000;
+    const HIGH_PRIORITY_CLASS: u32 = 0x0000_0080;
+
+    match nice {
+        15.. => IDLE_PRIORITY_CLASS,
+        1..=14 => BELOW_NORMAL_PRIORITY_CLASS,
+        0 => NORMAL_PRIORITY_CLASS,
+        -9..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+        _ => HIGH_PRIORITY_CLASS,
+    }
+}
+
+/// Windows can only take a priority class at creation time; Unix applies tuning after spawn.
+#[cfg(windows)]
+pub fn prepare_command(command: &mut Command, tuning: &ProcessTuning) {
+    use std::os::windows::process::CommandExt;
+
+    if let Some(nice) = tuning.nice {
+        command.creation_flags(windows_priority_class(nice));
+    }
+}
+
+#[cfg(not(windows))]
+pub fn prepare_command(_command: &mut Command, _tuning: &ProcessTuning) {}
+
+/// Linux nice values and affinity masks are per thread, so every task of the process is updated.
+#[cfg(target_os = "linux")]
+fn process_thread_ids(pid: u32) -> Vec<u32> {
+    let mut ids: Vec<u32> = std::fs::read_dir(format!("/proc/{pid}/task"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if ids.is_empty() {
+        ids.push(pid);
+    }
+
+    ids
+}
+
+#[cfg(target_os = "linux")]
+pub fn set_nice(pid: u32, nice: i32) -> Result<(), String> {
+    for id in process_thread_ids(pid) {
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, id as libc::id_t, nice) } != 0 {
+            return Err(format!("设置进程优先级失败：{}", std::io::Error::last_os_error()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn set_nice(pid: u32, nice: i32) -> Result<(), String> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, nice) } != 0 {
+        return Err(format!("设置进程优先级失败：{}", std::io::Error::last_os_error()));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn set_nice(_pid: u32, _nice: i32) -> Result<(), String> {
+    Err("当前平台不支持调整运行中任务的优先级".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn set_cpu_affinity(pid: u32, cores: &[u32]) -> Result<(), String> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for core in cores {
+        if *core as usize >= libc::CPU_SETSIZE as usize {
+            return Err(format!("CPU 核心编号超出范围：{core}"));
+        }
+        unsafe { libc::CPU_SET(*core as usize, &mut set) };
+    }
+
+    for id in process_thread_ids(pid) {
+        let result = unsafe {
+            libc::sched_setaffinity(id as libc::pid_t, std::mem::size_of::<libc::cpu_set_t>(), &set)
+        };
+        if result != 0 {
+            return Err(format!("设置 CPU 亲和性失败：{}", std::io::Error::last_os_error()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_cpu_affinity(_pid: u32, _cores: &[u32]) -> Result<(), String> {
+    Err("当前平台不支持 CPU 亲和性设置，已忽略 cpuCores".to_string())
+}
+
+/// Applies tuning to a freshly spawned child. Failures are returned as warnings; the job keeps running.
+pub fn apply_tuning(pid: u32, tuning: &ProcessTuning) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if let Some(nice) = tuning.nice {
+        if cfg!(unix) {
+            if let Err(message) = set_nice(pid, nice) {
+                warnings.push(message);
+            }
+        }
+    }
+
+    if !tuning.cpu_cores.is_empty() {
+        if let Err(message) = set_cpu_affinity(pid, &tuning.cpu_cores) {
+            warnings.push(message);
+        }
+    }
+
+    warnings
+}
+
+/// Sends ffmpeg's interactive quit key. Fails when stdin is not piped or already closed.
+fn request_quit(child: &mut Child) -> bool {
+    let Some(stdin) = child.stdin.as_mut() else {
+        return false;
+    };
+
+    stdin.write_all(b"q").and_then(|_| stdin.flush()).is_ok()
+}
+
+#[cfg(unix)]
+fn terminate(child: &mut Child) -> bool {
+    // The child is only reaped through `try_wait` under the same lock, so the pid is still ours.
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) == 0 }
+}
+
+#[cfg(not(unix))]
+fn terminate(child: &mut Child) -> bool {
+    child.kill().is_ok()
+}
+
+fn has_exited(child_ref: &Arc<Mutex<Child>>) -> bool {
+    match child_ref.lock() {
+        Ok(mut child) => !matches!(child.try_wait(), Ok(None)),
+        Err(_) => true,
+    }
+}
+
+fn wait_until_exited(child_ref: &Arc<Mutex<Child>>, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if has_exited(child_ref) {
+            return true;
+        }
+
+        if Instant::now() >= deadline {
+            return false;
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Stops the child according to `options`, escalating quit → SIGTERM → kill. Blocks until done.
+pub fn stop_child(child_ref: &Arc<Mutex<Child>>, options: &StopOptions) {
+    if options.mode == StopMode::Graceful {
+        let quit_sent = match child_ref.lock() {
+            Ok(mut child) => !matches!(child.try_wait(), Ok(None)) || request_quit(&mut child),
+            Err(_) => false,
+        };
+
+        if quit_sent && wait_until_exited(child_ref, options.graceful_timeout) {
+            return;
+        }
+
+        let terminated = match child_ref.lock() {
+            Ok(mut child) => !matches!(child.try_wait(), Ok(None)) || terminate(&mut child),
+            Err(_) => false,
+        };
+
+        if terminated && wait_until_exited(child_ref, options.terminate_timeout) {
+            return;
+        }
+    }
+
+    if let Ok(mut child) = child_ref.lock() {
+        if matches!(child.try_wait(), Ok(None)) {
+            let _ = child.kill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stop_options_default_to_hard_cancel() {
+        let options = StopOptions::from_payload(None);
+        assert_eq!(options.mode, StopMode::Kill);
+        assert_eq!(options.graceful_timeout, Duration::from_secs(8));
+
+        let options = StopOptions::from_payload(Some(&StopPayload {
+            mode: Some("graceful".to_string()),
+            graceful_timeout_sec: Some(1.5),
+            terminate_timeout_sec: Some(-2.0),
+        }));
+        assert_eq!(options.mode, StopMode::Graceful);
+        assert_eq!(options.graceful_timeout, Duration::from_millis(1500));
+        assert_eq!(options.terminate_timeout, Duration::from_secs(3));
+
+        let options = StopOptions::from_payload(Some(&StopPayload {
+            graceful_timeout_sec: Some(1e300),
+            ..Default::default()
+        }));
+        assert_eq!(options.graceful_timeout, Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn resolve_nice_prefers_explicit_level() {
+        assert_eq!(resolve_nice(Some(4.2), &Some("high".to_string())), Ok(Some(4)));
+        assert_eq!(resolve_nice(None, &Some("idle".to_string())), Ok(Some(19)));
+        assert_eq!(resolve_nice(None, &Some(" ".to_string())), Ok(None));
+        assert!(resolve_nice(Some(25.0), &None).is_err());
+        assert!(resolve_nice(None, &Some("realtime".to_string())).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn apply_tuning_renices_running_child() {
+        let mut child = Command::new("sleep").arg("5").spawn().expect("spawn sleep");
+        let warnings = apply_tuning(
+            child.id(),
+            &ProcessTuning {
+                nice: Some(7),
+                cpu_cores: vec![0],
+            },
+        );
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", child.id())).expect("read stat");
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert!(warnings.is_empty(), "{warnings:?}");
+        // Field 19 of /proc/<pid>/stat is the nice value; the comm field may contain spaces.
+        let fields: Vec<&str> = stat.rsplit(") ").next().unwrap().split_whitespace().collect();
+        assert_eq!(fields[16], "7");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn graceful_stop_writes_quit_key_to_stdin() {
+        use std::process::{Command, Stdio};
+
+        let child = Command::new("sh")
+            .args(["-c", "read -r key; [ \"$key\" = q ] && exit 0; exit 3"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .expect("spawn sh");
+        let child_ref = Arc::new(Mutex::new(child));
+
+        // `read` waits for a newline or EOF, so close stdin right after the key.
+        {
+            let mut child = child_ref.lock().unwrap();
+            assert!(request_quit(&mut child));
+            drop(child.stdin.take());
+        }
+
+        assert!(wait_until_exited(&child_ref, Duration::from_secs(5)));
+        let status = child_ref.lock().unwrap().try_wait().unwrap().unwrap();
+        assert!(status.success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn graceful_stop_escalates_when_quit_is_ignored() {
+        use std::process::{Command, Stdio};
+
+        let child = Command::new("sleep")
+            .arg("30")
+            .stdin(Stdio::null())
+            .spawn()
+            .expect("spawn sleep");
+        let child_ref = Arc::new(Mutex::new(child));
+
+        stop_child(
+            &child_ref,
+            &StopOptions {
+                mode: StopMode::Graceful,
+                graceful_timeout: Duration::from_millis(200),
+                terminate_timeout: Duration::from_secs(2),
+            },
+        );
+
+        assert!(has_exited(&child_ref));
+    }
+}
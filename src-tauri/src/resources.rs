@@ -0,0 +1,235 @@
+use serde::Serialize;
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceSample {
+    /// Percent of one core; multi-threaded encodes can exceed 100.
+    pub cpu_percent: Option<f64>,
+    pub rss_bytes: Option<u64>,
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceSummary {
+    pub samples: u64,
+    pub peak_cpu_percent: Option<f64>,
+    pub avg_cpu_percent: Option<f64>,
+    pub peak_rss_bytes: Option<u64>,
+    pub avg_rss_bytes: Option<u64>,
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ProcessUsage {
+    cpu_time_sec: Option<f64>,
+    rss_bytes: Option<u64>,
+    read_bytes: Option<u64>,
+    write_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct ResourceStats {
+    samples: u64,
+    cpu_sum: f64,
+    cpu_count: u64,
+    peak_cpu: Option<f64>,
+    rss_sum: u128,
+    rss_count: u64,
+    peak_rss: Option<u64>,
+    read_bytes: Option<u64>,
+    write_bytes: Option<u64>,
+}
+
+impl ResourceStats {
+    fn record(&mut self, sample: &ResourceSample) {
+        self.samples += 1;
+
+        if let Some(cpu) = sample.cpu_percent {
+            self.cpu_sum += cpu;
+            self.cpu_count += 1;
+            self.peak_cpu = Some(self.peak_cpu.map_or(cpu, |peak| peak.max(cpu)));
+        }
+
+        if let Some(rss) = sample.rss_bytes {
+            self.rss_sum += u128::from(rss);
+            self.rss_count += 1;
+            self.peak_rss = Some(self.peak_rss.map_or(rss, |peak| peak.max(rss)));
+        }
+
+        // I/O counters are cumulative, so the last reading is the job total.
+        self.read_bytes = sample.read_bytes.or(self.read_bytes);
+        self.write_bytes = sample.write_bytes.or(self.write_bytes);
+    }
+
+    fn summary(&self) -> ResourceSummary {
+        ResourceSummary {
+            samples: self.samples,
+            peak_cpu_percent: self.peak_cpu,
+            avg_cpu_percent: (self.cpu_count > 0).then(|| self.cpu_sum / self.cpu_count as f64),
+            peak_rss_bytes: self.peak_rss,
+            avg_rss_bytes: (self.rss_count > 0).then(|| (self.rss_sum / u128::from(self.rss_count)) as u64),
+            read_bytes: self.read_bytes,
+            write_bytes: self.write_bytes,
+        }
+    }
+}
+
+/// Returns utime + stime in clock ticks from `/proc/<pid>/stat`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_stat_cpu_ticks(text: &str) -> Option<u64> {
+    // The comm field is parenthesised and may contain spaces, so split after the last ')'.
+    let rest = &text[text.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let utime = fields.get(11)?.parse::<u64>().ok()?;
+    let stime = fields.get(12)?.parse::<u64>().ok()?;
+    Some(utime + stime)
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_status_rss_bytes(text: &str) -> Option<u64> {
+    let line = text.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_io_field(text: &str, key: &str) -> Option<u64> {
+    text.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+        .and_then(|value| value.trim().parse::<u64>().ok())
+}
+
+#[cfg(target_os = "linux")]
+fn read_process_usage(pid: u32) -> Option<ProcessUsage> {
+    use std::fs;
+
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    let cpu_time_sec = parse_stat_cpu_ticks(&stat)
+        .filter(|_| ticks_per_sec > 0)
+        .map(|ticks| ticks as f64 / ticks_per_sec as f64);
+    let rss_bytes = fs::read_to_string(format!("/proc/{pid}/status"))
+        .ok()
+        .and_then(|status| parse_status_rss_bytes(&status));
+    let io = fs::read_to_string(format!("/proc/{pid}/io")).unwrap_or_default();
+
+    Some(ProcessUsage {
+        cpu_time_sec,
+        rss_bytes,
+        read_bytes: parse_io_field(&io, "read_bytes"),
+        write_bytes: parse_io_field(&io, "write_bytes"),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_process_usage(_pid: u32) -> Option<ProcessUsage> {
+    None
+}
+
+fn cpu_percent(previous: Option<(Instant, f64)>, now: Instant, cpu_time_sec: Option<f64>) -> Option<f64> {
+    let (previous_at, previous_cpu) = previous?;
+    let elapsed = now.saturating_duration_since(previous_at).as_secs_f64();
+    if elapsed <= 0.0 {
+        return None;
+    }
+
+    Some(((cpu_time_sec? - previous_cpu) / elapsed * 100.0).max(0.0))
+}
+
+/// Samples the child until it exits and returns the aggregated summary.
+///
+/// The child lock is held while reading `/proc`, so the pid cannot be reaped and reused mid-sample.
+pub fn monitor_child(child_ref: &Arc<Mutex<Child>>, mut on_sample: impl FnMut(&ResourceSample)) -> ResourceSummary {
+    let mut stats = ResourceStats::default();
+    let mut previous: Option<(Instant, f64)> = None;
+    let mut next_sample_at = Instant::now();
+
+    loop {
+        let sample = {
+            let Ok(mut child) = child_ref.lock() else {
+                break;
+            };
+
+            if !matches!(child.try_wait(), Ok(None)) {
+                break;
+            }
+
+            let now = Instant::now();
+            if now < next_sample_at {
+                None
+            } else {
+                next_sample_at = now + SAMPLE_INTERVAL;
+                read_process_usage(child.id()).map(|usage| {
+                    let sample = ResourceSample {
+                        cpu_percent: cpu_percent(previous, now, usage.cpu_time_sec),
+                        rss_bytes: usage.rss_bytes,
+                        read_bytes: usage.read_bytes,
+                        write_bytes: usage.write_bytes,
+                    };
+                    previous = usage.cpu_time_sec.map(|cpu| (now, cpu));
+                    sample
+                })
+            }
+        };
+
+        if let Some(sample) = sample {
+            stats.record(&sample);
+            on_sample(&sample);
+        }
+
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+
+    stats.summary()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_parsers_extract_cpu_memory_and_io() {
+        let stat = "4242 (ffmpeg worker) R 1 4242 4242 0 -1 4194304 900 0 0 0 250 50 0 0 20 0 9 0 100 0";
+        assert_eq!(parse_stat_cpu_ticks(stat), Some(300));
+
+        let status = "Name:\tffmpeg\nVmPeak:\t  900000 kB\nVmRSS:\t  123456 kB\n";
+        assert_eq!(parse_status_rss_bytes(status), Some(123456 * 1024));
+
+        let io = "rchar: 10\nwchar: 20\nread_bytes: 4096\nwrite_bytes: 8192\ncancelled_write_bytes: 0\n";
+        assert_eq!(parse_io_field(io, "read_bytes"), Some(4096));
+        assert_eq!(parse_io_field(io, "write_bytes"), Some(8192));
+    }
+
+    #[test]
+    fn resource_stats_track_peak_and_average() {
+        let mut stats = ResourceStats::default();
+        for (cpu, rss, written) in [(None, 100, 10), (Some(150.0), 300, 50), (Some(50.0), 200, 90)] {
+            stats.record(&ResourceSample {
+                cpu_percent: cpu,
+                rss_bytes: Some(rss),
+                read_bytes: None,
+                write_bytes: Some(written),
+            });
+        }
+
+        let summary = stats.summary();
+        assert_eq!(summary.samples, 3);
+        assert_eq!(summary.peak_cpu_percent, Some(150.0));
+        assert_eq!(summary.avg_cpu_percent, Some(100.0));
+        assert_eq!(summary.peak_rss_bytes, Some(300));
+        assert_eq!(summary.avg_rss_bytes, Some(200));
+        assert_eq!(summary.write_bytes, Some(90));
+        assert_eq!(summary.read_bytes, None);
+    }
+}
@@ -0,0 +1,890 @@
+use serde::Serialize;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::command::format_command_preview;
+use crate::cutlist::{cut_duration_sec, has_cut_list, write_copy_cut_list};
+use crate::job::{build_ffmpeg_args, has_text, resolve_trim, text_from_option, JobPayload};
+use crate::keyframes::{apply_keyframe_snap, keyframe_snap};
+use crate::loudnorm::{self, LoudnessMeasurement};
+use crate::metrics::{self, MetricsResult};
+use crate::probe::{format_spawn_error, probe_media, resolve_executable_path};
+use crate::process::{self, ProcessTuning, StopMode, StopOptions};
+use crate::resources::{self, ResourceSample, ResourceSummary};
+use crate::timecode::{format_duration, parse_timecode};
+use crate::watchdog::Watchdog;
+
+/// Receives everything a running job reports. The GUI forwards these as Tauri events,
+/// the CLI prints them as JSON lines.
+pub trait JobEvents: Send + Sync + 'static {
+    fn state(&self, event: &StateEvent);
+    fn progress(&self, event: &ProgressEvent);
+    fn log(&self, line: &str);
+    fn resources(&self, sample: &ResourceSample);
+    fn loudness(&self, measurement: &LoudnessMeasurement);
+}
+
+#[derive(Clone)]
+pub struct RunningTask {
+    pub child: Arc<Mutex<Child>>,
+    stop_request: Arc<Mutex<Option<StopMode>>>,
+    /// Shared with the pass launcher, so a priority change also applies to later passes.
+    nice: Arc<Mutex<Option<i32>>>,
+}
+
+impl RunningTask {
+    pub fn pid(&self) -> Result<u32, String> {
+        self.child
+            .lock()
+            .map(|child| child.id())
+            .map_err(|_| "任务进程锁不可用".to_string())
+    }
+
+    /// Renices the current pass and keeps the level for the passes that follow it.
+    pub fn set_nice(&self, nice: i32) -> Result<(), String> {
+        // Passes are swapped under the child lock, so no pass can start with the old level.
+        let child = self.child.lock().map_err(|_| "任务进程锁不可用".to_string())?;
+        process::set_nice(child.id(), nice)?;
+        *self.nice.lock().map_err(|_| "任务状态锁不可用".to_string())? = Some(nice);
+        Ok(())
+    }
+
+    /// Records the stop request and escalates in the background; returns immediately.
+    pub fn request_stop(&self, options: StopOptions) -> Result<(), String> {
+        {
+            let mut stop_request = self
+                .stop_request
+                .lock()
+                .map_err(|_| "任务状态锁不可用".to_string())?;
+            // A hard cancel may override a pending graceful stop, but not the other way round.
+            if *stop_request == Some(StopMode::Kill) {
+                return Ok(());
+            }
+            *stop_request = Some(options.mode);
+        }
+
+        let child_ref = self.child.clone();
+        thread::spawn(move || process::stop_child(&child_ref, &options));
+        Ok(())
+    }
+}
+
+pub struct JobHandle {
+    task: RunningTask,
+    worker: JoinHandle<StateEvent>,
+}
+
+impl JobHandle {
+    pub fn task(&self) -> &RunningTask {
+        &self.task
+    }
+
+    /// Blocks until the job finishes and returns the final state event that was reported.
+    pub fn wait(self) -> StateEvent {
+        self.worker
+            .join()
+            .unwrap_or_else(|_| StateEvent::failed("任务监控线程异常退出".to_string()))
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressEvent {
+    pub ratio: Option<f64>,
+    pub current_time_sec: Option<f64>,
+    /// `current_time_sec` and the expected length as `H:MM:SS.mmm`, ready to display.
+    pub current_time: Option<String>,
+    pub total_time: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StateEvent {
+    pub status: String,
+    pub mode: Option<String>,
+    pub args: Option<String>,
+    pub message: Option<String>,
+    pub reason: Option<String>,
+    pub resources: Option<ResourceSummary>,
+    /// Quality scores, on the completion event of jobs with `compute_metrics`.
+    pub metrics: Option<MetricsResult>,
+}
+
+impl StateEvent {
+    fn running(mode: String, args: String) -> Self {
+        Self {
+            status: "running".to_string(),
+            mode: Some(mode),
+            args: Some(args),
+            message: None,
+            reason: None,
+            resources: None,
+            metrics: None,
+        }
+    }
+
+    fn completed() -> Self {
+        Self {
+            status: "completed".to_string(),
+            mode: None,
+            args: None,
+            message: None,
+            reason: None,
+            resources: None,
+            metrics: None,
+        }
+    }
+
+    fn stopped(reason: &str) -> Self {
+        Self {
+            status: "stopped".to_string(),
+            mode: None,
+            args: None,
+            message: None,
+            reason: Some(reason.to_string()),
+            resources: None,
+            metrics: None,
+        }
+    }
+
+    fn failed(message: String) -> Self {
+        Self {
+            status: "failed".to_string(),
+            mode: None,
+            args: None,
+            message: Some(message),
+            reason: None,
+            resources: None,
+            metrics: None,
+        }
+    }
+
+    fn failed_with_reason(reason: &str, message: String) -> Self {
+        Self {
+            reason: Some(reason.to_string()),
+            ..Self::failed(message)
+        }
+    }
+
+    fn with_resources(self, resources: Option<ResourceSummary>) -> Self {
+        Self { resources, ..self }
+    }
+
+    fn with_metrics(self, metrics: Option<MetricsResult>) -> Self {
+        Self { metrics, ..self }
+    }
+}
+
+pub fn parse_progress(line: &str, duration_sec: Option<f64>) -> Option<ProgressEvent> {
+    let time_index = line.find("time=")?;
+    let tail = &line[(time_index + 5)..];
+    let time_token = tail.split_whitespace().next()?;
+    // ffmpeg prints `time=N/A` before the first frame and slightly negative times at the start.
+    let current_time_sec = parse_timecode(time_token, None).ok()?.max(0.0);
+    let duration_sec = duration_sec.filter(|duration| duration.is_finite() && *duration > 0.0);
+    let ratio = duration_sec.map(|duration| (current_time_sec / duration).clamp(0.0, 1.0));
+
+    Some(ProgressEvent {
+        ratio,
+        current_time_sec: Some(current_time_sec),
+        current_time: Some(format_duration(current_time_sec)),
+        total_time: duration_sec.map(format_duration),
+    })
+}
+
+pub fn resolve_duration_sec(payload: &JobPayload) -> Option<f64> {
+    if let Ok(Some(duration)) = cut_duration_sec(payload) {
+        return Some(duration);
+    }
+
+    // `time=` counts output time in every seek mode, so the ratio is against the trimmed length.
+    let trim = resolve_trim(payload).unwrap_or_default();
+    let input_path = text_from_option(&payload.input_path);
+    let total_sec = has_text(&input_path)
+        .then(|| {
+            let configured_ffprobe = text_from_option(&payload.ffprobe_path);
+            let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+            probe_media(&ffprobe_path, configured_ffprobe.as_str(), &input_path).ok()
+        })
+        .flatten()
+        .and_then(|info| info.duration_sec);
+
+    trim.effective_length_sec(total_sec)
+}
+
+fn resolve_process_tuning(job: &JobPayload) -> Result<ProcessTuning, String> {
+    Ok(ProcessTuning {
+        nice: process::resolve_nice(job.nice_level, &job.priority_class)?,
+        cpu_cores: job.cpu_cores.clone().unwrap_or_default(),
+    })
+}
+
+fn wait_for_exit(child_ref: &Arc<Mutex<Child>>, watchdog: &Watchdog) -> Result<ExitStatus, String> {
+    loop {
+        let status = {
+            let mut child = child_ref
+                .lock()
+                .map_err(|_| "任务进程锁不可用".to_string())?;
+            let status = child.try_wait().map_err(|error| error.to_string())?;
+            if status.is_none() && watchdog.check(Instant::now()).is_some() {
+                let _ = child.kill();
+            }
+            status
+        };
+
+        if let Some(status) = status {
+            return Ok(status);
+        }
+
+        thread::sleep(Duration::from_millis(120));
+    }
+}
+
+/// Forwards stderr as log and progress events. Lines from the first one containing
+/// `capture_from` onwards are also returned, for passes whose result is printed at the end.
+fn stream_child_logs(
+    events: &dyn JobEvents,
+    child_ref: &Arc<Mutex<Child>>,
+    duration_sec: Option<f64>,
+    watchdog: &Watchdog,
+    capture_from: Option<&str>,
+) -> Vec<String> {
+    let mut captured = Vec::new();
+    let stderr_pipe = {
+        let mut child = match child_ref.lock() {
+            Ok(child) => child,
+            Err(_) => return captured,
+        };
+        child.stderr.take()
+    };
+
+    let Some(stderr_pipe) = stderr_pipe else {
+        return captured;
+    };
+
+    let reader = BufReader::new(stderr_pipe);
+    for line in reader.lines().map_while(Result::ok) {
+        if !has_text(&line) {
+            continue;
+        }
+
+        events.log(&line);
+        if let Some(marker) = capture_from {
+            if !captured.is_empty() || line.contains(marker) {
+                captured.push(line.clone());
+            }
+        }
+
+        if let Some(progress) = parse_progress(&line, duration_sec) {
+            if let Some(current_time_sec) = progress.current_time_sec {
+                watchdog.record_progress(current_time_sec, Instant::now());
+            }
+            events.progress(&progress);
+        }
+    }
+
+    captured
+}
+
+pub fn job_mode(job: &JobPayload) -> &'static str {
+    match text_from_option(&job.mode).as_str() {
+        "raw" => "raw",
+        "visual" => "visual",
+        _ => "preset",
+    }
+}
+
+struct PassOutcome {
+    wait_result: Result<ExitStatus, String>,
+    resources: Option<ResourceSummary>,
+    captured: Vec<String>,
+}
+
+/// Streams logs, samples resources and waits for the current child to exit.
+fn supervise_pass(
+    events: &Arc<dyn JobEvents>,
+    child_ref: &Arc<Mutex<Child>>,
+    duration_sec: Option<f64>,
+    watchdog: &Arc<Watchdog>,
+    capture_from: Option<&'static str>,
+) -> PassOutcome {
+    let log_thread = {
+        let events = events.clone();
+        let child_ref = child_ref.clone();
+        let watchdog = watchdog.clone();
+        thread::spawn(move || stream_child_logs(events.as_ref(), &child_ref, duration_sec, &watchdog, capture_from))
+    };
+
+    let monitor_thread = {
+        let events = events.clone();
+        let child_ref = child_ref.clone();
+        thread::spawn(move || resources::monitor_child(&child_ref, |sample| events.resources(sample)))
+    };
+
+    let wait_result = wait_for_exit(child_ref, watchdog);
+    let captured = log_thread.join().unwrap_or_default();
+    let resources = monitor_thread.join().ok();
+
+    PassOutcome {
+        wait_result,
+        resources,
+        captured,
+    }
+}
+
+/// The final state for a pass that did not finish successfully, or `None` if it did.
+fn unsuccessful_state(
+    wait_result: &Result<ExitStatus, String>,
+    stop_mode: Option<StopMode>,
+    watchdog: &Watchdog,
+) -> Option<StateEvent> {
+    let status = match wait_result {
+        Ok(status) => status,
+        Err(message) => return Some(StateEvent::failed(message.clone())),
+    };
+
+    if let Some(mode) = stop_mode {
+        return Some(StateEvent::stopped(mode.reason()));
+    }
+
+    if let Some(trip) = watchdog.tripped() {
+        return Some(StateEvent::failed_with_reason(trip.reason(), trip.message()));
+    }
+
+    if status.success() {
+        return None;
+    }
+
+    let exit_code = status
+        .code()
+        .map(|code| code.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    Some(StateEvent::failed(format!("ffmpeg 退出码 {exit_code}")))
+}
+
+fn spawn_ffmpeg(
+    ffmpeg_path: &str,
+    configured_ffmpeg: &str,
+    args: &[String],
+    tuning: &ProcessTuning,
+    events: &dyn JobEvents,
+) -> Result<Child, String> {
+    let mut command = Command::new(ffmpeg_path);
+    command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    process::prepare_command(&mut command, tuning);
+
+    let process = command
+        .spawn()
+        .map_err(|error| format_spawn_error(&error, "ffmpeg", configured_ffmpeg))?;
+
+    if !tuning.is_empty() {
+        for warning in process::apply_tuning(process.id(), tuning) {
+            events.log(&warning);
+        }
+    }
+
+    if process.stderr.is_none() {
+        return Err("ffmpeg stderr 管道初始化失败".to_string());
+    }
+
+    Ok(process)
+}
+
+/// Replaces the finished child with the next pass's process. Returns `Ok(false)` without
+/// spawning when a stop was requested; that check happens under the child lock, so a stop
+/// either lands here or on the new process.
+fn start_next_pass(
+    child_ref: &Arc<Mutex<Child>>,
+    stop_requested: impl Fn() -> bool,
+    spawn: impl FnOnce() -> Result<Child, String>,
+) -> Result<bool, String> {
+    let mut child = child_ref.lock().map_err(|_| "任务进程锁不可用".to_string())?;
+    if stop_requested() {
+        return Ok(false);
+    }
+
+    *child = spawn()?;
+    Ok(true)
+}
+
+/// What every pass of one job shares when it is launched.
+struct PassLauncher {
+    ffmpeg_path: String,
+    configured_ffmpeg: String,
+    tuning: ProcessTuning,
+    /// The task's current nice level, which overrides `tuning.nice` once changed.
+    nice: Arc<Mutex<Option<i32>>>,
+    mode: String,
+}
+
+impl PassLauncher {
+    fn spawn(&self, args: &[String], events: &dyn JobEvents) -> Result<Child, String> {
+        let tuning = ProcessTuning {
+            nice: *self.nice.lock().map_err(|_| "任务状态锁不可用".to_string())?,
+            ..self.tuning.clone()
+        };
+        events.state(&StateEvent::running(self.mode.clone(), format_command_preview(&self.ffmpeg_path, args)));
+        spawn_ffmpeg(&self.ffmpeg_path, &self.configured_ffmpeg, args, &tuning, events)
+    }
+}
+
+/// Scores the finished output in place of the encode process.
+fn run_metrics_pass(
+    payload: &JobPayload,
+    events: &Arc<dyn JobEvents>,
+    child_ref: &Arc<Mutex<Child>>,
+    stop_mode: impl Fn() -> Option<StopMode>,
+    watchdog: &Arc<Watchdog>,
+    launcher: &PassLauncher,
+    duration_sec: Option<f64>,
+) -> Result<MetricsResult, String> {
+    let plan = metrics::prepare_metrics(&metrics::payload_for_job(payload)?)?;
+    let started = start_next_pass(child_ref, || stop_mode().is_some(), || launcher.spawn(&plan.args, events.as_ref()));
+    match started {
+        Ok(true) => {}
+        Ok(false) => {
+            plan.cleanup();
+            return Err("任务已被停止".to_string());
+        }
+        Err(error) => {
+            plan.cleanup();
+            return Err(error);
+        }
+    }
+
+    watchdog.reset_progress(Instant::now());
+    let pass = supervise_pass(events, child_ref, duration_sec, watchdog, Some(metrics::METRICS_LOG_MARKER));
+    if let Some(state) = unsuccessful_state(&pass.wait_result, stop_mode(), watchdog) {
+        plan.cleanup();
+        return Err(state.message.or(state.reason).unwrap_or_default());
+    }
+
+    metrics::collect_results(&plan, &pass.captured.join("\n"))
+}
+
+/// Starts ffmpeg for `payload` and supervises it on a worker thread.
+///
+/// `on_started` runs before supervision begins, so callers can register the task
+/// without racing the final state event. Jobs with loudness normalization first run a
+/// measurement pass, and jobs with `compute_metrics` end with a scoring pass; each pass
+/// replaces the previous process inside the same task, so stop and priority requests keep
+/// working throughout.
+pub fn start_job(
+    payload: &JobPayload,
+    events: Arc<dyn JobEvents>,
+    on_started: impl FnOnce(&RunningTask),
+) -> Result<JobHandle, String> {
+    // The metrics reference is one plain input range; the joined pieces would be scored
+    // against unrelated frames.
+    if payload.compute_metrics.unwrap_or(false) && has_cut_list(payload) {
+        return Err("cutRanges cannot be combined with computeMetrics".to_string());
+    }
+    if payload.compute_metrics.unwrap_or(false) {
+        // Fails before the encode when the reference can't be matched to the output.
+        metrics::payload_for_job(payload)?;
+    }
+
+    // Snapped once up front so the encode, the progress total and the metrics reference all
+    // use the same range.
+    let snapped = match job_mode(payload) {
+        "visual" => keyframe_snap(payload)?
+            .map(|snap| apply_keyframe_snap(payload, &snap))
+            .transpose()?,
+        _ => None,
+    };
+    let payload = snapped.as_ref().unwrap_or(payload);
+
+    let configured_ffmpeg = text_from_option(&payload.ffmpeg_path);
+    let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+    let measure_loudness = loudnorm::needs_measurement(payload)?;
+    let args = if measure_loudness {
+        // Validates the encode args up front so a bad job fails before the long analysis pass.
+        build_ffmpeg_args(payload)?;
+        loudnorm::measurement_args(payload)?
+    } else {
+        build_ffmpeg_args(payload)?
+    };
+    let duration_sec = resolve_duration_sec(payload);
+    let tuning = resolve_process_tuning(payload)?;
+    let launcher = PassLauncher {
+        ffmpeg_path,
+        configured_ffmpeg,
+        tuning: tuning.clone(),
+        nice: Arc::new(Mutex::new(tuning.nice)),
+        mode: job_mode(payload).to_string(),
+    };
+
+    if job_mode(payload) == "visual" {
+        write_copy_cut_list(payload)?;
+    }
+    let process = launcher.spawn(&args, events.as_ref())?;
+
+    let task = RunningTask {
+        child: Arc::new(Mutex::new(process)),
+        stop_request: Arc::new(Mutex::new(None)),
+        nice: launcher.nice.clone(),
+    };
+    let watchdog = Arc::new(Watchdog::new(payload.timeout_sec, payload.stall_timeout_sec, Instant::now()));
+    on_started(&task);
+
+    let worker_task = task.clone();
+    let mut encode_payload = payload.clone();
+    let worker = thread::spawn(move || {
+        let child_ref = worker_task.child.clone();
+        let stop_mode = || worker_task.stop_request.lock().ok().and_then(|mode| *mode);
+
+        if measure_loudness {
+            let pass = supervise_pass(&events, &child_ref, duration_sec, &watchdog, Some(loudnorm::LOUDNORM_LOG_MARKER));
+            if let Some(state) = unsuccessful_state(&pass.wait_result, stop_mode(), &watchdog) {
+                events.state(&state);
+                return state;
+            }
+
+            let next = loudnorm::parse_measurement(&pass.captured.join("\n")).and_then(|measured| {
+                events.loudness(&measured);
+                encode_payload.loudness_measured = Some(measured);
+                build_ffmpeg_args(&encode_payload)
+            });
+
+            let swapped = next.and_then(|args| {
+                start_next_pass(&child_ref, || stop_mode().is_some(), || launcher.spawn(&args, events.as_ref()))
+            });
+
+            let state = match swapped {
+                Ok(true) => None,
+                Ok(false) => stop_mode().map(|mode| StateEvent::stopped(mode.reason())),
+                Err(message) => Some(StateEvent::failed(message)),
+            };
+
+            if let Some(state) = state {
+                events.state(&state);
+                return state;
+            }
+
+            // Output time restarts at zero for the encode pass.
+            watchdog.reset_progress(Instant::now());
+        }
+
+        let pass = supervise_pass(&events, &child_ref, duration_sec, &watchdog, None);
+        let final_state = match unsuccessful_state(&pass.wait_result, stop_mode(), &watchdog) {
+            Some(state) => state.with_resources(pass.resources),
+            None => {
+                events.progress(&ProgressEvent {
+                    ratio: Some(1.0),
+                    current_time_sec: duration_sec,
+                    current_time: duration_sec.map(format_duration),
+                    total_time: duration_sec.map(format_duration),
+                });
+
+                // The output is finished either way; a failed or stopped scoring pass only loses the scores.
+                let metrics = if encode_payload.compute_metrics.unwrap_or(false) {
+                    run_metrics_pass(
+                        &encode_payload,
+                        &events,
+                        &child_ref,
+                        stop_mode,
+                        &watchdog,
+                        &launcher,
+                        duration_sec,
+                    )
+                    .map_err(|error| events.log(&format!("质量评估未完成：{error}")))
+                    .ok()
+                } else {
+                    None
+                };
+
+                StateEvent::completed().with_resources(pass.resources).with_metrics(metrics)
+            }
+        };
+
+        events.state(&final_state);
+        final_state
+    });
+
+    Ok(JobHandle { task, worker })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_progress_extracts_ratio() {
+        let progress = parse_progress(
+            "frame=  240 fps=30 q=28.0 size=    1024kB time=00:00:10.00 bitrate= 838.9kbits/s speed=1.0x",
+            Some(40.0),
+        )
+        .expect("progress missing");
+
+        assert_eq!(progress.current_time_sec, Some(10.0));
+        assert_eq!(progress.ratio, Some(0.25));
+        assert_eq!(progress.current_time.as_deref(), Some("0:00:10.000"));
+        assert_eq!(progress.total_time.as_deref(), Some("0:00:40.000"));
+
+        let start = parse_progress("size=       0kB time=-00:00:00.04 bitrate=N/A speed=N/A", None).unwrap();
+        assert_eq!(start.current_time_sec, Some(0.0));
+        assert_eq!(start.total_time, None);
+        assert!(parse_progress("size=       0kB time=N/A bitrate=N/A", Some(40.0)).is_none());
+    }
+
+    #[derive(Default)]
+    struct RecordedEvents {
+        states: Mutex<Vec<StateEvent>>,
+        loudness: Mutex<Vec<LoudnessMeasurement>>,
+        logs: Mutex<Vec<String>>,
+    }
+
+    impl JobEvents for RecordedEvents {
+        fn state(&self, event: &StateEvent) {
+            self.states.lock().unwrap().push(event.clone());
+        }
+
+        fn progress(&self, _event: &ProgressEvent) {}
+
+        fn log(&self, line: &str) {
+            self.logs.lock().unwrap().push(line.to_string());
+        }
+
+        fn resources(&self, _sample: &ResourceSample) {}
+
+        fn loudness(&self, measurement: &LoudnessMeasurement) {
+            self.loudness.lock().unwrap().push(measurement.clone());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn loudnorm_jobs_measure_then_encode_with_measured_values() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ffmpeg-gui-runner-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let fake_ffmpeg = dir.join("ffmpeg");
+        std::fs::write(
+            &fake_ffmpeg,
+            "#!/bin/sh\ncase \"$*\" in *print_format=json*)\n  printf '[Parsed_loudnorm_0 @ 0x1]\\n{\\n\"input_i\" : \"-30.00\",\\n\
+             \"input_tp\" : \"-6.00\",\\n\"input_lra\" : \"5.00\",\\n\"input_thresh\" : \"-40.00\",\\n\
+             \"target_offset\" : \"0.20\"\\n}\\n' >&2;;\nesac\nexit 0\n",
+        )
+        .expect("write script");
+        std::fs::set_permissions(&fake_ffmpeg, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+
+        let payload = JobPayload {
+            mode: Some("visual".to_string()),
+            ffmpeg_path: Some(fake_ffmpeg.to_string_lossy().to_string()),
+            ffprobe_path: Some(dir.join("missing-ffprobe").to_string_lossy().to_string()),
+            input_path: Some("in.wav".to_string()),
+            output_path: Some("out.m4a".to_string()),
+            loudnorm: Some(true),
+            loudness_target: Some(-16.0),
+            ..Default::default()
+        };
+
+        let events = Arc::new(RecordedEvents::default());
+        let handle = start_job(&payload, events.clone(), |_| {}).expect("start failed");
+        let final_state = handle.wait();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(final_state.status, "completed");
+        assert_eq!(events.loudness.lock().unwrap().first().map(|measured| measured.input_i), Some(-30.0));
+
+        let states = events.states.lock().unwrap();
+        let commands: Vec<&str> = states.iter().filter_map(|state| state.args.as_deref()).collect();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].contains("print_format=json"));
+        assert!(commands[1].contains("measured_I=-30:measured_TP=-6:measured_LRA=5:measured_thresh=-40:offset=0.2"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn priority_change_carries_over_to_the_next_pass() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ffmpeg-gui-priority-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let fake_ffmpeg = dir.join("ffmpeg");
+        let nice_file = dir.join("nice");
+        std::fs::write(
+            &fake_ffmpeg,
+            format!(
+                "#!/bin/sh\ncase \"$*\" in *print_format=json*)\n  sleep 1\n  printf '[Parsed_loudnorm_0 @ 0x1]\\n{{\\n\
+                 \"input_i\" : \"-30.00\",\\n\"input_tp\" : \"-6.00\",\\n\"input_lra\" : \"5.00\",\\n\
+                 \"input_thresh\" : \"-40.00\",\\n\"target_offset\" : \"0.20\"\\n}}\\n' >&2;;\n\
+                 *) awk '{{print $19}}' /proc/$$/stat > '{}';;\nesac\nexit 0\n",
+                nice_file.display()
+            ),
+        )
+        .expect("write script");
+        std::fs::set_permissions(&fake_ffmpeg, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+
+        let payload = JobPayload {
+            mode: Some("visual".to_string()),
+            ffmpeg_path: Some(fake_ffmpeg.to_string_lossy().to_string()),
+            ffprobe_path: Some(dir.join("missing-ffprobe").to_string_lossy().to_string()),
+            input_path: Some("in.wav".to_string()),
+            output_path: Some("out.m4a".to_string()),
+            loudnorm: Some(true),
+            ..Default::default()
+        };
+
+        let handle = start_job(&payload, Arc::new(RecordedEvents::default()), |_| {}).expect("start failed");
+        handle.task().set_nice(19).expect("renice failed");
+        let final_state = handle.wait();
+        let encode_nice = std::fs::read_to_string(&nice_file).unwrap_or_default();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(final_state.status, "completed");
+        assert_eq!(encode_nice.trim(), "19");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_metrics_pass_keeps_the_job_completed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ffmpeg-gui-runner-metrics-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let fake_ffmpeg = dir.join("ffmpeg");
+        std::fs::write(&fake_ffmpeg, "#!/bin/sh\nexit 0\n").expect("write script");
+        std::fs::set_permissions(&fake_ffmpeg, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+
+        let payload = JobPayload {
+            ffmpeg_path: Some(fake_ffmpeg.to_string_lossy().to_string()),
+            ffprobe_path: Some(dir.join("missing-ffprobe").to_string_lossy().to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            compute_metrics: Some(true),
+            ..Default::default()
+        };
+
+        let events = Arc::new(RecordedEvents::default());
+        let final_state = start_job(&payload, events.clone(), |_| {}).expect("start failed").wait();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(final_state.status, "completed");
+        assert!(final_state.metrics.is_none());
+        assert!(events.logs.lock().unwrap().iter().any(|line| line.starts_with("质量评估未完成")));
+    }
+
+    #[test]
+    fn cut_list_jobs_reject_metrics_before_encoding() {
+        let payload = JobPayload {
+            mode: Some("visual".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            cut_ranges: Some(vec![crate::cutlist::TimeRange {
+                start_sec: 0.0,
+                end_sec: 10.0,
+            }]),
+            compute_metrics: Some(true),
+            ..Default::default()
+        };
+
+        let error = start_job(&payload, Arc::new(RecordedEvents::default()), |_| {}).err();
+        assert_eq!(error.as_deref(), Some("cutRanges cannot be combined with computeMetrics"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn snapped_start_is_shared_by_encode_and_metrics_reference() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ffmpeg-gui-runner-snap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let fake_ffmpeg = dir.join("ffmpeg");
+        let fake_ffprobe = dir.join("ffprobe");
+        std::fs::write(&fake_ffmpeg, "#!/bin/sh\nexit 0\n").expect("write ffmpeg");
+        std::fs::write(
+            &fake_ffprobe,
+            "#!/bin/sh\ncase \"$*\" in *-show_format*)\n  printf '{\"format\":{\"duration\":\"100\",\"start_time\":\"0\"},\
+             \"streams\":[{\"codec_type\":\"video\",\"width\":1280,\"height\":720}]}';;\n\
+             *) printf '60.000000,K__\\n67.500000,K__\\n';;\nesac\n",
+        )
+        .expect("write ffprobe");
+        for script in [&fake_ffmpeg, &fake_ffprobe] {
+            std::fs::set_permissions(script, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+        }
+
+        let payload = JobPayload {
+            mode: Some("visual".to_string()),
+            ffmpeg_path: Some(fake_ffmpeg.to_string_lossy().to_string()),
+            ffprobe_path: Some(fake_ffprobe.to_string_lossy().to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some(dir.join("out.mp4").to_string_lossy().to_string()),
+            start_time: Some("70".to_string()),
+            duration: Some("20".to_string()),
+            snap_to_keyframe: Some(true),
+            compute_metrics: Some(true),
+            ..Default::default()
+        };
+
+        let events = Arc::new(RecordedEvents::default());
+        let final_state = start_job(&payload, events.clone(), |_| {}).expect("start failed").wait();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(final_state.status, "completed");
+        let states = events.states.lock().unwrap();
+        let commands: Vec<&str> = states.iter().filter_map(|state| state.args.as_deref()).collect();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].contains("-ss 67.5 -t 22.5 -i in.mov"));
+        assert!(commands[1].contains("-ss 67.5 -t 22.5 -i in.mov"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn existing_output_without_overwrite_does_not_wait_for_a_prompt() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ffmpeg-gui-runner-no-overwrite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let fake_ffmpeg = dir.join("ffmpeg");
+        // Like ffmpeg: without -y/-n, an existing output means asking on stdin.
+        std::fs::write(
+            &fake_ffmpeg,
+            "#!/bin/sh
+for arg in \"$@\"; do case \"$arg\" in -y|-n) exit 0;; esac; done
+\
+             printf 'File exists. Overwrite? [y/N] ' >&2
+read answer
+exit 1
+",
+        )
+        .expect("write script");
+        std::fs::set_permissions(&fake_ffmpeg, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+        let output = dir.join("out.mp4");
+        std::fs::write(&output, b"existing").expect("write output");
+
+        let visual = JobPayload {
+            mode: Some("visual".to_string()),
+            ffmpeg_path: Some(fake_ffmpeg.to_string_lossy().to_string()),
+            ffprobe_path: Some(dir.join("missing-ffprobe").to_string_lossy().to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some(output.to_string_lossy().to_string()),
+            overwrite: Some(false),
+            // Turns a regression into a failure instead of a hung test.
+            timeout_sec: Some(10.0),
+            ..Default::default()
+        };
+        let raw = JobPayload {
+            mode: Some("raw".to_string()),
+            raw_args: Some("-i {input} {output}".to_string()),
+            ..visual.clone()
+        };
+
+        for payload in [visual, raw] {
+            let events = Arc::new(RecordedEvents::default());
+            let final_state = start_job(&payload, events.clone(), |_| {}).expect("start failed").wait();
+            assert_eq!(final_state.status, "completed");
+            let states = events.states.lock().unwrap();
+            assert!(states[0].args.as_deref().is_some_and(|args| args.contains(" -n ")));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,250 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cutlist::has_cut_list;
+use crate::job::{build_ffmpeg_args, has_text, resolve_trim, text_from_option, JobPayload};
+use crate::probe::{probe_media, resolve_executable_path, run_command};
+use crate::template::format_seconds;
+
+const DEFAULT_SAMPLE_SEC: f64 = 8.0;
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SampleRenderPayload {
+    /// Length of the sample; clamped to the job's own length.
+    pub sample_duration_sec: Option<f64>,
+    /// Where the sample starts in the input; defaults to the middle of the job's range.
+    pub sample_start_sec: Option<f64>,
+    #[serde(default)]
+    pub job: JobPayload,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SampleRenderResult {
+    pub path: String,
+    pub args: Vec<String>,
+    pub start_sec: f64,
+    pub duration_sec: f64,
+    pub size_bytes: u64,
+    /// Length the real job would encode.
+    pub full_duration_sec: f64,
+    /// `size_bytes` scaled to `full_duration_sec`; assumes the sample's bitrate holds throughout.
+    pub estimated_size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleWindow {
+    pub start_sec: f64,
+    pub duration_sec: f64,
+}
+
+/// Length of what the job encodes: its trim duration, or the probed input minus the start offset.
+pub(crate) fn full_duration_sec(job: &JobPayload, job_start: f64) -> Result<f64, String> {
+    // Windows are plain input ranges, which would land in footage the cut removes.
+    if has_cut_list(job) {
+        return Err("cutRanges cannot be combined with sample renders or CRF search".to_string());
+    }
+
+    if let Some(duration) = resolve_trim(job)?.length_sec() {
+        return Ok(duration);
+    }
+
+    let configured_ffprobe = text_from_option(&job.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let total = probe_media(&ffprobe_path, &configured_ffprobe, &text_from_option(&job.input_path))?
+        .duration_sec
+        .ok_or_else(|| "无法读取输入时长，不能估算完整输出大小".to_string())?;
+
+    Ok((total - job_start).max(0.0))
+}
+
+/// Places the sample inside `[job_start, job_start + full_duration]`.
+pub fn sample_window(
+    job_start: f64,
+    full_duration: f64,
+    requested_start: Option<f64>,
+    requested_duration: Option<f64>,
+) -> Result<SampleWindow, String> {
+    let requested_duration = requested_duration.unwrap_or(DEFAULT_SAMPLE_SEC);
+    if !requested_duration.is_finite() || !(1.0..=60.0).contains(&requested_duration) {
+        return Err("sampleDurationSec must be between 1 and 60".to_string());
+    }
+    if full_duration <= 0.0 {
+        return Err("任务的有效时长为 0，无法渲染样片".to_string());
+    }
+
+    let duration_sec = requested_duration.min(full_duration);
+    let latest_start = job_start + full_duration - duration_sec;
+    let start_sec = match requested_start {
+        None => job_start + (full_duration - duration_sec) / 2.0,
+        Some(start) if start.is_finite() && start >= job_start && start <= latest_start + 1e-6 => start,
+        Some(_) => return Err("sampleStartSec must leave the whole sample inside the job's range".to_string()),
+    };
+
+    Ok(SampleWindow {
+        start_sec,
+        duration_sec,
+    })
+}
+
+/// Copy of the job that encodes only the window into `output_path`. Everything else is
+/// left as the user set it so the sample is representative.
+pub fn sample_job(job: &JobPayload, window: &SampleWindow, output_path: &Path) -> JobPayload {
+    JobPayload {
+        start_time: Some(format_seconds(window.start_sec)),
+        duration: Some(format_seconds(window.duration_sec)),
+        output_path: Some(output_path.to_string_lossy().to_string()),
+        overwrite: Some(true),
+        // The window is already a plain input range; the cut list would clash with it.
+        cut_ranges: None,
+        end_time: None,
+        ..job.clone()
+    }
+}
+
+/// Temp path with the same extension as the real output, so the container choice holds.
+fn sample_output_path(output_path: &str) -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join("ffmpeg-gui-samples");
+    fs::create_dir_all(&dir).map_err(|error| format!("创建样片临时目录失败：{error}"))?;
+
+    let extension = Path::new(output_path)
+        .extension()
+        .and_then(|value| value.to_str())
+        .unwrap_or("mp4");
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+
+    Ok(dir.join(format!("sample-{}-{stamp}.{extension}", std::process::id())))
+}
+
+pub fn estimate_full_size(sample_bytes: u64, sample_sec: f64, full_sec: f64) -> u64 {
+    if sample_sec <= 0.0 {
+        return sample_bytes;
+    }
+
+    (sample_bytes as f64 * full_sec / sample_sec).round() as u64
+}
+
+/// Encodes one window of the job into a fresh temp file; returns its path, args and size.
+pub(crate) fn encode_window(job: &JobPayload, window: &SampleWindow) -> Result<(PathBuf, Vec<String>, u64), String> {
+    let path = sample_output_path(&text_from_option(&job.output_path))?;
+    let args = build_ffmpeg_args(&sample_job(job, window, &path))?;
+    let configured_ffmpeg = text_from_option(&job.ffmpeg_path);
+    let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+    run_command(&ffmpeg_path, &args, "ffmpeg", &configured_ffmpeg)?;
+
+    let size_bytes = fs::metadata(&path)
+        .map_err(|error| format!("读取样片文件失败：{error}"))?
+        .len();
+
+    Ok((path, args, size_bytes))
+}
+
+/// Encodes a short window with the job's exact settings into a temp file.
+pub fn render_sample(payload: &SampleRenderPayload) -> Result<SampleRenderResult, String> {
+    let job = &payload.job;
+    if text_from_option(&job.mode) == "raw" {
+        return Err("raw 模式的参数由用户完全控制，无法自动替换时间范围和输出路径".to_string());
+    }
+
+    if !has_text(&text_from_option(&job.input_path)) || !has_text(&text_from_option(&job.output_path)) {
+        return Err("inputPath and outputPath are required".to_string());
+    }
+
+    let job_start = resolve_trim(job)?.start_sec.unwrap_or(0.0);
+    let full_duration = full_duration_sec(job, job_start)?;
+    let window = sample_window(
+        job_start,
+        full_duration,
+        payload.sample_start_sec,
+        payload.sample_duration_sec,
+    )?;
+    let (path, args, size_bytes) = encode_window(job, &window)?;
+
+    Ok(SampleRenderResult {
+        path: path.to_string_lossy().to_string(),
+        args,
+        start_sec: window.start_sec,
+        duration_sec: window.duration_sec,
+        size_bytes,
+        full_duration_sec: full_duration,
+        estimated_size_bytes: estimate_full_size(size_bytes, window.duration_sec, full_duration),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_window_defaults_to_middle_of_job_range() {
+        assert_eq!(
+            sample_window(60.0, 7200.0, None, None).unwrap(),
+            SampleWindow {
+                start_sec: 3656.0,
+                duration_sec: 8.0,
+            }
+        );
+        assert_eq!(sample_window(0.0, 4.0, None, Some(10.0)).unwrap().duration_sec, 4.0);
+        assert_eq!(
+            sample_window(0.0, 100.0, Some(92.0), Some(8.0)).unwrap().start_sec,
+            92.0
+        );
+        assert!(sample_window(0.0, 100.0, Some(95.0), Some(8.0)).is_err());
+        assert!(sample_window(30.0, 100.0, Some(10.0), None).is_err());
+        assert!(sample_window(0.0, 100.0, None, Some(0.5)).is_err());
+    }
+
+    #[test]
+    fn sample_job_overrides_range_and_output_only() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            preset: Some("h265".to_string()),
+            input_path: Some("movie.mkv".to_string()),
+            output_path: Some("/videos/movie_converted.mkv".to_string()),
+            start_time: Some("00:01:00".to_string()),
+            overwrite: Some(false),
+            crf: Some(24.0),
+            ..Default::default()
+        };
+        let window = SampleWindow {
+            start_sec: 1800.5,
+            duration_sec: 6.0,
+        };
+
+        let args = build_ffmpeg_args(&sample_job(&job, &window, Path::new("/tmp/sample.mkv"))).unwrap();
+        let text = args.join(" ");
+        assert!(text.starts_with("-y -ss 1800.5 -t 6 -i movie.mkv"));
+        assert!(text.contains("-crf 24"));
+        assert_eq!(args.last().map(String::as_str), Some("/tmp/sample.mkv"));
+        assert!(!text.contains("movie_converted"));
+    }
+
+    #[test]
+    fn cut_list_jobs_have_no_single_range_to_sample() {
+        let job = JobPayload {
+            input_path: Some("movie.mkv".to_string()),
+            cut_ranges: Some(vec![crate::cutlist::TimeRange {
+                start_sec: 0.0,
+                end_sec: 10.0,
+            }]),
+            ..Default::default()
+        };
+        assert_eq!(
+            full_duration_sec(&job, 0.0).unwrap_err(),
+            "cutRanges cannot be combined with sample renders or CRF search"
+        );
+    }
+
+    #[test]
+    fn estimate_scales_sample_size_to_full_length() {
+        assert_eq!(estimate_full_size(2_000_000, 8.0, 7200.0), 1_800_000_000);
+        assert_eq!(estimate_full_size(1234, 0.0, 60.0), 1234);
+    }
+}
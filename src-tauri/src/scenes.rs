@@ -0,0 +1,208 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::job::{has_text, push_trim_args, resolve_trim, text_from_option, JobPayload, TrimRange};
+use crate::probe::{resolve_executable_path, run_command};
+use crate::sample::full_duration_sec;
+use crate::template::format_seconds;
+
+const DEFAULT_THRESHOLD: f64 = 0.4;
+const DEFAULT_MIN_GAP_SEC: f64 = 1.0;
+const SHOWINFO_LOG_MARKER: &str = "Parsed_showinfo";
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneDetectPayload {
+    pub input_path: Option<String>,
+    pub ffmpeg_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    /// Same meaning as the job fields; cut times are then relative to `start_time`, i.e. in output time.
+    pub start_time: Option<String>,
+    pub duration: Option<String>,
+    /// Scene score (0–1) a frame must exceed to count as a cut.
+    pub threshold: Option<f64>,
+    /// Cuts closer than this to the previous one (or to the start) are dropped.
+    pub min_gap_sec: Option<f64>,
+    /// Writes the cuts as an FFMETADATA chapter file here, for the job's `chaptersPath`.
+    pub chapters_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneDetectResult {
+    pub cuts: Vec<f64>,
+    pub duration_sec: f64,
+    pub chapters_path: Option<String>,
+}
+
+pub fn scene_detect_args(input_path: &str, trim: &TrimRange, threshold: f64) -> Vec<String> {
+    let mut args = vec!["-hide_banner".to_string(), "-nostats".to_string()];
+    push_trim_args(&mut args, trim);
+    args.extend([
+        "-i".to_string(),
+        input_path.to_string(),
+        "-map".to_string(),
+        "0:v:0".to_string(),
+        // showinfo logs one line per frame that passes the select.
+        "-vf".to_string(),
+        format!("select='gt(scene,{})',showinfo", format_seconds(threshold)),
+        "-an".to_string(),
+        "-sn".to_string(),
+        "-dn".to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ]);
+    args
+}
+
+/// Reads `pts_time:` from the showinfo lines, e.g.
+/// `[Parsed_showinfo_1 @ 0x...] n:   0 pts: 123123 pts_time:5.13013 duration: ...`.
+pub fn parse_scene_cuts(stderr: &str) -> Vec<f64> {
+    stderr
+        .lines()
+        .filter(|line| line.contains(SHOWINFO_LOG_MARKER))
+        .filter_map(|line| {
+            let value = &line[line.find("pts_time:")? + "pts_time:".len()..];
+            value.split_whitespace().next()?.parse::<f64>().ok()
+        })
+        .filter(|time| time.is_finite() && *time >= 0.0)
+        .collect()
+}
+
+/// Sorts the cuts and drops any within `min_gap` of the previous kept cut or of the start.
+pub fn merge_close_cuts(mut cuts: Vec<f64>, min_gap: f64) -> Vec<f64> {
+    cuts.sort_by(f64::total_cmp);
+    let mut kept: Vec<f64> = Vec::new();
+    for cut in cuts {
+        if cut - kept.last().copied().unwrap_or(0.0) >= min_gap {
+            kept.push(cut);
+        }
+    }
+    kept
+}
+
+/// One chapter per scene, in milliseconds, from 0 to `duration_sec`.
+pub fn ffmetadata_chapters(cuts: &[f64], duration_sec: f64) -> String {
+    let mut bounds = vec![0];
+    bounds.extend(
+        cuts.iter()
+            .filter(|cut| **cut > 0.0 && **cut < duration_sec)
+            .map(|cut| (cut * 1000.0).round() as u64),
+    );
+    bounds.push((duration_sec * 1000.0).round() as u64);
+
+    let mut text = String::from(";FFMETADATA1\n");
+    for (index, pair) in bounds.windows(2).enumerate() {
+        text.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle=Chapter {}\n",
+            pair[0],
+            pair[1],
+            index + 1
+        ));
+    }
+    text
+}
+
+pub fn detect_scenes(payload: &SceneDetectPayload) -> Result<SceneDetectResult, String> {
+    let input_path = text_from_option(&payload.input_path);
+    if !has_text(&input_path) {
+        return Err("inputPath is required".to_string());
+    }
+
+    let threshold = payload.threshold.unwrap_or(DEFAULT_THRESHOLD);
+    if !threshold.is_finite() || threshold <= 0.0 || threshold >= 1.0 {
+        return Err("threshold must be between 0 and 1".to_string());
+    }
+    let min_gap = payload.min_gap_sec.unwrap_or(DEFAULT_MIN_GAP_SEC);
+    if !min_gap.is_finite() || min_gap < 0.0 {
+        return Err("minGapSec must be a non-negative number".to_string());
+    }
+
+    let range_job = JobPayload {
+        input_path: Some(input_path.clone()),
+        ffprobe_path: payload.ffprobe_path.clone(),
+        start_time: payload.start_time.clone(),
+        duration: payload.duration.clone(),
+        ..Default::default()
+    };
+    let trim = resolve_trim(&range_job)?;
+    let duration_sec = full_duration_sec(&range_job, trim.start_sec.unwrap_or(0.0))?;
+
+    let configured_ffmpeg = text_from_option(&payload.ffmpeg_path);
+    let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+    let args = scene_detect_args(&input_path, &trim, threshold);
+    let (_, stderr) = run_command(&ffmpeg_path, &args, "ffmpeg", &configured_ffmpeg)?;
+    let cuts = merge_close_cuts(parse_scene_cuts(&stderr), min_gap);
+
+    let chapters_path = text_from_option(&payload.chapters_path);
+    let chapters_path = if has_text(&chapters_path) {
+        fs::write(&chapters_path, ffmetadata_chapters(&cuts, duration_sec))
+            .map_err(|error| format!("写入章节文件失败：{error}"))?;
+        Some(chapters_path)
+    } else {
+        None
+    };
+
+    Ok(SceneDetectResult {
+        cuts,
+        duration_sec,
+        chapters_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_scene_cuts_reads_showinfo_lines_only() {
+        let stderr = "\
+[Parsed_showinfo_1 @ 0x5617] config in time_base: 1/24000, frame_rate: 24000/1001
+[Parsed_showinfo_1 @ 0x5617] n:   0 pts: 123123 pts_time:5.13013 duration:   1001 duration_time:0.0417083 fmt:yuv420p
+[Parsed_showinfo_1 @ 0x5617] n:   1 pts: 300300 pts_time:12.5125 duration:   1001 duration_time:0.0417083 fmt:yuv420p
+[out#0/null @ 0x5618] video:12KiB audio:0KiB pts_time:99";
+
+        assert_eq!(parse_scene_cuts(stderr), vec![5.13013, 12.5125]);
+        assert_eq!(
+            merge_close_cuts(vec![12.5, 0.4, 5.0, 5.6, 30.0], 1.0),
+            vec![5.0, 12.5, 30.0]
+        );
+    }
+
+    #[test]
+    fn chapters_cover_the_whole_output() {
+        assert_eq!(
+            ffmetadata_chapters(&[5.13, 99.0], 20.0),
+            ";FFMETADATA1\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=5130\ntitle=Chapter 1\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=5130\nEND=20000\ntitle=Chapter 2\n"
+        );
+        let trim = TrimRange {
+            start_sec: Some(60.0),
+            ..Default::default()
+        };
+        assert!(scene_detect_args("in.mp4", &trim, 0.3)
+            .join(" ")
+            .contains("-ss 60 -i in.mp4 -map 0:v:0 -vf select='gt(scene,0.3)',showinfo"));
+    }
+
+    #[test]
+    fn visual_args_mux_chapters_after_subtitle_input() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            preset: Some("custom".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mkv".to_string()),
+            subtitle_path: Some("talk.srt".to_string()),
+            chapters_path: Some("chapters.txt".to_string()),
+            disable_audio: Some(true),
+            ..Default::default()
+        };
+
+        let args = crate::job::build_visual_args(&job).expect("build failed").join(" ");
+        assert!(args.starts_with("-y -i in.mov -i talk.srt -f ffmetadata -i chapters.txt "));
+        assert!(args.ends_with("-map 1:0 -c:s srt -map_chapters 2 out.mkv"));
+    }
+}
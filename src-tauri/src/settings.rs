@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::job::{has_text, suggest_output_path, text_from_option, JobPayload};
+
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+pub const SETTINGS_SCHEMA_VERSION: u64 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub schema_version: u64,
+    pub ffmpeg_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    pub default_preset: Option<String>,
+    pub output_dir: Option<String>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            schema_version: SETTINGS_SCHEMA_VERSION,
+            ffmpeg_path: None,
+            ffprobe_path: None,
+            default_preset: None,
+            output_dir: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsPayload {
+    ffmpeg_path: Option<String>,
+    ffprobe_path: Option<String>,
+    default_preset: Option<String>,
+    output_dir: Option<String>,
+}
+
+fn normalize_setting(value: &Option<String>) -> Option<String> {
+    let text = text_from_option(value);
+    if has_text(&text) {
+        Some(text)
+    } else {
+        None
+    }
+}
+
+impl AppSettings {
+    /// Fields present in the payload overwrite the stored value; an empty string clears it.
+    pub fn merge(&mut self, patch: &SettingsPayload) {
+        if patch.ffmpeg_path.is_some() {
+            self.ffmpeg_path = normalize_setting(&patch.ffmpeg_path);
+        }
+
+        if patch.ffprobe_path.is_some() {
+            self.ffprobe_path = normalize_setting(&patch.ffprobe_path);
+        }
+
+        if patch.default_preset.is_some() {
+            self.default_preset = normalize_setting(&patch.default_preset);
+        }
+
+        if patch.output_dir.is_some() {
+            self.output_dir = normalize_setting(&patch.output_dir);
+        }
+    }
+
+    /// Fills empty job fields from the stored settings without overriding anything the UI sent.
+    pub fn apply_to_job(&self, job: &mut JobPayload) {
+        if !has_text(&text_from_option(&job.ffmpeg_path)) {
+            job.ffmpeg_path = self.ffmpeg_path.clone();
+        }
+
+        if !has_text(&text_from_option(&job.ffprobe_path)) {
+            job.ffprobe_path = self.ffprobe_path.clone();
+        }
+
+        if !has_text(&text_from_option(&job.preset)) {
+            job.preset = self.default_preset.clone();
+        }
+
+        let input_path = text_from_option(&job.input_path);
+        if has_text(&text_from_option(&job.output_path)) || !has_text(&input_path) {
+            return;
+        }
+
+        if let Some(output_dir) = &self.output_dir {
+            let preset = text_from_option(&job.preset);
+            let suggested = suggest_output_path(&input_path, &preset);
+            if let Some(file_name) = Path::new(&suggested).file_name() {
+                job.output_path = Some(Path::new(output_dir).join(file_name).to_string_lossy().to_string());
+            }
+        }
+    }
+}
+
+/// Upgrades an on-disk settings document to the current schema, one version at a time.
+fn migrate_settings_value(mut value: Value) -> Value {
+    let Some(object) = value.as_object_mut() else {
+        return Value::Object(Default::default());
+    };
+
+    let mut version = object
+        .get("schemaVersion")
+        .and_then(|version| version.as_u64())
+        .unwrap_or(0);
+
+    while version < SETTINGS_SCHEMA_VERSION {
+        if version == 0 {
+            // Unversioned files may be hand-written with snake_case keys.
+            for (legacy, current) in [
+                ("ffmpeg_path", "ffmpegPath"),
+                ("ffprobe_path", "ffprobePath"),
+                ("default_preset", "defaultPreset"),
+                ("output_dir", "outputDir"),
+            ] {
+                if let Some(entry) = object.remove(legacy) {
+                    object.entry(current).or_insert(entry);
+                }
+            }
+        }
+
+        version += 1;
+    }
+
+    object.insert("schemaVersion".to_string(), Value::from(SETTINGS_SCHEMA_VERSION));
+    value
+}
+
+pub fn load_settings_from(path: &Path) -> Result<AppSettings, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(AppSettings::default()),
+        Err(error) => return Err(format!("读取设置文件失败：{error}")),
+    };
+
+    let parsed: Value = serde_json::from_str(&text).map_err(|_| "设置文件不是有效的 JSON".to_string())?;
+    serde_json::from_value(migrate_settings_value(parsed)).map_err(|error| format!("设置文件格式无效：{error}"))
+}
+
+/// Applies the patch to the stored settings. A file that can't be read is an error rather
+/// than defaults, so saving never overwrites it.
+pub fn update_settings_at(path: &Path, patch: &SettingsPayload) -> Result<AppSettings, String> {
+    let mut settings = load_settings_from(path)?;
+    settings.merge(patch);
+    save_settings_to(path, &settings)?;
+    Ok(settings)
+}
+
+pub fn save_settings_to(path: &Path, settings: &AppSettings) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|error| format!("创建设置目录失败：{error}"))?;
+    }
+
+    let text = serde_json::to_string_pretty(settings).map_err(|error| error.to_string())?;
+    let staging_path = path.with_extension("json.tmp");
+    fs::write(&staging_path, text).map_err(|error| format!("写入设置文件失败：{error}"))?;
+    fs::rename(&staging_path, path).map_err(|error| format!("写入设置文件失败：{error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrate_settings_value_upgrades_unversioned_documents() {
+        let migrated = migrate_settings_value(json!({
+            "ffmpeg_path": "/usr/bin/ffmpeg",
+            "outputDir": "/tmp/out"
+        }));
+        let settings: AppSettings = serde_json::from_value(migrated).expect("migrated settings");
+
+        assert_eq!(settings.schema_version, SETTINGS_SCHEMA_VERSION);
+        assert_eq!(settings.ffmpeg_path.as_deref(), Some("/usr/bin/ffmpeg"));
+        assert_eq!(settings.output_dir.as_deref(), Some("/tmp/out"));
+    }
+
+    #[test]
+    fn settings_round_trip_through_disk() {
+        let dir = std::env::temp_dir().join(format!("ffmpeg-gui-settings-{}", std::process::id()));
+        let path = dir.join(SETTINGS_FILE_NAME);
+        assert_eq!(load_settings_from(&path).expect("missing file"), AppSettings::default());
+
+        let mut settings = AppSettings::default();
+        settings.merge(&SettingsPayload {
+            ffprobe_path: Some(" /opt/ffprobe ".to_string()),
+            default_preset: Some("h265".to_string()),
+            ..Default::default()
+        });
+        save_settings_to(&path, &settings).expect("save failed");
+
+        assert_eq!(load_settings_from(&path).expect("load failed"), settings);
+        assert_eq!(settings.ffprobe_path.as_deref(), Some("/opt/ffprobe"));
+
+        fs::write(&path, "{ broken").expect("write failed");
+        assert!(update_settings_at(&path, &SettingsPayload::default()).is_err());
+        assert_eq!(fs::read_to_string(&path).expect("read failed"), "{ broken");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn apply_to_job_only_fills_empty_fields() {
+        let settings = AppSettings {
+            ffmpeg_path: Some("/opt/ffmpeg".to_string()),
+            default_preset: Some("mp3".to_string()),
+            output_dir: Some("/exports".to_string()),
+            ..Default::default()
+        };
+
+        let mut job = JobPayload {
+            ffmpeg_path: Some("/custom/ffmpeg".to_string()),
+            input_path: Some("/media/talk.wav".to_string()),
+            ..Default::default()
+        };
+        settings.apply_to_job(&mut job);
+
+        assert_eq!(job.ffmpeg_path.as_deref(), Some("/custom/ffmpeg"));
+        assert_eq!(job.preset.as_deref(), Some("mp3"));
+        assert_eq!(job.output_path.as_deref(), Some("/exports/talk_converted.mp3"));
+    }
+}
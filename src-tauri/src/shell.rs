@@ -0,0 +1,451 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::command::{preview_job, split_command_line};
+use crate::job::{
+    build_ffmpeg_args, extension_for_preset, has_text, text_from_option, JobPayload, INPUT_PLACEHOLDER,
+    OUTPUT_PLACEHOLDER,
+};
+use crate::template::{parse_templates, MEDIA_PLACEHOLDERS};
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    #[default]
+    Bash,
+    PowerShell,
+    Cmd,
+    Fish,
+}
+
+impl Shell {
+    pub fn parse(text: &str) -> Result<Shell, String> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "" | "bash" | "sh" | "zsh" => Ok(Shell::Bash),
+            "powershell" | "pwsh" => Ok(Shell::PowerShell),
+            "cmd" | "bat" => Ok(Shell::Cmd),
+            "fish" => Ok(Shell::Fish),
+            other => Err(format!("Unsupported shell: {other}")),
+        }
+    }
+
+    pub fn script_extension(&self) -> &'static str {
+        match self {
+            Shell::Bash => "sh",
+            Shell::PowerShell => "ps1",
+            Shell::Cmd => "bat",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellExportPayload {
+    pub shell: Option<String>,
+    /// Renders a script that loops over its arguments instead of a single command.
+    pub script: Option<bool>,
+    #[serde(default)]
+    pub job: JobPayload,
+}
+
+/// One piece of an argument in a script: literal text or the loop's input/output variable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Piece<'a> {
+    Text(&'a str),
+    Input,
+    Output,
+}
+
+fn split_placeholders(arg: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = arg;
+
+    while !rest.is_empty() {
+        let next = [(INPUT_PLACEHOLDER, Piece::Input), (OUTPUT_PLACEHOLDER, Piece::Output)]
+            .into_iter()
+            .filter_map(|(placeholder, piece)| rest.find(placeholder).map(|at| (at, placeholder.len(), piece)))
+            .min_by_key(|(at, _, _)| *at);
+
+        let Some((at, len, piece)) = next else {
+            pieces.push(Piece::Text(rest));
+            break;
+        };
+
+        if at > 0 {
+            pieces.push(Piece::Text(&rest[..at]));
+        }
+        pieces.push(piece);
+        rest = &rest[at + len..];
+    }
+
+    pieces
+}
+
+fn is_plain_arg(shell: Shell, text: &str) -> bool {
+    !text.is_empty()
+        && text.chars().all(|ch| {
+            ch.is_ascii_alphanumeric()
+                || match shell {
+                    Shell::Bash => "_./:=+,-@%".contains(ch),
+                    Shell::Fish => "_./:=+,-@".contains(ch),
+                    // PowerShell splits `-opt.x` and `-opt:x` tokens, so only bare words stay unquoted.
+                    Shell::PowerShell => "_/-".contains(ch),
+                    // `=` and `,` are argument separators for cmd.
+                    Shell::Cmd => "_./:+-\\".contains(ch),
+                }
+        })
+}
+
+/// Escapes text for the MSVCRT argv parser inside a double-quoted argument.
+fn escape_windows_argv(text: &str, closing_quote: bool) -> String {
+    let mut escaped = String::new();
+    let mut backslashes = 0;
+
+    for ch in text.chars() {
+        match ch {
+            '\\' => backslashes += 1,
+            '"' => {
+                escaped.push_str(&"\\".repeat(backslashes * 2 + 1));
+                escaped.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                escaped.push_str(&"\\".repeat(backslashes));
+                escaped.push(ch);
+                backslashes = 0;
+            }
+        }
+    }
+
+    // Backslashes right before the closing quote would escape it.
+    let trailing = if closing_quote { backslashes * 2 } else { backslashes };
+    escaped.push_str(&"\\".repeat(trailing));
+    escaped
+}
+
+/// Applies cmd.exe escaping on top of an argv-quoted string. cmd toggles its own quote
+/// state on every `"`, so metacharacters are caret-escaped whenever that state is off.
+fn escape_cmd(argv_text: &str, in_script: bool) -> String {
+    let mut escaped = String::new();
+    let mut quoted = false;
+
+    for ch in argv_text.chars() {
+        match ch {
+            '"' => {
+                quoted = !quoted;
+                escaped.push(ch);
+            }
+            // Batch files double `%`; interactively a caret only works outside quotes,
+            // so the quote is closed around it.
+            '%' if in_script => escaped.push_str("%%"),
+            '%' if quoted => escaped.push_str("\"^%\""),
+            '%' => escaped.push_str("^%"),
+            '&' | '|' | '<' | '>' | '^' | '(' | ')' | '!' if !quoted => {
+                escaped.push('^');
+                escaped.push(ch);
+            }
+            _ => escaped.push(ch),
+        }
+    }
+
+    escaped
+}
+
+// Stand-ins for the loop variables while cmd escaping runs, so their `%` are left alone.
+const CMD_INPUT_MARK: char = '\u{1}';
+const CMD_OUTPUT_MARK: char = '\u{2}';
+
+fn quote_cmd(pieces: &[Piece<'_>], in_script: bool) -> String {
+    let last = pieces.len().saturating_sub(1);
+    let body: String = pieces
+        .iter()
+        .enumerate()
+        .map(|(index, piece)| match piece {
+            Piece::Text(text) => escape_windows_argv(text, index == last),
+            Piece::Input => CMD_INPUT_MARK.to_string(),
+            Piece::Output => CMD_OUTPUT_MARK.to_string(),
+        })
+        .collect();
+
+    escape_cmd(&format!("\"{body}\""), in_script)
+}
+
+/// Quotes one argument so the given shell passes it to ffmpeg unchanged.
+pub fn quote_for_shell(shell: Shell, value: &str) -> String {
+    if is_plain_arg(shell, value) {
+        return value.to_string();
+    }
+
+    match shell {
+        Shell::Bash => format!("'{}'", value.replace('\'', "'\\''")),
+        Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+        Shell::PowerShell => format!("'{}'", value.replace('\'', "''")),
+        Shell::Cmd => quote_cmd(&[Piece::Text(value)], false),
+    }
+}
+
+/// Renders the binary and args as a single copy-paste command for the shell.
+pub fn format_shell_command(shell: Shell, binary_path: &str, args: &[String]) -> String {
+    let binary = if has_text(binary_path) {
+        binary_path.trim()
+    } else {
+        "ffmpeg"
+    };
+
+    let mut command: Vec<String> = vec![quote_for_shell(shell, binary)];
+    command.extend(args.iter().map(|arg| quote_for_shell(shell, arg)));
+
+    let line = command.join(" ");
+    if shell == Shell::PowerShell {
+        // A quoted string is an expression in PowerShell; the call operator runs it.
+        format!("& {line}")
+    } else {
+        line
+    }
+}
+
+/// Quotes an argument that may reference the loop's input/output variables.
+fn quote_script_arg(shell: Shell, arg: &str, suffix: &str) -> String {
+    let pieces = split_placeholders(arg);
+    if shell == Shell::Cmd {
+        let output = format!("%%~dpnF{}", suffix.replace('%', "%%"));
+        return if is_plain_arg(shell, arg) {
+            arg.to_string()
+        } else {
+            quote_cmd(&pieces, true)
+                .replace(CMD_INPUT_MARK, "%%~fF")
+                .replace(CMD_OUTPUT_MARK, &output)
+        };
+    }
+
+    if pieces.iter().all(|piece| matches!(piece, Piece::Text(_))) {
+        return quote_for_shell(shell, arg);
+    }
+
+    match shell {
+        Shell::PowerShell => {
+            let body: String = pieces
+                .iter()
+                .map(|piece| match piece {
+                    Piece::Text(text) => text.replace('`', "``").replace('"', "`\"").replace('$', "`$"),
+                    Piece::Input => "$inFile".to_string(),
+                    Piece::Output => "$outFile".to_string(),
+                })
+                .collect();
+            format!("\"{body}\"")
+        }
+        _ => pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => quote_for_shell(shell, text),
+                Piece::Input => "\"$in_file\"".to_string(),
+                Piece::Output => "\"$out_file\"".to_string(),
+            })
+            .collect(),
+    }
+}
+
+fn output_suffix(job: &JobPayload) -> String {
+    let output_path = text_from_option(&job.output_path);
+    let extension = Path::new(&output_path)
+        .extension()
+        .and_then(|value| value.to_str())
+        .filter(|value| has_text(value) && !output_path.contains(OUTPUT_PLACEHOLDER))
+        .map(|value| format!(".{value}"))
+        .unwrap_or_else(|| extension_for_preset(&text_from_option(&job.preset)).to_string());
+
+    format!("_converted{extension}")
+}
+
+/// Renders a script that runs the job once per input given on its command line, writing
+/// `<stem>_converted.<ext>` next to each input and stopping at the first failure.
+pub fn format_shell_script(shell: Shell, job: &JobPayload) -> Result<String, String> {
+    if text_from_option(&job.mode) == "raw" {
+        // Only `{input}` and `{output}` become loop variables; the other input-derived
+        // placeholders would reach ffmpeg as literal braces.
+        let (_, used) = parse_templates(&split_command_line(&text_from_option(&job.raw_args))?)?;
+        let per_input = used
+            .iter()
+            .find(|name| ["stem", "dir", "ext"].contains(&name.as_str()) || MEDIA_PLACEHOLDERS.contains(&name.as_str()));
+        if let Some(name) = per_input {
+            return Err(format!("{{{name}}} cannot be used in an exported script"));
+        }
+    }
+
+    let mut template = job.clone();
+    template.input_path = Some(INPUT_PLACEHOLDER.to_string());
+    template.output_path = Some(OUTPUT_PLACEHOLDER.to_string());
+
+    let args = build_ffmpeg_args(&template)?;
+    let binary = text_from_option(&job.ffmpeg_path);
+    let binary = if has_text(&binary) { binary } else { "ffmpeg".to_string() };
+    let suffix = output_suffix(job);
+
+    let mut command: Vec<String> = vec![quote_script_arg(shell, &binary, &suffix)];
+    command.extend(args.iter().map(|arg| quote_script_arg(shell, arg, &suffix)));
+    let command = command.join(" ");
+
+    let script = match shell {
+        Shell::Bash => format!(
+            // Only a dot in the file name starts an extension, not one in a directory name.
+            "#!/usr/bin/env bash\nset -euo pipefail\n\nfor in_file in \"$@\"; do\n  stem=\"$in_file\"\n  if [[ \"${{in_file##*/}}\" == *.* ]]; then stem=\"${{in_file%.*}}\"; fi\n  out_file=\"$stem\"{}\n  {command}\ndone\n",
+            quote_for_shell(shell, &suffix)
+        ),
+        Shell::Fish => format!(
+            "#!/usr/bin/env fish\n\nfor in_file in $argv\n    set out_file (string replace -r '\\.[^./]*$' '' -- $in_file){}\n    {command}; or exit $status\nend\n",
+            quote_for_shell(shell, &suffix)
+        ),
+        Shell::PowerShell => format!(
+            "$ErrorActionPreference = 'Stop'\n\nforeach ($inFile in $args) {{\n    $outFile = [System.IO.Path]::Combine([System.IO.Path]::GetDirectoryName($inFile), [System.IO.Path]::GetFileNameWithoutExtension($inFile) + {})\n    & {command}\n    if ($LASTEXITCODE -ne 0) {{ exit $LASTEXITCODE }}\n}}\n",
+            quote_for_shell(shell, &suffix)
+        ),
+        Shell::Cmd => format!(
+            "@echo off\r\nsetlocal\r\n\r\nfor %%F in (%*) do (\r\n  {command} || exit /b 1\r\n)\r\n"
+        ),
+    };
+
+    Ok(script)
+}
+
+/// Renders the job for the requested shell, as a one-off command or a batch script.
+pub fn export_for_shell(payload: &ShellExportPayload) -> Result<String, String> {
+    let shell = Shell::parse(&text_from_option(&payload.shell))?;
+    if payload.script.unwrap_or(false) {
+        return format_shell_script(shell, &payload.job);
+    }
+
+    let preview = preview_job(&payload.job)?;
+    Ok(format_shell_command(shell, &text_from_option(&payload.job.ffmpeg_path), &preview.args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic xorshift so the generated cases are reproducible without extra crates.
+    fn random_args(seed: u64, count: usize) -> Vec<String> {
+        const ALPHABET: &[char] = &[
+            'a', 'Z', '0', ' ', '\t', '\n', '\'', '"', '\\', '$', '`', '!', '*', '?', '~', '#', '{', '}', '(', ')',
+            ';', '&', '|', '<', '>', '%', '=', ',', ':', '-', '.', '/', 'é', '中',
+        ];
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        (0..count)
+            .map(|_| {
+                let len = (next() % 12) as usize;
+                (0..len).map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize]).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bash_quoting_round_trips_through_tokenizer() {
+        for seed in 1..200 {
+            let args = random_args(seed * 7919, 8);
+            let command = format_shell_command(Shell::Bash, "ffmpeg", &args);
+            let tokens = split_command_line(&command).expect("tokenize failed");
+
+            let expected: Vec<String> = std::iter::once("ffmpeg".to_string())
+                .chain(args.iter().filter(|arg| !arg.is_empty()).cloned())
+                .collect();
+            let actual: Vec<String> = tokens.into_iter().filter(|token| !token.is_empty()).collect();
+            assert_eq!(actual, expected, "command: {command}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bash_quoting_survives_a_real_shell() {
+        if !Path::new("/bin/bash").exists() {
+            return;
+        }
+
+        let args = random_args(42, 64);
+        let quoted: Vec<String> = args.iter().map(|arg| quote_for_shell(Shell::Bash, arg)).collect();
+        let output = std::process::Command::new("/bin/bash")
+            .arg("-c")
+            .arg(format!("printf '%s\\0' {}", quoted.join(" ")))
+            .output()
+            .expect("bash failed");
+
+        let printed = String::from_utf8(output.stdout).expect("utf8");
+        let actual: Vec<&str> = printed.split('\0').collect();
+        assert_eq!(&actual[..args.len()], args.iter().map(String::as_str).collect::<Vec<_>>().as_slice());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bash_script_strips_only_the_file_extension() {
+        if !Path::new("/bin/bash").exists() {
+            return;
+        }
+
+        let job = JobPayload {
+            mode: Some("raw".to_string()),
+            raw_args: Some("-y {output}".to_string()),
+            ffmpeg_path: Some("echo".to_string()),
+            ..Default::default()
+        };
+        let script = format_shell_script(Shell::Bash, &job).expect("script failed");
+        let output = std::process::Command::new("/bin/bash")
+            .arg("-c")
+            .arg(&script)
+            .args(["script", "clips.v2/raw", "clips.v2/take.1.mov"])
+            .output()
+            .expect("bash failed");
+
+        assert_eq!(
+            String::from_utf8(output.stdout).expect("utf8"),
+            "-y clips.v2/raw_converted.mp4\n-y clips.v2/take.1_converted.mp4\n"
+        );
+    }
+
+    #[test]
+    fn other_shells_escape_their_own_metacharacters() {
+        assert_eq!(quote_for_shell(Shell::PowerShell, "it's $HOME"), "'it''s $HOME'");
+        assert_eq!(quote_for_shell(Shell::Fish, "a\\b'c"), "'a\\\\b\\'c'");
+        assert_eq!(quote_for_shell(Shell::Cmd, "50% off"), "\"50\"^%\" off\"");
+        // The escaped quote flips cmd's own quote state, so the `&` after it needs a caret.
+        assert_eq!(quote_for_shell(Shell::Cmd, "a\"b & c"), "\"a\\\"b ^& c\"");
+        assert_eq!(format_shell_command(Shell::PowerShell, "C:\\ff mpeg.exe", &[]), "& 'C:\\ff mpeg.exe'");
+    }
+
+    #[test]
+    fn bash_script_loops_over_inputs() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            preset: Some("mp3".to_string()),
+            ..Default::default()
+        };
+        let script = format_shell_script(Shell::Bash, &job).expect("script failed");
+
+        assert!(script.starts_with("#!/usr/bin/env bash\nset -euo pipefail\n"));
+        assert!(script.contains("out_file=\"$stem\"_converted.mp3"));
+        assert!(script.contains("-i \"$in_file\""));
+        assert!(script.contains(" \"$out_file\"\ndone"));
+
+        let raw = JobPayload {
+            mode: Some("raw".to_string()),
+            raw_args: Some("-i {input} -metadata title={stem} {output}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_shell_script(Shell::Bash, &raw).unwrap_err(),
+            "{stem} cannot be used in an exported script"
+        );
+        let plain_raw = JobPayload {
+            raw_args: Some("-i {input} -c copy {output}".to_string()),
+            ..raw
+        };
+        assert!(format_shell_script(Shell::Bash, &plain_raw)
+            .expect("script failed")
+            .contains("-i \"$in_file\" -c copy \"$out_file\""));
+    }
+}
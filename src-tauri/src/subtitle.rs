@@ -0,0 +1,267 @@
+use std::path::Path;
+
+use crate::job::{has_text, text_from_option, JobPayload};
+use crate::template::format_seconds;
+use crate::video_filter::escape_filter_value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubtitleSource {
+    /// External `.srt` / `.ass` / `.ssa` / `.vtt` file.
+    File(String),
+    /// Subtitle-relative stream index in the main input (`0:s:N`).
+    Stream(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleMode {
+    Soft,
+    Burn,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitlePlan {
+    pub source: SubtitleSource,
+    pub mode: SubtitleMode,
+}
+
+fn file_extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|value| value.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn is_ass_file(path: &str) -> bool {
+    matches!(file_extension(path).as_str(), "ass" | "ssa")
+}
+
+/// Reads the subtitle fields of the job; `None` when no subtitle is selected.
+pub fn subtitle_plan(job: &JobPayload) -> Result<Option<SubtitlePlan>, String> {
+    let path = text_from_option(&job.subtitle_path);
+    let stream = job.subtitle_stream.filter(|index| index.is_finite());
+
+    let source = match (has_text(&path), stream) {
+        (true, Some(_)) => return Err("subtitlePath and subtitleStream cannot both be set".to_string()),
+        (true, None) => SubtitleSource::File(path),
+        (false, Some(index)) if index >= 0.0 && index.fract() == 0.0 => SubtitleSource::Stream(index as u64),
+        (false, Some(_)) => return Err("subtitleStream must be a non-negative integer".to_string()),
+        (false, None) => return Ok(None),
+    };
+
+    let mode = match text_from_option(&job.subtitle_mode).as_str() {
+        "" | "soft" => SubtitleMode::Soft,
+        "burn" => SubtitleMode::Burn,
+        other => return Err(format!("Unsupported subtitleMode: {other}")),
+    };
+
+    Ok(Some(SubtitlePlan { source, mode }))
+}
+
+/// Builds the `subtitles=` / `ass=` filter that renders the subtitle onto the video.
+///
+/// Cues are timed against the source, but frames restart at zero where an input seek lands;
+/// `seek_offset_sec` moves them back onto the source timeline for the filter and out again.
+pub fn burn_in_filter(source: &SubtitleSource, input_path: &str, seek_offset_sec: f64) -> String {
+    let filter = match source {
+        SubtitleSource::File(path) if is_ass_file(path) => format!("ass=filename={}", escape_filter_value(path)),
+        SubtitleSource::File(path) => format!("subtitles=filename={}", escape_filter_value(path)),
+        SubtitleSource::Stream(index) => {
+            format!("subtitles=filename={}:si={index}", escape_filter_value(input_path))
+        }
+    };
+    if seek_offset_sec <= 0.0 {
+        return filter;
+    }
+
+    let offset = format_seconds(seek_offset_sec);
+    format!("setpts=PTS+{offset}/TB,{filter},setpts=PTS-{offset}/TB")
+}
+
+/// Picks the subtitle codec the output container can hold.
+pub fn soft_subtitle_codec(output_path: &str, source: &SubtitleSource) -> Result<&'static str, String> {
+    match file_extension(output_path).as_str() {
+        "mp4" | "m4v" | "mov" => Ok("mov_text"),
+        "webm" => Ok("webvtt"),
+        "mkv" | "mka" => Ok(match source {
+            SubtitleSource::File(path) if is_ass_file(path) => "ass",
+            SubtitleSource::File(_) => "srt",
+            // Keeps bitmap formats such as PGS, which cannot be converted to text.
+            SubtitleSource::Stream(_) => "copy",
+        }),
+        other => Err(format!("输出格式 {other} 不支持软字幕，请使用 mp4、mov、mkv 或 webm，或改为烧录字幕")),
+    }
+}
+
+fn language_code(job: &JobPayload) -> Result<Option<String>, String> {
+    let language = text_from_option(&job.subtitle_language);
+    if !has_text(&language) {
+        return Ok(None);
+    }
+
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|ch| ch.is_ascii_alphabetic()) {
+        return Err("subtitleLanguage must be a 2 or 3 letter language code".to_string());
+    }
+
+    Ok(Some(language.to_ascii_lowercase()))
+}
+
+/// Pushes the maps, codec and metadata for muxing the subtitle as its own stream.
+/// Adding any `-map` turns off ffmpeg's default stream selection, so video and audio are mapped too.
+pub(crate) fn push_soft_subtitle_args(
+    args: &mut Vec<String>,
+    job: &JobPayload,
+    source: &SubtitleSource,
+    output_path: &str,
+    keep_video: bool,
+    keep_audio: bool,
+) -> Result<(), String> {
+    let codec = soft_subtitle_codec(output_path, source)?;
+    let language = language_code(job)?;
+
+    let map_field = text_from_option(&job.map_field);
+    if has_text(&map_field) {
+        // The metadata below addresses output subtitle stream 0, but the user's map can put
+        // the input's own subtitle streams ahead of the added one.
+        if language.is_some() || job.subtitle_default.is_some() {
+            return Err("subtitleLanguage and subtitleDefault cannot be combined with map".to_string());
+        }
+        args.push("-map".to_string());
+        args.push(map_field);
+    } else {
+        if keep_video {
+            args.push("-map".to_string());
+            args.push("0:v?".to_string());
+        }
+
+        if keep_audio {
+            args.push("-map".to_string());
+            args.push("0:a?".to_string());
+        }
+    }
+
+    args.push("-map".to_string());
+    args.push(match source {
+        SubtitleSource::File(_) => "1:0".to_string(),
+        SubtitleSource::Stream(index) => format!("0:s:{index}"),
+    });
+
+    args.push("-c:s".to_string());
+    args.push(codec.to_string());
+
+    if let Some(language) = language {
+        args.push("-metadata:s:s:0".to_string());
+        args.push(format!("language={language}"));
+    }
+
+    if let Some(is_default) = job.subtitle_default {
+        args.push("-disposition:s:0".to_string());
+        args.push(if is_default { "default" } else { "0" }.to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burn_in_filter_escapes_subtitle_paths() {
+        assert_eq!(
+            burn_in_filter(&SubtitleSource::File("/media/a b.ass".to_string()), "in.mkv", 0.0),
+            "ass=filename=/media/a b.ass"
+        );
+        assert_eq!(
+            burn_in_filter(&SubtitleSource::Stream(1), "/media/x:y.mkv", 0.0),
+            r"subtitles=filename=/media/x\\:y.mkv:si=1"
+        );
+        // After `-ss 90 -i`, frames start at 0 but the cue for 1:30 must still show.
+        assert_eq!(
+            burn_in_filter(&SubtitleSource::File("talk.srt".to_string()), "in.mkv", 90.0),
+            "setpts=PTS+90/TB,subtitles=filename=talk.srt,setpts=PTS-90/TB"
+        );
+    }
+
+    #[test]
+    fn soft_subtitle_codec_follows_container() {
+        let srt = SubtitleSource::File("talk.srt".to_string());
+        assert_eq!(soft_subtitle_codec("out.mp4", &srt), Ok("mov_text"));
+        assert_eq!(soft_subtitle_codec("out.MKV", &srt), Ok("srt"));
+        assert_eq!(soft_subtitle_codec("out.mkv", &SubtitleSource::File("a.ASS".to_string())), Ok("ass"));
+        assert_eq!(soft_subtitle_codec("out.mkv", &SubtitleSource::Stream(0)), Ok("copy"));
+        assert!(soft_subtitle_codec("out.gif", &srt).is_err());
+    }
+
+    #[test]
+    fn visual_args_mux_external_subtitle_with_metadata() {
+        let job = JobPayload {
+            mode: Some("visual".to_string()),
+            preset: Some("custom".to_string()),
+            input_path: Some("in.mov".to_string()),
+            output_path: Some("out.mp4".to_string()),
+            subtitle_path: Some("talk.srt".to_string()),
+            subtitle_language: Some("ENG".to_string()),
+            subtitle_default: Some(true),
+            disable_audio: Some(true),
+            ..Default::default()
+        };
+
+        let args = crate::job::build_visual_args(&job).expect("build failed").join(" ");
+        assert_eq!(
+            args,
+            "-y -i in.mov -i talk.srt -an -map 0:v? -map 1:0 -c:s mov_text \
+             -metadata:s:s:0 language=eng -disposition:s:0 default out.mp4"
+        );
+
+        let burn = JobPayload {
+            subtitle_mode: Some("burn".to_string()),
+            video_codec: Some("copy".to_string()),
+            ..job.clone()
+        };
+        assert!(crate::job::build_visual_args(&burn).is_err());
+
+        let preset = JobPayload {
+            mode: Some("preset".to_string()),
+            preset: Some("h264".to_string()),
+            ..job.clone()
+        };
+        assert_eq!(crate::job::build_ffmpeg_args(&preset).unwrap_err(), "字幕需要使用 visual 模式");
+
+        let mapped = JobPayload {
+            map_field: Some("0".to_string()),
+            ..job.clone()
+        };
+        assert_eq!(
+            crate::job::build_visual_args(&mapped).unwrap_err(),
+            "subtitleLanguage and subtitleDefault cannot be combined with map"
+        );
+        let mapped_plain = JobPayload {
+            subtitle_language: None,
+            subtitle_default: None,
+            ..mapped
+        };
+        assert!(crate::job::build_visual_args(&mapped_plain)
+            .expect("build failed")
+            .join(" ")
+            .ends_with("-map 0 -map 1:0 -c:s mov_text out.mp4"));
+
+        let trimmed_soft = JobPayload {
+            start_time: Some("60".to_string()),
+            duration: Some("30".to_string()),
+            ..job.clone()
+        };
+        let args = crate::job::build_visual_args(&trimmed_soft).expect("build failed").join(" ");
+        assert!(args.starts_with("-y -ss 60 -t 30 -i in.mov -ss 60 -t 30 -i talk.srt -an"));
+
+        let trimmed_burn = JobPayload {
+            subtitle_mode: Some("burn".to_string()),
+            start_time: Some("1:30".to_string()),
+            seek_mode: Some("hybrid".to_string()),
+            ..job
+        };
+        let args = crate::job::build_visual_args(&trimmed_burn).expect("build failed").join(" ");
+        assert!(args.starts_with("-y -ss 80 -i in.mov -ss 10 -an"));
+        assert!(args.contains("-vf setpts=PTS+80/TB,subtitles=filename=talk.srt,setpts=PTS-80/TB"));
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Placeholders resolved from the input/output paths and the current date.
+pub const PATH_PLACEHOLDERS: &[&str] = &["input", "output", "stem", "dir", "ext", "date"];
+/// Placeholders that need an ffprobe run on the input.
+pub const MEDIA_PLACEHOLDERS: &[&str] = &["duration", "width", "height"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Placeholder(String),
+    Env(String),
+    Home,
+}
+
+/// A raw-args token split into literal text, `{name}` placeholders, `$VAR` references and a leading `~`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+fn is_name_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+pub fn is_valid_variable_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_') && name.chars().all(is_name_char)
+}
+
+fn push_text(segments: &mut Vec<Segment>, ch: char) {
+    if let Some(Segment::Text(text)) = segments.last_mut() {
+        text.push(ch);
+    } else {
+        segments.push(Segment::Text(ch.to_string()));
+    }
+}
+
+impl Template {
+    /// `{{` / `}}` are literal braces and `$$` a literal dollar; a `$` not followed by a name stays as is.
+    pub fn parse(token: &str) -> Result<Template, String> {
+        let mut segments = Vec::new();
+        let mut chars = token.chars().peekable();
+
+        if token == "~" || token.starts_with("~/") || token.starts_with("~\\") {
+            chars.next();
+            segments.push(Segment::Home);
+        }
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    push_text(&mut segments, '{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    push_text(&mut segments, '}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(next) => name.push(next),
+                            None => return Err(format!("Unclosed placeholder in raw args: {token} (use {{{{ for a literal brace)")),
+                        }
+                    }
+
+                    if !is_valid_variable_name(&name) {
+                        return Err(format!("Invalid placeholder {{{name}}} in raw args (use {{{{ for a literal brace)"));
+                    }
+                    segments.push(Segment::Placeholder(name));
+                }
+                '$' if chars.peek() == Some(&'$') => {
+                    chars.next();
+                    push_text(&mut segments, '$');
+                }
+                '$' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(next) => name.push(next),
+                            None => return Err(format!("Unclosed ${{...}} in raw args: {token}")),
+                        }
+                    }
+
+                    if !is_valid_variable_name(&name) {
+                        return Err(format!("Invalid environment variable ${{{name}}} in raw args"));
+                    }
+                    segments.push(Segment::Env(name));
+                }
+                '$' if chars.peek().is_some_and(|next| next.is_ascii_alphabetic() || *next == '_') => {
+                    let mut name = String::new();
+                    while let Some(next) = chars.peek().copied().filter(|next| is_name_char(*next)) {
+                        name.push(next);
+                        chars.next();
+                    }
+                    segments.push(Segment::Env(name));
+                }
+                _ => push_text(&mut segments, ch),
+            }
+        }
+
+        Ok(Template { segments })
+    }
+
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Placeholder(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Expands the token; every placeholder must be in `values` and every variable must be set.
+    pub fn render(&self, values: &BTreeMap<String, String>, env: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+        let mut rendered = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => rendered.push_str(text),
+                Segment::Placeholder(name) => {
+                    let value = values.get(name).ok_or_else(|| format!("Unknown placeholder {{{name}}} in raw args"))?;
+                    rendered.push_str(value);
+                }
+                Segment::Env(name) => {
+                    let value = env(name).ok_or_else(|| format!("Environment variable ${name} is not set"))?;
+                    rendered.push_str(&value);
+                }
+                Segment::Home => {
+                    let home = env("HOME")
+                        .or_else(|| env("USERPROFILE"))
+                        .ok_or_else(|| "Cannot expand ~: HOME is not set".to_string())?;
+                    rendered.push_str(&home);
+                }
+            }
+        }
+
+        Ok(rendered)
+    }
+}
+
+/// Parses every token and returns the templates plus the set of placeholder names they use.
+pub fn parse_templates(tokens: &[String]) -> Result<(Vec<Template>, BTreeSet<String>), String> {
+    let templates = tokens.iter().map(|token| Template::parse(token)).collect::<Result<Vec<_>, _>>()?;
+    let used = templates
+        .iter()
+        .flat_map(|template| template.placeholders())
+        .map(str::to_string)
+        .collect();
+    Ok((templates, used))
+}
+
+/// Adds `{stem}`, `{dir}` and `{ext}` for the input path; `{ext}` has no leading dot.
+pub fn insert_path_values(values: &mut BTreeMap<String, String>, input_path: &str) {
+    let source = Path::new(input_path);
+    let part = |value: Option<&std::ffi::OsStr>| value.map(|text| text.to_string_lossy().to_string()).unwrap_or_default();
+
+    values.insert("stem".to_string(), part(source.file_stem()));
+    values.insert("ext".to_string(), part(source.extension()));
+    values.insert("dir".to_string(), part(source.parent().map(Path::as_os_str)));
+}
+
+/// Formats seconds without trailing zeros, e.g. `12.5` or `90`.
+pub fn format_seconds(value: f64) -> String {
+    let text = format!("{value:.3}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(unix)]
+fn utc_offset_sec(timestamp: i64) -> i64 {
+    let time = timestamp as libc::time_t;
+    let mut local: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut local) }.is_null() {
+        return 0;
+    }
+
+    local.tm_gmtoff
+}
+
+#[cfg(not(unix))]
+fn utc_offset_sec(_timestamp: i64) -> i64 {
+    0
+}
+
+/// Today's local date as `YYYY-MM-DD` (UTC where the offset is unavailable).
+pub fn current_date() -> String {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    let (year, month, day) = civil_from_days((timestamp + utc_offset_sec(timestamp)).div_euclid(86_400));
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".to_string()),
+            "CLIP" => Some("intro".to_string()),
+            _ => None,
+        }
+    }
+
+    fn render(token: &str, values: &BTreeMap<String, String>) -> Result<String, String> {
+        Template::parse(token)?.render(values, &env)
+    }
+
+    #[test]
+    fn template_expands_placeholders_variables_and_home() {
+        let mut values = BTreeMap::new();
+        values.insert("input".to_string(), "/media/clip.mov".to_string());
+        insert_path_values(&mut values, "/media/clip.mov");
+
+        assert_eq!(render("~/out/{stem}_$CLIP.{ext}", &values).unwrap(), "/home/me/out/clip_intro.mov");
+        assert_eq!(render("${HOME}/x", &values).unwrap(), "/home/me/x");
+        assert_eq!(render("{dir}", &values).unwrap(), "/media");
+        assert_eq!(render("a~b$", &values).unwrap(), "a~b$");
+    }
+
+    #[test]
+    fn template_escapes_braces_and_dollars() {
+        let values = BTreeMap::new();
+        assert_eq!(render("{{x}} costs $$5", &values).unwrap(), "{x} costs $5");
+    }
+
+    #[test]
+    fn template_reports_unknown_and_malformed_placeholders() {
+        let values = BTreeMap::new();
+        assert_eq!(render("{nope}", &values).unwrap_err(), "Unknown placeholder {nope} in raw args");
+        assert!(render("{unclosed", &values).unwrap_err().contains("Unclosed placeholder"));
+        assert!(render("{a b}", &values).unwrap_err().contains("Invalid placeholder"));
+        assert_eq!(render("$MISSING", &values).unwrap_err(), "Environment variable $MISSING is not set");
+    }
+
+    #[test]
+    fn civil_from_days_handles_epoch_and_leap_day() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+}
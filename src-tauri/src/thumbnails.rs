@@ -0,0 +1,282 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cropdetect::sample_points;
+use crate::job::{has_text, text_from_option};
+use crate::probe::{probe_media, resolve_executable_path, run_command};
+use crate::template::format_seconds;
+use crate::timecode::format_clock;
+use crate::video_filter::VideoFilterNode;
+
+const DEFAULT_COUNT: f64 = 8.0;
+const DEFAULT_WIDTH: f64 = 320.0;
+const DEFAULT_COLUMNS: f64 = 4.0;
+const SHEET_PADDING: u64 = 4;
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailPayload {
+    pub input_path: Option<String>,
+    pub ffmpeg_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    /// Evenly spaced frames over the input; ignored when `timestamps` is set.
+    pub count: Option<f64>,
+    /// Explicit frame times in seconds.
+    pub timestamps: Option<Vec<f64>>,
+    pub width: Option<f64>,
+    /// Also tile the thumbnails into one image with timestamp labels.
+    pub contact_sheet: Option<bool>,
+    pub columns: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Thumbnail {
+    pub time_sec: f64,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailResult {
+    pub cache_dir: String,
+    pub thumbnails: Vec<Thumbnail>,
+    pub contact_sheet: Option<String>,
+}
+
+fn checked_integer(value: Option<f64>, default: f64, min: f64, max: f64, field: &str) -> Result<u64, String> {
+    let value = value.unwrap_or(default);
+    if !value.is_finite() || value < min || value > max || value.fract() != 0.0 {
+        return Err(format!("{field} must be an integer between {min} and {max}"));
+    }
+
+    Ok(value as u64)
+}
+
+/// FNV-1a; stable across Rust releases, unlike `DefaultHasher`, so cache names survive upgrades.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Cache folder for one version of the input: editing or replacing the file changes its
+/// mtime or size, and with it the folder.
+pub fn cache_key(input_path: &str) -> Result<String, String> {
+    let metadata = fs::metadata(input_path).map_err(|error| format!("无法读取输入文件信息：{error}"))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    let absolute = fs::canonicalize(input_path).unwrap_or_else(|_| PathBuf::from(input_path));
+
+    let identity = format!("{}|{modified}|{}", absolute.to_string_lossy(), metadata.len());
+    Ok(format!("{:016x}", fnv1a(identity.as_bytes())))
+}
+
+fn thumbnail_file_name(time_sec: f64, width: u64) -> String {
+    format!("thumb_w{width}_{}ms.jpg", (time_sec * 1000.0).round() as u64)
+}
+
+/// Input seeking (`-ss` before `-i`) jumps to the nearest keyframe and decodes only from there.
+pub fn thumbnail_args(input_path: &str, time_sec: f64, width: u64, output_path: &Path) -> Vec<String> {
+    vec![
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        format_seconds(time_sec),
+        "-i".to_string(),
+        input_path.to_string(),
+        "-map".to_string(),
+        "0:v:0".to_string(),
+        "-frames:v".to_string(),
+        "1".to_string(),
+        "-vf".to_string(),
+        format!("scale={width}:-2"),
+        "-q:v".to_string(),
+        "3".to_string(),
+        "-y".to_string(),
+        output_path.to_string_lossy().to_string(),
+    ]
+}
+
+/// Labels each cached thumbnail, concatenates them into a frame sequence and tiles it.
+pub fn contact_sheet_args(thumbnails: &[Thumbnail], columns: u64, output_path: &Path) -> Result<Vec<String>, String> {
+    let columns = columns.min(thumbnails.len() as u64).max(1);
+    let rows = (thumbnails.len() as u64).div_ceil(columns);
+
+    let mut args = vec!["-hide_banner".to_string(), "-loglevel".to_string(), "error".to_string()];
+    let mut graph = Vec::new();
+    let mut labelled = String::new();
+    for (index, thumbnail) in thumbnails.iter().enumerate() {
+        args.push("-i".to_string());
+        args.push(thumbnail.path.clone());
+
+        let label = VideoFilterNode::Drawtext {
+            text: format_clock(thumbnail.time_sec),
+            x: Some("8".to_string()),
+            y: Some("h-th-8".to_string()),
+            font_size: Some(18.0),
+            font_color: Some("white".to_string()),
+            font_file: None,
+            box_color: Some("black@0.6".to_string()),
+        }
+        .to_filter()?;
+        graph.push(format!("[{index}:v]{label}[t{index}]"));
+        labelled.push_str(&format!("[t{index}]"));
+    }
+
+    graph.push(format!(
+        "{labelled}concat=n={}:v=1:a=0,tile={columns}x{rows}:padding={SHEET_PADDING}:margin={SHEET_PADDING}",
+        thumbnails.len()
+    ));
+    args.extend([
+        "-filter_complex".to_string(),
+        graph.join(";"),
+        "-frames:v".to_string(),
+        "1".to_string(),
+        "-q:v".to_string(),
+        "3".to_string(),
+        "-y".to_string(),
+        output_path.to_string_lossy().to_string(),
+    ]);
+
+    Ok(args)
+}
+
+fn requested_timestamps(payload: &ThumbnailPayload, input_path: &str) -> Result<Vec<f64>, String> {
+    if let Some(timestamps) = payload.timestamps.as_ref().filter(|list| !list.is_empty()) {
+        if timestamps.iter().any(|time| !time.is_finite() || *time < 0.0) {
+            return Err("timestamps must be non-negative numbers".to_string());
+        }
+        return Ok(timestamps.clone());
+    }
+
+    let count = checked_integer(payload.count, DEFAULT_COUNT, 1.0, 100.0, "count")?;
+    let configured_ffprobe = text_from_option(&payload.ffprobe_path);
+    let ffprobe_path = resolve_executable_path(Some(configured_ffprobe.as_str()), "ffprobe");
+    let duration = probe_media(&ffprobe_path, &configured_ffprobe, input_path)?
+        .duration_sec
+        .filter(|duration| *duration > 0.0)
+        .ok_or_else(|| "无法读取输入时长，请改用指定时间点".to_string())?;
+
+    Ok(sample_points(duration, count as usize, 0.0))
+}
+
+/// Extracts the thumbnails into `cache_root/<cache key>/`, reusing files already there.
+pub fn generate_thumbnails(payload: &ThumbnailPayload, cache_root: &Path) -> Result<ThumbnailResult, String> {
+    let input_path = text_from_option(&payload.input_path);
+    if !has_text(&input_path) {
+        return Err("inputPath is required".to_string());
+    }
+
+    let width = checked_integer(payload.width, DEFAULT_WIDTH, 16.0, 3840.0, "width")?;
+    let columns = checked_integer(payload.columns, DEFAULT_COLUMNS, 1.0, 20.0, "columns")?;
+    let timestamps = requested_timestamps(payload, &input_path)?;
+
+    let cache_dir = cache_root.join(cache_key(&input_path)?);
+    fs::create_dir_all(&cache_dir).map_err(|error| format!("创建缩略图缓存目录失败：{error}"))?;
+
+    let configured_ffmpeg = text_from_option(&payload.ffmpeg_path);
+    let ffmpeg_path = resolve_executable_path(Some(configured_ffmpeg.as_str()), "ffmpeg");
+    let mut thumbnails = Vec::new();
+    for time_sec in timestamps {
+        let path = cache_dir.join(thumbnail_file_name(time_sec, width));
+        if !path.is_file() {
+            let args = thumbnail_args(&input_path, time_sec, width, &path);
+            run_command(&ffmpeg_path, &args, "ffmpeg", &configured_ffmpeg)?;
+            if !path.is_file() {
+                return Err(format!(
+                    "{} 处没有可用的画面，可能超出了输入时长",
+                    format_clock(time_sec)
+                ));
+            }
+        }
+
+        thumbnails.push(Thumbnail {
+            time_sec,
+            path: path.to_string_lossy().to_string(),
+        });
+    }
+
+    let contact_sheet = if payload.contact_sheet.unwrap_or(false) {
+        let times: Vec<String> = thumbnails
+            .iter()
+            .map(|thumbnail| format_seconds(thumbnail.time_sec))
+            .collect();
+        let sheet_name = format!(
+            "sheet_w{width}_c{columns}_{:016x}.jpg",
+            fnv1a(times.join(",").as_bytes())
+        );
+        let sheet_path = cache_dir.join(sheet_name);
+        if !sheet_path.is_file() {
+            let args = contact_sheet_args(&thumbnails, columns, &sheet_path)?;
+            run_command(&ffmpeg_path, &args, "ffmpeg", &configured_ffmpeg)?;
+        }
+        Some(sheet_path.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    Ok(ThumbnailResult {
+        cache_dir: cache_dir.to_string_lossy().to_string(),
+        thumbnails,
+        contact_sheet,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_follows_file_contents() {
+        let dir = std::env::temp_dir().join(format!("ffmpeg-gui-thumbnails-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("clip.mp4");
+        fs::write(&path, b"first").unwrap();
+        let path_text = path.to_string_lossy().to_string();
+
+        let first = cache_key(&path_text).unwrap();
+        assert_eq!(first, cache_key(&path_text).unwrap());
+        fs::write(&path, b"second version").unwrap();
+        assert_ne!(first, cache_key(&path_text).unwrap());
+        assert!(cache_key(&dir.join("missing.mp4").to_string_lossy()).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn contact_sheet_labels_and_tiles_thumbnails() {
+        let thumbnails: Vec<Thumbnail> = [5.0, 3725.5, 7200.0]
+            .iter()
+            .map(|time_sec| Thumbnail {
+                time_sec: *time_sec,
+                path: format!("/cache/{}.jpg", *time_sec as u64),
+            })
+            .collect();
+
+        let args = contact_sheet_args(&thumbnails, 4, Path::new("/cache/sheet.jpg")).unwrap();
+        let graph = &args[args.iter().position(|arg| arg == "-filter_complex").unwrap() + 1];
+        assert!(graph.starts_with(r"[0:v]drawtext=text=0\\:00\\:05:"));
+        assert!(graph.contains(r"text=1\\:02\\:05:"));
+        assert!(graph.ends_with("[t0][t1][t2]concat=n=3:v=1:a=0,tile=3x1:padding=4:margin=4"));
+        assert_eq!(args.iter().filter(|arg| *arg == "-i").count(), 3);
+    }
+
+    #[test]
+    fn thumbnail_args_seek_before_input() {
+        let args = thumbnail_args("in.mkv", 12.25, 320, Path::new("/cache/t.jpg")).join(" ");
+        assert_eq!(
+            args,
+            "-hide_banner -loglevel error -ss 12.25 -i in.mkv -map 0:v:0 -frames:v 1 -vf scale=320:-2 -q:v 3 -y /cache/t.jpg"
+        );
+        assert_eq!(thumbnail_file_name(12.25, 320), "thumb_w320_12250ms.jpg");
+    }
+}
@@ -0,0 +1,297 @@
+//! Time values typed by users or printed by ffmpeg: plain seconds, `M:S`, `H:M:S`, unit
+//! strings like `1h2m3s`, and SMPTE `HH:MM:SS:FF` / drop-frame `HH:MM:SS;FF`.
+
+/// True when the text counts frames, so it can only be converted with the input's frame rate.
+pub fn needs_frame_rate(text: &str) -> bool {
+    text.contains(';') || text.matches(':').count() == 3
+}
+
+fn invalid(text: &str, reason: &str) -> String {
+    format!("Invalid time \"{text}\": {reason}")
+}
+
+/// Digits with an optional fraction; no sign, exponent, `inf` or `NaN` like `f64::from_str` allows.
+fn parse_unsigned(part: &str) -> Option<f64> {
+    let (whole, fraction) = part.split_once('.').unwrap_or((part, ""));
+    let digits = |text: &str| text.chars().all(|ch| ch.is_ascii_digit());
+    if whole.is_empty() && fraction.is_empty() || !digits(whole) || !digits(fraction) {
+        return None;
+    }
+    part.parse::<f64>().ok()
+}
+
+fn parse_integer(part: &str) -> Option<u64> {
+    if part.is_empty() || !part.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    part.parse::<u64>().ok()
+}
+
+/// `S`, `M:S` or `H:M:S`; only the last field may have a fraction.
+fn parse_clock(text: &str, body: &str) -> Result<f64, String> {
+    let parts: Vec<&str> = body.split(':').collect();
+    let (last, leading) = parts.split_last().ok_or_else(|| invalid(text, "empty value"))?;
+    if leading.len() > 2 {
+        return Err(invalid(text, "expected S, M:S or H:M:S"));
+    }
+
+    let seconds = parse_unsigned(last).ok_or_else(|| invalid(text, "seconds must be a number"))?;
+    let mut fields = Vec::new();
+    for part in leading {
+        fields.push(parse_integer(part).ok_or_else(|| invalid(text, "hours and minutes must be whole numbers"))?);
+    }
+    if !leading.is_empty() && seconds >= 60.0 {
+        return Err(invalid(text, "seconds must be below 60"));
+    }
+    // Minutes may run past 59 in `M:S` (`90:00`), as ffmpeg allows, but not under an hour field.
+    if let [_, minutes] = fields[..] {
+        if minutes >= 60 {
+            return Err(invalid(text, "minutes must be below 60"));
+        }
+    }
+
+    let whole_minutes = fields.iter().fold(0, |total, field| total * 60 + field);
+    Ok(whole_minutes as f64 * 60.0 + seconds)
+}
+
+/// `1h2m3s`, `1h 30m`, `90s`, `1.5h`, `250ms`: each unit once, largest first.
+fn parse_units(text: &str, body: &str) -> Result<f64, String> {
+    const UNITS: [(&str, f64); 4] = [("h", 3600.0), ("m", 60.0), ("s", 1.0), ("ms", 0.001)];
+
+    let compact: String = body
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let mut rest = compact.as_str();
+    let mut next_unit = 0;
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+            .ok_or_else(|| invalid(text, "missing unit after the last number"))?;
+        let value =
+            parse_unsigned(&rest[..number_len]).ok_or_else(|| invalid(text, "expected a number before each unit"))?;
+        rest = &rest[number_len..];
+
+        let unit_len = rest
+            .find(|ch: char| ch.is_ascii_digit() || ch == '.')
+            .unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        let index = UNITS
+            .iter()
+            .position(|(name, _)| *name == unit)
+            .ok_or_else(|| invalid(text, "units must be h, m, s or ms"))?;
+        if index < next_unit {
+            return Err(invalid(text, "units must appear once each, largest first"));
+        }
+        total += value * UNITS[index].1;
+        next_unit = index + 1;
+        rest = &rest[unit_len..];
+    }
+
+    Ok(total)
+}
+
+/// `HH:MM:SS:FF`, or drop-frame `HH:MM:SS;FF` (any `;` separator marks drop-frame).
+fn parse_smpte(text: &str, body: &str, fps: Option<f64>) -> Result<f64, String> {
+    let fps = fps
+        .filter(|fps| fps.is_finite() && *fps > 0.0)
+        .ok_or_else(|| invalid(text, "frame timecodes need the input's frame rate"))?;
+    let drop_frame = body.contains(';');
+    let fields: Vec<u64> = body
+        .split([':', ';'])
+        .map(parse_integer)
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid(text, "timecode fields must be whole numbers"))?;
+    let [hours, minutes, seconds, frames] = fields[..] else {
+        return Err(invalid(text, "expected HH:MM:SS:FF"));
+    };
+
+    // Timecode counts whole frames per second: 30 for 29.97, 24 for 23.976.
+    let nominal = fps.round().max(1.0) as u64;
+    if minutes >= 60 || seconds >= 60 {
+        return Err(invalid(text, "minutes and seconds must be below 60"));
+    }
+    if frames >= nominal {
+        return Err(invalid(text, &format!("frames must be below {nominal}")));
+    }
+
+    let mut frame_number = (hours * 3600 + minutes * 60 + seconds) * nominal + frames;
+    if drop_frame {
+        if !nominal.is_multiple_of(30) || (fps - fps.round()).abs() < 0.001 {
+            return Err(invalid(text, "drop-frame timecode only exists for 29.97 and 59.94 fps"));
+        }
+        // Frame numbers 0 and 1 (0–3 at 59.94) are skipped at every minute except each tenth.
+        let dropped = nominal / 15;
+        let total_minutes = hours * 60 + minutes;
+        if seconds == 0 && frames < dropped && !minutes.is_multiple_of(10) {
+            return Err(invalid(text, "this frame number is skipped in drop-frame timecode"));
+        }
+        frame_number -= dropped * (total_minutes - total_minutes / 10);
+    }
+
+    Ok(frame_number as f64 / fps)
+}
+
+/// Parses any supported form to seconds. A leading `-` negates the whole value; what a negative
+/// time means is up to the caller. `fps` is only needed for frame timecodes.
+pub fn parse_timecode(text: &str, fps: Option<f64>) -> Result<f64, String> {
+    let trimmed = text.trim();
+    let (sign, body) = match trimmed.strip_prefix('-') {
+        Some(rest) => (-1.0, rest.trim_start()),
+        None => (1.0, trimmed.strip_prefix('+').unwrap_or(trimmed).trim_start()),
+    };
+    if body.is_empty() {
+        return Err(invalid(text, "empty value"));
+    }
+
+    let seconds = if needs_frame_rate(body) {
+        parse_smpte(text, body, fps)?
+    } else if body.chars().any(|ch| ch.is_ascii_alphabetic()) {
+        parse_units(text, body)?
+    } else {
+        parse_clock(text, body)?
+    };
+
+    Ok(sign * seconds)
+}
+
+/// `H:MM:SS.mmm`, for durations and positions shown in events and logs.
+pub fn format_duration(seconds: f64) -> String {
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    // Rounded to whole milliseconds first, so 59.9996 carries into the next minute.
+    let total_millis = (seconds.abs() * 1000.0).round() as u64;
+    let (whole, millis) = (total_millis / 1000, total_millis % 1000);
+    format!(
+        "{sign}{}:{:02}:{:02}.{millis:03}",
+        whole / 3600,
+        whole / 60 % 60,
+        whole % 60
+    )
+}
+
+/// `H:MM:SS`, truncated to the second, for labels.
+pub fn format_clock(seconds: f64) -> String {
+    let total = seconds.max(0.0).floor() as u64;
+    format!("{}:{:02}:{:02}", total / 3600, total / 60 % 60, total % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> f64 {
+        parse_timecode(text, None).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn parses_seconds_and_clock_forms() {
+        assert_eq!(parse("90"), 90.0);
+        assert_eq!(parse(" 12.5 "), 12.5);
+        assert_eq!(parse(".25"), 0.25);
+        assert_eq!(parse("3."), 3.0);
+        assert_eq!(parse("1:30"), 90.0);
+        assert_eq!(parse("90:00"), 5400.0);
+        assert_eq!(parse("01:02:03"), 3723.0);
+        assert_eq!(parse("1:02:03.250"), 3723.25);
+        assert_eq!(parse("100:00:00"), 360_000.0);
+        assert_eq!(parse("+5"), 5.0);
+    }
+
+    #[test]
+    fn parses_unit_strings() {
+        assert_eq!(parse("1h2m3s"), 3723.0);
+        assert_eq!(parse("1h 30m"), 5400.0);
+        assert_eq!(parse("90s"), 90.0);
+        assert_eq!(parse("1.5h"), 5400.0);
+        assert_eq!(parse("2m30.5s"), 150.5);
+        assert_close(parse("250ms"), 0.25);
+        assert_close(parse("1s500ms"), 1.5);
+        assert_eq!(parse("2H3M"), 7380.0);
+    }
+
+    #[test]
+    fn negative_values_negate_every_form() {
+        assert_eq!(parse("-5"), -5.0);
+        assert_eq!(parse("-1:30"), -90.0);
+        assert_eq!(parse("- 1m"), -60.0);
+        assert_eq!(parse_timecode("-00:00:01:12", Some(24.0)).unwrap(), -1.5);
+    }
+
+    #[test]
+    fn parses_smpte_frames_with_frame_rate() {
+        assert_eq!(parse_timecode("00:00:01:12", Some(24.0)).unwrap(), 1.5);
+        assert_eq!(parse_timecode("01:00:00:00", Some(25.0)).unwrap(), 3600.0);
+        // Non-drop timecode at 29.97 runs slow: an hour of timecode is 108000 real frames.
+        assert_close(
+            parse_timecode("01:00:00:00", Some(30000.0 / 1001.0)).unwrap(),
+            108_000.0 * 1001.0 / 30000.0,
+        );
+        assert!(needs_frame_rate("00:00:01:12"));
+        assert!(needs_frame_rate("00:00:01;12"));
+        assert!(!needs_frame_rate("00:01:12"));
+    }
+
+    #[test]
+    fn drop_frame_timecode_matches_real_time() {
+        let ntsc = Some(30000.0 / 1001.0);
+        // Drop-frame keeps an hour of timecode within a few ms of an hour of real time.
+        let hour = parse_timecode("01:00:00;00", ntsc).unwrap();
+        assert_close(hour, 107_892.0 * 1001.0 / 30000.0);
+        assert!((hour - 3600.0).abs() < 0.004);
+        // 00:01:00;02 is the first frame after the skipped 00:01:00;00 and ;01.
+        assert_close(parse_timecode("00:01:00;02", ntsc).unwrap(), 1800.0 * 1001.0 / 30000.0);
+        assert_close(
+            parse_timecode("00:10:00;00", ntsc).unwrap(),
+            17_982.0 * 1001.0 / 30000.0,
+        );
+        assert_close(
+            parse_timecode("00:01:00;04", Some(60000.0 / 1001.0)).unwrap(),
+            3600.0 * 1001.0 / 60000.0,
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        for text in [
+            "",
+            "-",
+            "abc",
+            "1:2:3:4:5",
+            "1:60",
+            "1:60:00",
+            "1:00:60",
+            "1e3",
+            "inf",
+            "NaN",
+            "1.5:00",
+            "1:-5",
+            "5m1h",
+            "1h1h",
+            "10x",
+            "1h30",
+            "m5",
+        ] {
+            assert!(parse_timecode(text, None).is_err(), "{text} should be rejected");
+        }
+        assert!(parse_timecode("00:00:01:12", None).unwrap_err().contains("frame rate"));
+        assert!(parse_timecode("00:00:01:24", Some(24.0)).is_err());
+        assert!(parse_timecode("00:00:01;00", Some(25.0)).is_err());
+        assert!(parse_timecode("00:01:00;01", Some(30000.0 / 1001.0)).is_err());
+    }
+
+    #[test]
+    fn formats_durations_and_clock_labels() {
+        assert_eq!(format_duration(3723.25), "1:02:03.250");
+        assert_eq!(format_duration(59.9996), "0:01:00.000");
+        assert_eq!(format_duration(-1.5), "-0:00:01.500");
+        assert_eq!(format_clock(3725.9), "1:02:05");
+        assert_eq!(format_clock(-3.0), "0:00:00");
+        assert_eq!(parse(&format_duration(3723.25)), 3723.25);
+    }
+}
@@ -0,0 +1,396 @@
+use serde::{Deserialize, Serialize};
+
+use crate::job::has_text;
+
+/// One typed step of the visual-mode `-vf` chain. Nodes run in list order.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum VideoFilterNode {
+    Crop {
+        width: f64,
+        height: f64,
+        x: Option<f64>,
+        y: Option<f64>,
+    },
+    Scale {
+        width: Option<f64>,
+        height: Option<f64>,
+    },
+    /// Centred when `x` / `y` are omitted.
+    Pad {
+        width: f64,
+        height: f64,
+        x: Option<f64>,
+        y: Option<f64>,
+        color: Option<String>,
+    },
+    /// Quarter turns use lossless `transpose`; other angles use `rotate` with a grown canvas.
+    Rotate {
+        degrees: f64,
+    },
+    Transpose {
+        direction: String,
+    },
+    Flip {
+        horizontal: Option<bool>,
+        vertical: Option<bool>,
+    },
+    Deinterlace {
+        /// `yadif` (default) or `bwdif`.
+        method: Option<String>,
+        /// Outputs one frame per field, doubling the frame rate.
+        send_field: Option<bool>,
+    },
+    Denoise {
+        /// `hqdn3d` (default) or `nlmeans`.
+        method: Option<String>,
+        strength: Option<f64>,
+    },
+    Sharpen {
+        amount: Option<f64>,
+    },
+    Eq {
+        brightness: Option<f64>,
+        contrast: Option<f64>,
+        saturation: Option<f64>,
+        gamma: Option<f64>,
+    },
+    Fade {
+        direction: String,
+        start_sec: Option<f64>,
+        duration_sec: f64,
+        color: Option<String>,
+    },
+    Drawtext {
+        text: String,
+        x: Option<String>,
+        y: Option<String>,
+        font_size: Option<f64>,
+        font_color: Option<String>,
+        font_file: Option<String>,
+        box_color: Option<String>,
+    },
+    Setsar {
+        ratio: Option<String>,
+    },
+    Format {
+        pixel_format: String,
+    },
+    /// Free-text filters, inserted as written.
+    Raw {
+        filter: String,
+    },
+}
+
+/// Escapes a filter option value for both levels ffmpeg parses it at: first the option
+/// parser (`\`, `'`, `:`), then the filtergraph parser (`\`, `'`, `[`, `]`, `,`, `;`).
+pub fn escape_filter_value(value: &str) -> String {
+    let escape = |text: &str, special: &[char]| {
+        let mut escaped = String::with_capacity(text.len());
+        for ch in text.chars() {
+            if special.contains(&ch) {
+                escaped.push('\\');
+            }
+            escaped.push(ch);
+        }
+        escaped
+    };
+
+    let option_level = escape(value, &['\\', '\'', ':']);
+    escape(&option_level, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+/// drawtext expands `\x` to `x` and `%{...}` sequences in its text before the usual escaping.
+fn escape_drawtext_text(text: &str) -> String {
+    escape_filter_value(&text.replace('\\', "\\\\").replace('%', "\\%"))
+}
+
+fn number(value: f64) -> String {
+    let text = format!("{value:.4}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn checked(value: f64, min: f64, max: f64, field: &str) -> Result<f64, String> {
+    if !value.is_finite() || value < min || value > max {
+        return Err(format!("{field} must be between {min} and {max}"));
+    }
+
+    Ok(value)
+}
+
+fn dimension(value: f64, field: &str) -> Result<i64, String> {
+    if !value.is_finite() || value < 1.0 || value.fract() != 0.0 {
+        return Err(format!("{field} must be a positive integer"));
+    }
+
+    Ok(value as i64)
+}
+
+fn offset(value: Option<f64>, field: &str) -> Result<Option<i64>, String> {
+    match value {
+        None => Ok(None),
+        Some(number) if number.is_finite() && number >= 0.0 && number.fract() == 0.0 => Ok(Some(number as i64)),
+        Some(_) => Err(format!("{field} must be a non-negative integer")),
+    }
+}
+
+/// Colors and expressions are passed through escaping; only their presence is checked here.
+fn text_value(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|text| has_text(text)).map(escape_filter_value)
+}
+
+impl VideoFilterNode {
+    /// Renders the node as one or more comma-joined filters, already escaped for `-vf`.
+    pub fn to_filter(&self) -> Result<String, String> {
+        self.to_filter_at(0.0)
+    }
+
+    /// Like `to_filter`, with fade times moved by `time_offset_sec`: where the output starts in
+    /// the graph's timeline when the trim is cut after the filters.
+    pub fn to_filter_at(&self, time_offset_sec: f64) -> Result<String, String> {
+        let filter = match self {
+            VideoFilterNode::Crop { width, height, x, y } => {
+                let mut filter = format!("crop={}:{}", dimension(*width, "crop.width")?, dimension(*height, "crop.height")?);
+                match (offset(*x, "crop.x")?, offset(*y, "crop.y")?) {
+                    (None, None) => {}
+                    (x, y) => filter.push_str(&format!(":{}:{}", x.unwrap_or(0), y.unwrap_or(0))),
+                }
+                filter
+            }
+            VideoFilterNode::Scale { width, height } => {
+                let width = width.map(|value| dimension(value, "scale.width")).transpose()?;
+                let height = height.map(|value| dimension(value, "scale.height")).transpose()?;
+                if width.is_none() && height.is_none() {
+                    return Err("scale needs width or height".to_string());
+                }
+                format!("scale={}:{}:flags=lanczos", width.unwrap_or(-1), height.unwrap_or(-1))
+            }
+            VideoFilterNode::Pad { width, height, x, y, color } => {
+                let x = offset(*x, "pad.x")?.map_or("(ow-iw)/2".to_string(), |value| value.to_string());
+                let y = offset(*y, "pad.y")?.map_or("(oh-ih)/2".to_string(), |value| value.to_string());
+                let mut filter = format!(
+                    "pad={}:{}:{}:{}",
+                    dimension(*width, "pad.width")?,
+                    dimension(*height, "pad.height")?,
+                    escape_filter_value(&x),
+                    escape_filter_value(&y)
+                );
+                if let Some(color) = text_value(color) {
+                    filter.push_str(&format!(":color={color}"));
+                }
+                filter
+            }
+            VideoFilterNode::Rotate { degrees } => {
+                let degrees = checked(*degrees, -360.0, 360.0, "rotate.degrees")?;
+                match degrees.rem_euclid(360.0) {
+                    0.0 => return Err("rotate.degrees must not be a full turn".to_string()),
+                    90.0 => "transpose=clock".to_string(),
+                    180.0 => "hflip,vflip".to_string(),
+                    270.0 => "transpose=cclock".to_string(),
+                    _ => {
+                        let radians = number(degrees.to_radians());
+                        format!("rotate={radians}:ow=rotw({radians}):oh=roth({radians})")
+                    }
+                }
+            }
+            VideoFilterNode::Transpose { direction } => match direction.as_str() {
+                "clock" | "cclock" | "clock_flip" | "cclock_flip" => format!("transpose={direction}"),
+                other => return Err(format!("Unsupported transpose direction: {other}")),
+            },
+            VideoFilterNode::Flip { horizontal, vertical } => {
+                match (horizontal.unwrap_or(false), vertical.unwrap_or(false)) {
+                    (true, true) => "hflip,vflip".to_string(),
+                    (true, false) => "hflip".to_string(),
+                    (false, true) => "vflip".to_string(),
+                    (false, false) => return Err("flip needs horizontal or vertical".to_string()),
+                }
+            }
+            VideoFilterNode::Deinterlace { method, send_field } => {
+                let method = match method.as_deref().unwrap_or("yadif") {
+                    "" | "yadif" => "yadif",
+                    "bwdif" => "bwdif",
+                    other => return Err(format!("Unsupported deinterlace method: {other}")),
+                };
+                let mode = if send_field.unwrap_or(false) { "send_field" } else { "send_frame" };
+                format!("{method}=mode={mode}")
+            }
+            VideoFilterNode::Denoise { method, strength } => match method.as_deref().unwrap_or("hqdn3d") {
+                "" | "hqdn3d" => format!(
+                    "hqdn3d=luma_spatial={}",
+                    number(checked(strength.unwrap_or(4.0), 0.0, 100.0, "denoise.strength")?)
+                ),
+                "nlmeans" => format!(
+                    "nlmeans=s={}",
+                    number(checked(strength.unwrap_or(1.0), 1.0, 30.0, "denoise.strength")?)
+                ),
+                other => return Err(format!("Unsupported denoise method: {other}")),
+            },
+            VideoFilterNode::Sharpen { amount } => {
+                let amount = checked(amount.unwrap_or(1.0), -2.0, 5.0, "sharpen.amount")?;
+                format!("unsharp=luma_msize_x=5:luma_msize_y=5:luma_amount={}", number(amount))
+            }
+            VideoFilterNode::Eq {
+                brightness,
+                contrast,
+                saturation,
+                gamma,
+            } => {
+                let mut options = Vec::new();
+                for (name, value, min, max) in [
+                    ("brightness", brightness, -1.0, 1.0),
+                    ("contrast", contrast, -1000.0, 1000.0),
+                    ("saturation", saturation, 0.0, 3.0),
+                    ("gamma", gamma, 0.1, 10.0),
+                ] {
+                    if let Some(value) = value {
+                        options.push(format!("{name}={}", number(checked(*value, min, max, &format!("eq.{name}"))?)));
+                    }
+                }
+                if options.is_empty() {
+                    return Err("eq needs at least one of brightness, contrast, saturation or gamma".to_string());
+                }
+                format!("eq={}", options.join(":"))
+            }
+            VideoFilterNode::Fade {
+                direction,
+                start_sec,
+                duration_sec,
+                color,
+            } => {
+                if direction != "in" && direction != "out" {
+                    return Err(format!("Unsupported fade direction: {direction}"));
+                }
+                let start = checked(start_sec.unwrap_or(0.0), 0.0, f64::MAX, "fade.startSec")?;
+                let duration = checked(*duration_sec, 0.001, f64::MAX, "fade.durationSec")?;
+                let mut filter = format!(
+                    "fade=t={direction}:st={}:d={}",
+                    number(start + time_offset_sec),
+                    number(duration)
+                );
+                if let Some(color) = text_value(color) {
+                    filter.push_str(&format!(":color={color}"));
+                }
+                filter
+            }
+            VideoFilterNode::Drawtext {
+                text,
+                x,
+                y,
+                font_size,
+                font_color,
+                font_file,
+                box_color,
+            } => {
+                if text.is_empty() {
+                    return Err("drawtext.text is required".to_string());
+                }
+                let mut options = vec![format!("text={}", escape_drawtext_text(text))];
+                if let Some(font_file) = text_value(font_file) {
+                    options.push(format!("fontfile={font_file}"));
+                }
+                if let Some(size) = font_size {
+                    options.push(format!("fontsize={}", dimension(*size, "drawtext.fontSize")?));
+                }
+                if let Some(color) = text_value(font_color) {
+                    options.push(format!("fontcolor={color}"));
+                }
+                options.push(format!("x={}", text_value(x).unwrap_or_else(|| "10".to_string())));
+                options.push(format!("y={}", text_value(y).unwrap_or_else(|| "10".to_string())));
+                if let Some(color) = text_value(box_color) {
+                    options.push(format!("box=1:boxcolor={color}:boxborderw=8"));
+                }
+                format!("drawtext={}", options.join(":"))
+            }
+            VideoFilterNode::Setsar { ratio } => {
+                format!("setsar={}", text_value(ratio).unwrap_or_else(|| "1".to_string()))
+            }
+            VideoFilterNode::Format { pixel_format } => {
+                if pixel_format.is_empty() || !pixel_format.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_') {
+                    return Err(format!("Invalid pixel format: {pixel_format}"));
+                }
+                format!("format={pixel_format}")
+            }
+            VideoFilterNode::Raw { filter } => {
+                if !has_text(filter) {
+                    return Err("raw filter is empty".to_string());
+                }
+                filter.trim().to_string()
+            }
+        };
+
+        Ok(filter)
+    }
+}
+
+/// Renders the nodes in order; the error names the position of the node that failed.
+pub fn build_filter_nodes(nodes: &[VideoFilterNode], time_offset_sec: f64) -> Result<Vec<String>, String> {
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            node.to_filter_at(time_offset_sec)
+                .map_err(|error| format!("videoFilters[{index}]: {error}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_filter_value_handles_separators_quotes_and_backslashes() {
+        assert_eq!(escape_filter_value("a:b"), r"a\\:b");
+        assert_eq!(escape_filter_value("a,b"), r"a\,b");
+        assert_eq!(escape_filter_value("it's"), r"it\\\'s");
+        assert_eq!(escape_filter_value(r"C:\Subs\it's, [v2].srt"), r"C\\:\\\\Subs\\\\it\\\'s\, \[v2\].srt");
+    }
+
+    #[test]
+    fn drawtext_escapes_text_for_expansion_and_filtergraph() {
+        let node = VideoFilterNode::Drawtext {
+            text: r"50% off: it's a\b, ok".to_string(),
+            x: Some("(w-tw)/2".to_string()),
+            y: None,
+            font_size: Some(32.0),
+            font_color: Some("white@0.8".to_string()),
+            font_file: None,
+            box_color: None,
+        };
+
+        assert_eq!(
+            node.to_filter().unwrap(),
+            r"drawtext=text=50\\\\% off\\: it\\\'s a\\\\\\\\b\, ok:fontsize=32:fontcolor=white@0.8:x=(w-tw)/2:y=10"
+        );
+    }
+
+    #[test]
+    fn nodes_render_in_order_and_report_invalid_parameters() {
+        let nodes: Vec<VideoFilterNode> = serde_json::from_value(serde_json::json!([
+            { "type": "deinterlace", "method": "bwdif" },
+            { "type": "crop", "width": 1920, "height": 800, "x": 0, "y": 140 },
+            { "type": "rotate", "degrees": 90 },
+            { "type": "eq", "contrast": 1.1, "saturation": 1.2 },
+            { "type": "pad", "width": 1080, "height": 1920, "color": "black" },
+            { "type": "raw", "filter": "vignette" },
+            { "type": "format", "pixelFormat": "yuv420p" }
+        ]))
+        .expect("nodes");
+
+        assert_eq!(
+            build_filter_nodes(&nodes, 0.0).unwrap().join(","),
+            r"bwdif=mode=send_frame,crop=1920:800:0:140,transpose=clock,eq=contrast=1.1:saturation=1.2,pad=1080:1920:(ow-iw)/2:(oh-ih)/2:color=black,vignette,format=yuv420p"
+        );
+
+        let invalid = [VideoFilterNode::Eq {
+            brightness: Some(2.0),
+            contrast: None,
+            saturation: None,
+            gamma: None,
+        }];
+        assert_eq!(
+            build_filter_nodes(&invalid, 0.0).unwrap_err(),
+            "videoFilters[0]: eq.brightness must be between -1 and 1"
+        );
+    }
+}
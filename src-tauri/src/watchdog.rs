@@ -0,0 +1,162 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchdogTrip {
+    Timeout(Duration),
+    Stalled(Duration),
+}
+
+impl WatchdogTrip {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            WatchdogTrip::Timeout(_) => "timeout",
+            WatchdogTrip::Stalled(_) => "stalled",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            WatchdogTrip::Timeout(limit) => {
+                format!("任务运行超过 {} 秒，已强制终止", limit.as_secs_f64())
+            }
+            WatchdogTrip::Stalled(limit) => {
+                format!("ffmpeg 已 {} 秒没有进度推进，已强制终止", limit.as_secs_f64())
+            }
+        }
+    }
+}
+
+struct ProgressMark {
+    current_time_sec: Option<f64>,
+    advanced_at: Instant,
+}
+
+/// Tracks wall-clock runtime and progress advancement for one running job.
+pub struct Watchdog {
+    started_at: Instant,
+    timeout: Option<Duration>,
+    stall_timeout: Option<Duration>,
+    progress: Mutex<ProgressMark>,
+    tripped: Mutex<Option<WatchdogTrip>>,
+}
+
+fn positive_duration(seconds: Option<f64>) -> Option<Duration> {
+    let value = seconds?;
+    if !value.is_finite() || value <= 0.0 {
+        return None;
+    }
+
+    // A limit too long to represent can never be reached, so it means no limit.
+    Duration::try_from_secs_f64(value).ok()
+}
+
+impl Watchdog {
+    pub fn new(timeout_sec: Option<f64>, stall_timeout_sec: Option<f64>, started_at: Instant) -> Self {
+        Self {
+            started_at,
+            timeout: positive_duration(timeout_sec),
+            stall_timeout: positive_duration(stall_timeout_sec),
+            progress: Mutex::new(ProgressMark {
+                current_time_sec: None,
+                advanced_at: started_at,
+            }),
+            tripped: Mutex::new(None),
+        }
+    }
+
+    pub fn record_progress(&self, current_time_sec: f64, now: Instant) {
+        let Ok(mut mark) = self.progress.lock() else {
+            return;
+        };
+
+        let advanced = match mark.current_time_sec {
+            Some(previous) => current_time_sec > previous,
+            None => true,
+        };
+
+        if advanced {
+            mark.current_time_sec = Some(current_time_sec);
+            mark.advanced_at = now;
+        }
+    }
+
+    /// Starts stall tracking over, for a new pass whose progress time begins at zero again.
+    pub fn reset_progress(&self, now: Instant) {
+        if let Ok(mut mark) = self.progress.lock() {
+            mark.current_time_sec = None;
+            mark.advanced_at = now;
+        }
+    }
+
+    /// Returns the trip the first time a limit is exceeded; later calls return `None`.
+    pub fn check(&self, now: Instant) -> Option<WatchdogTrip> {
+        let mut tripped = self.tripped.lock().ok()?;
+        if tripped.is_some() {
+            return None;
+        }
+
+        let verdict = self
+            .timeout
+            .filter(|limit| now.saturating_duration_since(self.started_at) >= *limit)
+            .map(WatchdogTrip::Timeout)
+            .or_else(|| {
+                let limit = self.stall_timeout?;
+                let advanced_at = self.progress.lock().ok()?.advanced_at;
+                if now.saturating_duration_since(advanced_at) >= limit {
+                    Some(WatchdogTrip::Stalled(limit))
+                } else {
+                    None
+                }
+            });
+
+        *tripped = verdict;
+        verdict
+    }
+
+    pub fn tripped(&self) -> Option<WatchdogTrip> {
+        self.tripped.lock().ok().and_then(|tripped| *tripped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_trips_on_wall_clock_timeout() {
+        let start = Instant::now();
+        assert!(Watchdog::new(Some(1e300), None, start).check(start + Duration::from_secs(86_400)).is_none());
+        let watchdog = Watchdog::new(Some(10.0), None, start);
+
+        assert_eq!(watchdog.check(start + Duration::from_secs(9)), None);
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(10)),
+            Some(WatchdogTrip::Timeout(Duration::from_secs(10)))
+        );
+        assert_eq!(watchdog.check(start + Duration::from_secs(11)), None);
+        assert_eq!(watchdog.tripped().map(|trip| trip.reason()), Some("timeout"));
+    }
+
+    #[test]
+    fn watchdog_stall_resets_only_when_progress_advances() {
+        let start = Instant::now();
+        let watchdog = Watchdog::new(None, Some(5.0), start);
+
+        watchdog.record_progress(1.0, start + Duration::from_secs(4));
+        assert_eq!(watchdog.check(start + Duration::from_secs(8)), None);
+
+        watchdog.record_progress(1.0, start + Duration::from_secs(8));
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(9)),
+            Some(WatchdogTrip::Stalled(Duration::from_secs(5)))
+        );
+    }
+
+    #[test]
+    fn watchdog_ignores_non_positive_limits() {
+        let start = Instant::now();
+        let watchdog = Watchdog::new(Some(0.0), Some(-1.0), start);
+        assert_eq!(watchdog.check(start + Duration::from_secs(3600)), None);
+    }
+}
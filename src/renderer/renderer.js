@@ -8,6 +8,8 @@ const els = {
   preset: document.querySelector('#preset'),
   startTime: document.querySelector('#startTime'),
   duration: document.querySelector('#duration'),
+  endTime: document.querySelector('#endTime'),
+  seekMode: document.querySelector('#seekMode'),
   overwrite: document.querySelector('#overwrite'),
   crf: document.querySelector('#crf'),
   speedPreset: document.querySelector('#speedPreset'),
@@ -418,6 +420,8 @@ function buildPayload() {
     preset: els.preset.value,
     startTime: textValue(els.startTime.value),
     duration: textValue(els.duration.value),
+    endTime: textValue(els.endTime.value),
+    seekMode: els.seekMode.value,
     overwrite: Boolean(els.overwrite.checked),
     crf: optionalNumber(els.crf.value),
     speedPreset: els.speedPreset.value,
@@ -627,6 +631,8 @@ const watchedInputs = [
   els.outputPath,
   els.startTime,
   els.duration,
+  els.endTime,
+  els.seekMode,
   els.crf,
   els.speedPreset,
   els.videoCodec,
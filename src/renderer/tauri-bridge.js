@@ -53,10 +53,29 @@ window.ffmpegShell = {
   pickOutput: (payload) => invokeCommand('pick_output', payload),
   suggestOutput: (payload) => invokeCommand('suggest_output', payload),
   probeInput: (payload) => invokeCommand('probe_input', payload),
+  detectCrop: (payload) => invokeCommand('detect_crop', payload),
+  generateThumbnails: (payload) => invokeCommand('generate_thumbnails', payload),
   preview: (payload) => invokeCommand('preview', payload),
+  listKeyframes: (payload) => invokeCommand('list_keyframes', payload),
+  detectDeadAir: (payload) => invokeCommand('detect_dead_air', payload),
+  detectScenes: (payload) => invokeCommand('detect_scenes', payload),
+  renderSample: (payload) => invokeCommand('render_sample', payload),
+  computeMetrics: (payload) => invokeCommand('compute_metrics', payload),
   run: (payload) => invokeCommand('run_ffmpeg', payload),
-  stop: () => invokeCommand('stop_ffmpeg'),
+  searchCrf: (payload) => invokeCommand('search_crf', payload),
+  stop: (payload) => invokeCommand('stop_ffmpeg', payload),
+  setJobPriority: (payload) => invokeCommand('set_job_priority', payload),
+  exportJob: (payload) => invokeCommand('export_job', payload),
+  importJob: (payload) => invokeCommand('import_job', payload),
+  importCommand: (payload) => invokeCommand('import_command', payload),
+  exportShellCommand: (payload) => invokeCommand('export_shell_command', payload),
+  saveShellScript: (payload) => invokeCommand('save_shell_script', payload),
+  getSettings: () => invokeCommand('get_settings'),
+  updateSettings: (payload) => invokeCommand('update_settings', payload),
   onState: (callback) => bindEvent('ffmpeg:state', callback),
   onProgress: (callback) => bindEvent('ffmpeg:progress', callback),
-  onLog: (callback) => bindEvent('ffmpeg:log', callback)
+  onLog: (callback) => bindEvent('ffmpeg:log', callback),
+  onResources: (callback) => bindEvent('ffmpeg:resources', callback),
+  onLoudness: (callback) => bindEvent('ffmpeg:loudness', callback),
+  onCrfSearch: (callback) => bindEvent('ffmpeg:crf-search', callback)
 };